@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::git_service::{GitAlias, Scope};
+use crate::group_service::{AliasGroup, GroupData};
+
+/// Aliases together with the groups they belong to, e.g. the current state,
+/// a teammate's export or a dotfiles repository.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AliasSet {
+    pub aliases: Vec<GitAlias>,
+    #[serde(default)]
+    pub groups: GroupData,
+}
+
+/// One alias in a diff. Groups are compared by name, since group IDs are
+/// generated per machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffEntry {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<GitAlias>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<GitAlias>,
+    pub old_groups: Vec<String>,
+    pub new_groups: Vec<String>,
+}
+
+/// Differences between two alias sets. An alias that changed in several ways
+/// is listed under each of them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AliasDiff {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub command_changed: Vec<DiffEntry>,
+    pub scope_changed: Vec<DiffEntry>,
+    pub regrouped: Vec<DiffEntry>,
+    /// Groups only the new set defines (by name).
+    pub groups_added: Vec<AliasGroup>,
+    /// Groups only the old set defines (by name).
+    pub groups_removed: Vec<AliasGroup>,
+}
+
+impl AliasDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.command_changed.is_empty()
+            && self.scope_changed.is_empty()
+            && self.regrouped.is_empty()
+            && self.groups_added.is_empty()
+            && self.groups_removed.is_empty()
+    }
+}
+
+/// An alias both sides changed differently since the base. `None` means the
+/// side deleted it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub name: String,
+    pub scope: Scope,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+/// Result of a three-way merge. Conflicting aliases keep our side in
+/// `merged` until the user picks one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeResult {
+    pub merged: AliasSet,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Identity of an alias within a set: git matches names case-insensitively,
+/// and the same name may be defined in several scopes.
+type Key = (String, Scope);
+
+fn key(alias: &GitAlias) -> Key {
+    (alias.name.to_lowercase(), alias.scope)
+}
+
+/// Names of the groups `alias_name` is assigned to in `groups`, sorted.
+fn group_names(groups: &GroupData, alias_name: &str) -> Vec<String> {
+    let ids = groups
+        .assignments
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(alias_name))
+        .flat_map(|(_, ids)| ids);
+    let names: BTreeSet<String> = ids
+        .filter_map(|id| groups.groups.iter().find(|g| &g.id == id))
+        .map(|g| g.name.clone())
+        .collect();
+    names.into_iter().collect()
+}
+
+/// Compares `old` with `new`. Aliases are matched by name and scope; an
+/// alias that only exists under another scope on the other side counts as
+/// moved (scope-changed) rather than removed and added.
+pub fn diff(old: &AliasSet, new: &AliasSet) -> AliasDiff {
+    let mut out = AliasDiff::default();
+    let entry = |o: Option<&GitAlias>, n: Option<&GitAlias>| {
+        let name = n.or(o).map(|a| a.name.clone()).unwrap_or_default();
+        DiffEntry {
+            old_groups: o
+                .map(|a| group_names(&old.groups, &a.name))
+                .unwrap_or_default(),
+            new_groups: n
+                .map(|a| group_names(&new.groups, &a.name))
+                .unwrap_or_default(),
+            name,
+            old: o.cloned(),
+            new: n.cloned(),
+        }
+    };
+
+    let mut unmatched_old: Vec<&GitAlias> = Vec::new();
+    let mut matched_new = vec![false; new.aliases.len()];
+    let mut pairs: Vec<(&GitAlias, &GitAlias)> = Vec::new();
+    for o in &old.aliases {
+        match new
+            .aliases
+            .iter()
+            .enumerate()
+            .find(|(i, n)| !matched_new[*i] && key(n) == key(o))
+        {
+            Some((i, n)) => {
+                matched_new[i] = true;
+                pairs.push((o, n));
+            }
+            None => unmatched_old.push(o),
+        }
+    }
+    // Same name under a different scope: the alias moved
+    for o in unmatched_old {
+        match new
+            .aliases
+            .iter()
+            .enumerate()
+            .find(|(i, n)| !matched_new[*i] && n.name.eq_ignore_ascii_case(&o.name))
+        {
+            Some((i, n)) => {
+                matched_new[i] = true;
+                pairs.push((o, n));
+            }
+            None => out.removed.push(entry(Some(o), None)),
+        }
+    }
+    for (i, n) in new.aliases.iter().enumerate() {
+        if !matched_new[i] {
+            out.added.push(entry(None, Some(n)));
+        }
+    }
+
+    for (o, n) in pairs {
+        let e = entry(Some(o), Some(n));
+        if o.command != n.command {
+            out.command_changed.push(e.clone());
+        }
+        if o.scope != n.scope || o.local_path != n.local_path {
+            out.scope_changed.push(e.clone());
+        }
+        if e.old_groups != e.new_groups {
+            out.regrouped.push(e);
+        }
+    }
+
+    let has_group = |set: &GroupData, name: &str| set.groups.iter().any(|g| g.name == name);
+    out.groups_added = new
+        .groups
+        .groups
+        .iter()
+        .filter(|g| !has_group(&old.groups, &g.name))
+        .cloned()
+        .collect();
+    out.groups_removed = old
+        .groups
+        .groups
+        .iter()
+        .filter(|g| !has_group(&new.groups, &g.name))
+        .cloned()
+        .collect();
+    out
+}
+
+/// Picks the three-way merge of one value: a side's change wins over an
+/// unchanged side, and `None` marks a conflict when both changed it differently.
+fn merge_value<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours.clone())
+    } else if ours == base {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Merges `ours` and `theirs`, both derived from `base`. Commands are merged
+/// per alias (name and scope), group membership per group name, and group
+/// definitions by name. When both sides changed an alias differently it is
+/// reported as a conflict and our version is kept.
+pub fn merge(base: &AliasSet, ours: &AliasSet, theirs: &AliasSet) -> MergeResult {
+    let index = |set: &AliasSet| -> BTreeMap<Key, GitAlias> {
+        set.aliases.iter().map(|a| (key(a), a.clone())).collect()
+    };
+    let (base_map, ours_map, theirs_map) = (index(base), index(ours), index(theirs));
+
+    // Keep our order, then append aliases only the other side knows about
+    let mut keys: Vec<Key> = Vec::new();
+    for alias in ours
+        .aliases
+        .iter()
+        .chain(&theirs.aliases)
+        .chain(&base.aliases)
+    {
+        if !keys.contains(&key(alias)) {
+            keys.push(key(alias));
+        }
+    }
+
+    let mut result = MergeResult::default();
+    let mut memberships: BTreeMap<String, (String, BTreeSet<String>)> = BTreeMap::new();
+    for k in keys {
+        let (b, o, t) = (base_map.get(&k), ours_map.get(&k), theirs_map.get(&k));
+        let command = |a: Option<&GitAlias>| a.map(|a| a.command.clone());
+        let merged = match merge_value(&command(b), &command(o), &command(t)) {
+            Some(winner) if winner == command(o) => o.cloned(),
+            Some(_) => t.cloned(),
+            None => {
+                let any = o.or(t).or(b).map(|a| a.name.clone()).unwrap_or_default();
+                result.conflicts.push(MergeConflict {
+                    name: any,
+                    scope: k.1,
+                    base: command(b),
+                    ours: command(o),
+                    theirs: command(t),
+                });
+                o.cloned()
+            }
+        };
+        let Some(alias) = merged else {
+            continue;
+        };
+
+        let groups_of = |set: &AliasSet| -> BTreeSet<String> {
+            group_names(&set.groups, &alias.name).into_iter().collect()
+        };
+        let (gb, go, gt) = (groups_of(base), groups_of(ours), groups_of(theirs));
+        let names: BTreeSet<String> = gb.iter().chain(&go).chain(&gt).cloned().collect();
+        let groups = names
+            .into_iter()
+            .filter(|g| {
+                merge_value(&gb.contains(g), &go.contains(g), &gt.contains(g)).unwrap_or(true)
+            })
+            .collect();
+        memberships
+            .entry(alias.name.to_lowercase())
+            .or_insert_with(|| (alias.name.clone(), groups));
+        result.merged.aliases.push(alias);
+    }
+
+    result.merged.groups = merge_groups(base, ours, theirs, &memberships);
+    result
+}
+
+/// Merges group definitions by name and rebuilds the assignments from the
+/// merged memberships. Groups keep our ID when we have them.
+fn merge_groups(
+    base: &AliasSet,
+    ours: &AliasSet,
+    theirs: &AliasSet,
+    memberships: &BTreeMap<String, (String, BTreeSet<String>)>,
+) -> GroupData {
+    let find = |set: &AliasSet, name: &str| -> Option<AliasGroup> {
+        set.groups.groups.iter().find(|g| g.name == name).cloned()
+    };
+    let mut names: Vec<String> = Vec::new();
+    for group in ours
+        .groups
+        .groups
+        .iter()
+        .chain(&theirs.groups.groups)
+        .chain(&base.groups.groups)
+    {
+        if !names.contains(&group.name) {
+            names.push(group.name.clone());
+        }
+    }
+
+    let mut data = GroupData::default();
+    for name in names {
+        let (b, o, t) = (find(base, &name), find(ours, &name), find(theirs, &name));
+        let color = |g: &Option<AliasGroup>| g.as_ref().map(|g| g.color.clone());
+        let merged_color =
+            merge_value(&color(&b), &color(&o), &color(&t)).unwrap_or_else(|| color(&o));
+        // A group still used by a merged alias stays, even if one side deleted it
+        let in_use = memberships
+            .values()
+            .any(|(_, groups)| groups.contains(&name));
+        let group = match merged_color {
+            Some(color) => o.or(t).map(|g| AliasGroup { color, ..g }),
+            None if in_use => o.or(t).or(b),
+            None => None,
+        };
+        if let Some(group) = group {
+            data.groups.push(group);
+        }
+    }
+
+    for (alias_name, groups) in memberships.values() {
+        let ids: Vec<String> = data
+            .groups
+            .iter()
+            .filter(|g| groups.contains(&g.name))
+            .map(|g| g.id.clone())
+            .collect();
+        if !ids.is_empty() {
+            data.assignments.insert(alias_name.clone(), ids);
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alias(name: &str, command: &str, scope: Scope) -> GitAlias {
+        GitAlias {
+            name: name.to_string(),
+            command: command.to_string(),
+            scope,
+            local_path: None,
+            score: None,
+            origin: None,
+            include_chain: Vec::new(),
+        }
+    }
+
+    fn set(aliases: Vec<GitAlias>, groups: &[(&str, &str, &[&str])]) -> AliasSet {
+        let mut data = GroupData::default();
+        for (id, name, members) in groups {
+            data.groups.push(AliasGroup {
+                id: id.to_string(),
+                name: name.to_string(),
+                color: "#fff".to_string(),
+            });
+            for member in *members {
+                data.assignments
+                    .entry(member.to_string())
+                    .or_default()
+                    .push(id.to_string());
+            }
+        }
+        AliasSet {
+            aliases,
+            groups: data,
+        }
+    }
+
+    fn names(entries: &[DiffEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn diff_classifies_changes() {
+        let old = set(
+            vec![
+                alias("co", "checkout", Scope::Global),
+                alias("st", "status", Scope::Global),
+                alias("lg", "log --graph", Scope::Global),
+                alias("br", "branch", Scope::Global),
+            ],
+            &[("g-1", "History", &["lg"])],
+        );
+        // Group IDs differ between machines; only names matter
+        let new = set(
+            vec![
+                alias("co", "switch", Scope::Global),
+                alias("st", "status", Scope::Local),
+                alias("lg", "log --graph", Scope::Global),
+                alias("ci", "commit", Scope::Global),
+            ],
+            &[("x-9", "History", &[]), ("x-2", "Daily", &["lg", "ci"])],
+        );
+
+        let d = diff(&old, &new);
+        assert_eq!(names(&d.added), ["ci"]);
+        assert_eq!(d.added[0].new_groups, ["Daily"]);
+        assert_eq!(names(&d.removed), ["br"]);
+        assert_eq!(names(&d.command_changed), ["co"]);
+        assert_eq!(names(&d.scope_changed), ["st"]);
+        assert_eq!(names(&d.regrouped), ["lg"]);
+        assert_eq!(d.regrouped[0].old_groups, ["History"]);
+        assert_eq!(d.regrouped[0].new_groups, ["Daily"]);
+        assert_eq!(d.groups_added.len(), 1);
+        assert!(d.groups_removed.is_empty());
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn merge_takes_each_sides_changes() {
+        let base = set(
+            vec![
+                alias("co", "checkout", Scope::Global),
+                alias("st", "status", Scope::Global),
+                alias("br", "branch", Scope::Global),
+            ],
+            &[("g-1", "Daily", &["co"])],
+        );
+        let ours = set(
+            vec![
+                alias("co", "switch", Scope::Global),
+                alias("st", "status", Scope::Global),
+                alias("br", "branch", Scope::Global),
+                alias("lg", "log --oneline", Scope::Global),
+            ],
+            &[("g-1", "Daily", &["co", "lg"])],
+        );
+        let theirs = set(
+            vec![
+                alias("co", "checkout", Scope::Global),
+                alias("st", "status -sb", Scope::Global),
+                alias("ci", "commit", Scope::Global),
+            ],
+            &[("t-1", "Daily", &["co", "ci"])],
+        );
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        let merged: Vec<(&str, &str)> = result
+            .merged
+            .aliases
+            .iter()
+            .map(|a| (a.name.as_str(), a.command.as_str()))
+            .collect();
+        assert_eq!(
+            merged,
+            [
+                ("co", "switch"),
+                ("st", "status -sb"),
+                ("lg", "log --oneline"),
+                ("ci", "commit")
+            ]
+        );
+        let groups = &result.merged.groups;
+        assert_eq!(groups.groups.len(), 1);
+        assert_eq!(groups.groups[0].id, "g-1");
+        for name in ["co", "lg", "ci"] {
+            assert_eq!(groups.assignments[name], ["g-1"]);
+        }
+    }
+
+    #[test]
+    fn merge_reports_conflicts_and_keeps_ours() {
+        let base = set(vec![alias("co", "checkout", Scope::Global)], &[]);
+        let ours = set(vec![alias("co", "switch", Scope::Global)], &[]);
+        let theirs = set(vec![alias("co", "checkout -b", Scope::Global)], &[]);
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].theirs.as_deref(), Some("checkout -b"));
+        assert_eq!(result.merged.aliases[0].command, "switch");
+
+        // Deleted on one side, edited on the other
+        let deleted = set(Vec::new(), &[]);
+        let result = merge(&base, &deleted, &theirs);
+        assert_eq!(result.conflicts[0].ours, None);
+        assert!(result.merged.aliases.is_empty());
+    }
+}
@@ -0,0 +1,737 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::GamError;
+use crate::git_environment::GitEnvironment;
+
+/// Commands that ship with git, built in or installed in its exec-path.
+/// Git ignores an alias with one of these names.
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    "add",
+    "add--interactive",
+    "am",
+    "annotate",
+    "apply",
+    "archimport",
+    "archive",
+    "backfill",
+    "bisect",
+    "bisect--helper",
+    "blame",
+    "branch",
+    "bugreport",
+    "bundle",
+    "cat-file",
+    "check-attr",
+    "check-ignore",
+    "check-mailmap",
+    "check-ref-format",
+    "checkout",
+    "checkout--worker",
+    "checkout-index",
+    "cherry",
+    "cherry-pick",
+    "citool",
+    "clean",
+    "clone",
+    "column",
+    "commit",
+    "commit-graph",
+    "commit-tree",
+    "config",
+    "count-objects",
+    "credential",
+    "credential-cache",
+    "credential-cache--daemon",
+    "credential-store",
+    "cvsexportcommit",
+    "cvsimport",
+    "cvsserver",
+    "daemon",
+    "describe",
+    "diagnose",
+    "diff",
+    "diff-files",
+    "diff-index",
+    "diff-pairs",
+    "diff-tree",
+    "difftool",
+    "difftool--helper",
+    "env--helper",
+    "fast-export",
+    "fast-import",
+    "fetch",
+    "fetch-pack",
+    "filter-branch",
+    "fmt-merge-msg",
+    "for-each-ref",
+    "for-each-repo",
+    "format-patch",
+    "fsck",
+    "fsck-objects",
+    "fsmonitor--daemon",
+    "gc",
+    "get-tar-commit-id",
+    "grep",
+    "gui",
+    "hash-object",
+    "help",
+    "hook",
+    "http-backend",
+    "http-fetch",
+    "http-push",
+    "imap-send",
+    "index-pack",
+    "init",
+    "init-db",
+    "instaweb",
+    "interpret-trailers",
+    "log",
+    "ls-files",
+    "ls-remote",
+    "ls-tree",
+    "mailinfo",
+    "mailsplit",
+    "maintenance",
+    "merge",
+    "merge-base",
+    "merge-file",
+    "merge-index",
+    "merge-octopus",
+    "merge-one-file",
+    "merge-ours",
+    "merge-recursive",
+    "merge-resolve",
+    "merge-subtree",
+    "merge-tree",
+    "mergetool",
+    "mergetool--lib",
+    "mktag",
+    "mktree",
+    "multi-pack-index",
+    "mv",
+    "name-rev",
+    "notes",
+    "p4",
+    "pack-objects",
+    "pack-redundant",
+    "pack-refs",
+    "patch-id",
+    "prune",
+    "prune-packed",
+    "pull",
+    "push",
+    "quiltimport",
+    "range-diff",
+    "read-tree",
+    "rebase",
+    "receive-pack",
+    "reflog",
+    "refs",
+    "remote",
+    "remote-ext",
+    "remote-fd",
+    "remote-ftp",
+    "remote-ftps",
+    "remote-http",
+    "remote-https",
+    "repack",
+    "replace",
+    "replay",
+    "request-pull",
+    "rerere",
+    "reset",
+    "restore",
+    "rev-list",
+    "rev-parse",
+    "revert",
+    "rm",
+    "send-email",
+    "send-pack",
+    "sh-i18n",
+    "sh-i18n--envsubst",
+    "sh-setup",
+    "shell",
+    "shortlog",
+    "show",
+    "show-branch",
+    "show-index",
+    "show-ref",
+    "sparse-checkout",
+    "stage",
+    "stash",
+    "status",
+    "stripspace",
+    "submodule",
+    "submodule--helper",
+    "subtree",
+    "svn",
+    "switch",
+    "symbolic-ref",
+    "tag",
+    "unpack-file",
+    "unpack-objects",
+    "update-index",
+    "update-ref",
+    "update-server-info",
+    "upload-archive",
+    "upload-pack",
+    "var",
+    "verify-commit",
+    "verify-pack",
+    "verify-tag",
+    "version",
+    "web--browse",
+    "whatchanged",
+    "worktree",
+    "write-tree",
+];
+
+/// Git global options that consume the following word as their value.
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-C",
+    "-c",
+    "--git-dir",
+    "--work-tree",
+    "--namespace",
+    "--config-env",
+    "--super-prefix",
+    "--exec-path",
+    "--list-cmds",
+    "--attr-source",
+];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_COMMANDS.contains(&name)
+}
+
+/// Looks for a `git-<name>` executable in git's exec-path, then on `PATH`,
+/// like git does. Git runs such a command instead of an alias with the same
+/// name.
+pub fn find_external_command(name: &str) -> Option<PathBuf> {
+    let file = format!("git-{}", name);
+    GitEnvironment::current()
+        .exec_path
+        .and_then(|dir| executable_in(&dir, &file))
+        .or_else(|| find_executable(&file))
+}
+
+/// Looks for an executable called `file` on `PATH`.
+pub fn find_executable(file: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| executable_in(&dir, file))
+}
+
+fn executable_in(dir: &Path, file: &str) -> Option<PathBuf> {
+    let candidate = dir.join(file);
+    if candidate.is_file() {
+        return Some(candidate);
+    }
+    if cfg!(target_os = "windows") {
+        let exe = dir.join(format!("{}.exe", file));
+        if exe.is_file() {
+            return Some(exe);
+        }
+    }
+    None
+}
+
+/// Index of the subcommand word in `words`, skipping git's global options.
+pub fn subcommand_index<S: AsRef<str>>(words: &[S]) -> Option<usize> {
+    let mut i = 0;
+    while let Some(word) = words.get(i).map(|w| w.as_ref()) {
+        if OPTIONS_WITH_VALUE.contains(&word) {
+            i += 2;
+        } else if word.starts_with('-') {
+            i += 1;
+        } else {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// The git command an alias expands to, e.g. `log` for `-c color.ui=never log -p`.
+/// Shell aliases (`!…`) don't expand to a git command and return `None`.
+pub fn alias_target(command: &str) -> Option<&str> {
+    if command.trim_start().starts_with('!') {
+        return None;
+    }
+    let words: Vec<&str> = command.split_whitespace().collect();
+    subcommand_index(&words).map(|i| words[i])
+}
+
+/// Splits an alias value into words the way git's `split_cmdline` does:
+/// whitespace separates words, quotes group them and `\\` escapes a character.
+pub fn split_cmdline(command: &str) -> Result<Vec<String>, GamError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (_, '\\') if quote != Some('\'') => {
+                let escaped = chars.next().ok_or_else(|| {
+                    GamError::Invalid("Alias ends with a trailing backslash".to_string())
+                })?;
+                word.push(escaped);
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+        if !word.is_empty() {
+            in_word = true;
+        }
+    }
+
+    if quote.is_some() {
+        return Err(GamError::Invalid("Alias contains an unclosed quote".to_string()));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Quotes `word` for a POSIX shell, leaving plain words untouched.
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,^".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Substitutes `$1`…`$9`, `${N}`, `$@`, `$*` and `$#` in a shell alias with
+/// `args`, the way `sh` would when git runs it. Function bodies are left as
+/// they are: there these refer to the function's own arguments.
+fn substitute_positional(script: &str, args: &[String]) -> String {
+    let joined = |quote: bool| {
+        args.iter()
+            .map(|a| if quote { shell_quote(a) } else { a.clone() })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let arg = |n: usize, quote: bool| match args.get(n.wrapping_sub(1)) {
+        Some(a) if quote => shell_quote(a),
+        Some(a) => a.clone(),
+        None => String::new(),
+    };
+
+    let mut out = String::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    // Just after `name()`, and inside a body as `(open, close, depth)`
+    let mut declared = false;
+    let mut body: Option<(char, char, usize)> = None;
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !in_single && !in_double {
+            match body.as_mut() {
+                Some((open, close, depth)) => {
+                    if c == *open {
+                        *depth += 1;
+                    } else if c == *close {
+                        *depth -= 1;
+                    }
+                    if *depth == 0 {
+                        body = None;
+                    }
+                }
+                None if declared && (c == '{' || c == '(') => {
+                    body = Some((c, if c == '{' { '}' } else { ')' }, 1));
+                    declared = false;
+                }
+                None if c == '(' => {
+                    declared = chars.clone().find(|c| !c.is_whitespace()) == Some(')');
+                }
+                None if c != ')' && !c.is_whitespace() => declared = false,
+                None => {}
+            }
+        }
+        match c {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single && body.is_none() => {
+                // "$@" expands to one properly quoted word per argument.
+                let rest: String = chars.clone().take(3).collect();
+                if !in_double && rest == "$@\"" {
+                    chars.nth(2);
+                    out.push_str(&joined(true));
+                    continue;
+                }
+                in_double = !in_double;
+            }
+            '"' if !in_single => in_double = !in_double,
+            '$' if !in_single && body.is_none() => {
+                let quote = !in_double;
+                match chars.peek().copied() {
+                    Some(d @ '1'..='9') => {
+                        chars.next();
+                        out.push_str(&arg(d as usize - '0' as usize, quote));
+                        continue;
+                    }
+                    Some('{') => {
+                        let digits: String = chars
+                            .clone()
+                            .skip(1)
+                            .take_while(|c| c.is_ascii_digit())
+                            .collect();
+                        let closed = chars.clone().nth(digits.len() + 1) == Some('}');
+                        if let (Ok(n), true) = (digits.parse::<usize>(), closed) {
+                            chars.nth(digits.len() + 1);
+                            out.push_str(&arg(n, quote));
+                            continue;
+                        }
+                    }
+                    Some('@' | '*') => {
+                        chars.next();
+                        out.push_str(&joined(quote));
+                        continue;
+                    }
+                    Some('#') => {
+                        chars.next();
+                        out.push_str(&args.len().to_string());
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The fully expanded form of an alias invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasExpansion {
+    /// Aliases expanded along the way, outermost first.
+    pub chain: Vec<String>,
+    /// Whether the expansion ends in a `!` shell alias.
+    pub shell: bool,
+    /// The command as it will run, e.g. `git log --oneline -5 main`.
+    pub command_line: String,
+}
+
+/// How an alias' first word is resolved by git.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Builtin,
+    External(PathBuf),
+    Alias(String),
+    Unknown(String),
+}
+
+/// Alias-to-alias expansion graph over a set of effective aliases.
+pub struct AliasGraph {
+    commands: HashMap<String, String>,
+}
+
+impl AliasGraph {
+    pub fn new(aliases: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            commands: aliases
+                .into_iter()
+                .map(|(name, command)| (name.to_lowercase(), command))
+                .collect(),
+        }
+    }
+
+    pub fn command(&self, name: &str) -> Option<&str> {
+        self.commands.get(&name.to_lowercase()).map(|c| c.as_str())
+    }
+
+    /// Resolves what git runs for the first word of `name`'s expansion,
+    /// in git's order: built-ins, then `git-*` executables, then aliases.
+    pub fn target(&self, name: &str) -> Option<Target> {
+        let word = alias_target(self.command(name)?)?;
+        Some(if is_builtin(word) {
+            Target::Builtin
+        } else if let Some(path) = find_external_command(word) {
+            Target::External(path)
+        } else if self.commands.contains_key(&word.to_lowercase()) {
+            Target::Alias(word.to_lowercase())
+        } else {
+            Target::Unknown(word.to_string())
+        })
+    }
+
+    /// Follows alias-to-alias expansion from `start` and returns the chain up
+    /// to the first repeated alias if the expansion never terminates.
+    pub fn find_cycle(&self, start: &str) -> Option<Vec<String>> {
+        let mut chain = vec![start.to_lowercase()];
+        let mut current = start.to_lowercase();
+        while let Some(Target::Alias(next)) = self.target(&current) {
+            let looped = chain.contains(&next);
+            chain.push(next.clone());
+            if looped {
+                return Some(chain);
+            }
+            current = next;
+        }
+        None
+    }
+
+    /// The first `!` shell alias running `name` leads to, if any, following
+    /// alias-to-alias expansion.
+    pub fn shell_alias(&self, name: &str) -> Option<String> {
+        let mut chain = vec![name.to_lowercase()];
+        loop {
+            let current = chain.last()?;
+            if self.command(current)?.trim_start().starts_with('!') {
+                return Some(current.clone());
+            }
+            match self.target(current)? {
+                Target::Alias(next) if !chain.contains(&next) => chain.push(next),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Expands an invocation such as `git lg -5 main` the way git would:
+    /// aliases are replaced recursively, the caller's arguments are appended
+    /// and shell aliases receive them as positional parameters.
+    pub fn expand(&self, invocation: &str) -> Result<AliasExpansion, GamError> {
+        let mut argv = split_cmdline(invocation)?;
+        if argv.first().is_some_and(|w| w == "git") {
+            argv.remove(0);
+        }
+        let mut chain: Vec<String> = Vec::new();
+
+        while let Some(i) = subcommand_index(&argv) {
+            let word = argv[i].as_str();
+            if is_builtin(word) || find_external_command(word).is_some() {
+                break;
+            }
+            let key = word.to_lowercase();
+            let Some(command) = self.command(&key) else {
+                return Err(GamError::Invalid(format!(
+                    "\"{}\" is neither a git command nor an alias",
+                    word
+                )));
+            };
+            if chain.contains(&key) {
+                chain.push(key);
+                return Err(GamError::Invalid(format!(
+                    "Alias loop detected: {}",
+                    chain.join(" -> ")
+                )));
+            }
+            chain.push(key);
+
+            let args = argv.split_off(i + 1);
+            if let Some(script) = command.trim_start().strip_prefix('!') {
+                let mut line = substitute_positional(script, &args);
+                for arg in &args {
+                    line.push(' ');
+                    line.push_str(&shell_quote(arg));
+                }
+                return Ok(AliasExpansion {
+                    chain,
+                    shell: true,
+                    command_line: line,
+                });
+            }
+            argv.pop();
+            argv.extend(split_cmdline(command)?);
+            argv.extend(args);
+        }
+
+        let command_line = std::iter::once("git".to_string())
+            .chain(argv.iter().map(|w| shell_quote(w)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(AliasExpansion {
+            chain,
+            shell: false,
+            command_line,
+        })
+    }
+
+    /// The word `name` expands to when it is neither a git command nor an alias.
+    pub fn dead_reference(&self, name: &str) -> Option<String> {
+        match self.target(name)? {
+            Target::Unknown(word) => Some(word),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(pairs: &[(&str, &str)]) -> AliasGraph {
+        AliasGraph::new(pairs.iter().map(|(n, c)| (n.to_string(), c.to_string())))
+    }
+
+    #[test]
+    fn recognizes_builtins() {
+        assert!(is_builtin("status"));
+        assert!(is_builtin("cherry-pick"));
+        assert!(is_builtin("submodule"));
+        assert!(is_builtin("mergetool"));
+        assert!(!is_builtin("co"));
+    }
+
+    #[test]
+    fn alias_target_skips_global_options() {
+        assert_eq!(alias_target("log --oneline"), Some("log"));
+        assert_eq!(
+            alias_target("-c color.ui=never --no-pager lg -5"),
+            Some("lg")
+        );
+        assert_eq!(alias_target("-C ../other st"), Some("st"));
+        assert_eq!(alias_target("!git log | head"), None);
+        assert_eq!(alias_target("--no-pager"), None);
+    }
+
+    #[test]
+    fn target_prefers_builtins_over_aliases() {
+        let g = graph(&[("log", "lg"), ("lg", "log --oneline"), ("st", "status")]);
+        assert_eq!(g.target("lg"), Some(Target::Builtin));
+        assert_eq!(g.target("st"), Some(Target::Builtin));
+        assert_eq!(g.target("missing"), None);
+    }
+
+    #[test]
+    fn find_cycle_reports_loops() {
+        let g = graph(&[
+            ("a", "b"),
+            ("b", "c --all"),
+            ("c", "a"),
+            ("d", "d -v"),
+            ("e", "b"),
+        ]);
+        assert_eq!(
+            g.find_cycle("a"),
+            Some(vec!["a".into(), "b".into(), "c".into(), "a".into()])
+        );
+        assert_eq!(g.find_cycle("d"), Some(vec!["d".into(), "d".into()]));
+        assert_eq!(
+            g.find_cycle("e"),
+            Some(vec![
+                "e".into(),
+                "b".into(),
+                "c".into(),
+                "a".into(),
+                "b".into()
+            ])
+        );
+    }
+
+    #[test]
+    fn find_cycle_ignores_terminating_chains() {
+        let g = graph(&[("lg", "log --graph"), ("lga", "lg --all"), ("sh", "!lga")]);
+        assert_eq!(g.find_cycle("lga"), None);
+        assert_eq!(g.find_cycle("sh"), None);
+    }
+
+    #[test]
+    fn dead_reference_finds_unknown_targets() {
+        let g = graph(&[
+            ("x", "gamnosuchcommand --flag"),
+            ("y", "x"),
+            ("z", "status"),
+        ]);
+        assert_eq!(g.dead_reference("x"), Some("gamnosuchcommand".to_string()));
+        assert_eq!(g.dead_reference("y"), None);
+        assert_eq!(g.dead_reference("z"), None);
+    }
+
+    #[test]
+    fn split_cmdline_handles_quotes_and_escapes() {
+        assert_eq!(
+            split_cmdline(r#"log --format="%h %s" 'a b'c \"x"#).unwrap(),
+            vec!["log", "--format=%h %s", "a bc", "\"x"]
+        );
+        assert_eq!(
+            split_cmdline(r#"commit -m """#).unwrap(),
+            vec!["commit", "-m", ""]
+        );
+        assert!(split_cmdline("log 'oops").is_err());
+    }
+
+    #[test]
+    fn expand_follows_alias_chains_and_appends_args() {
+        let g = graph(&[("lg", "log --oneline --graph"), ("lga", "lg --all")]);
+        let e = g.expand("git lga -5 main").unwrap();
+        assert_eq!(e.command_line, "git log --oneline --graph --all -5 main");
+        assert_eq!(e.chain, vec!["lga", "lg"]);
+        assert!(!e.shell);
+
+        let e = g.expand("--no-pager lg 'two words'").unwrap();
+        assert_eq!(
+            e.command_line,
+            "git --no-pager log --oneline --graph 'two words'"
+        );
+    }
+
+    #[test]
+    fn expand_substitutes_shell_alias_arguments() {
+        let g = graph(&[
+            ("nb", "!f() { git checkout -b \"$1\" origin/$2; }; f"),
+            ("each", "!echo $# \"$@\""),
+        ]);
+        let e = g.expand("git nb feat main").unwrap();
+        assert!(e.shell);
+        assert_eq!(
+            e.command_line,
+            "f() { git checkout -b \"$1\" origin/$2; }; f feat main"
+        );
+
+        let e = g.expand("each 'a b' c").unwrap();
+        assert_eq!(e.command_line, "echo 2 'a b' c 'a b' c");
+    }
+
+    #[test]
+    fn expand_leaves_function_arguments_alone() {
+        // git runs `f x a`, so inside f `$2` is `a`
+        let g = graph(&[
+            ("lx", "!f() { git log \"$2\" ${1}; }; f x"),
+            ("sub", "!f() ( cd \"$1\" && git status ); echo $1; f $2"),
+        ]);
+        let e = g.expand("lx a").unwrap();
+        assert_eq!(e.command_line, "f() { git log \"$2\" ${1}; }; f x a");
+
+        let e = g.expand("sub 'a b' c").unwrap();
+        assert_eq!(
+            e.command_line,
+            "f() ( cd \"$1\" && git status ); echo 'a b'; f c 'a b' c"
+        );
+    }
+
+    #[test]
+    fn expand_reports_loops_and_unknown_commands() {
+        let g = graph(&[("a", "b"), ("b", "a -v")]);
+        assert!(g.expand("a").unwrap_err().to_string().contains("a -> b -> a"));
+        assert!(g.expand("gamnosuchcommand").is_err());
+        assert_eq!(
+            g.expand("status -sb").unwrap().command_line,
+            "git status -sb"
+        );
+    }
+}
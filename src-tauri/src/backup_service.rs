@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::GamError;
+use crate::git_config::{self, FileChange};
+use crate::settings_service::SettingsService;
+
+/// Settings keys holding the retention policy.
+pub const KEEP_SETTING: &str = "backup_keep";
+pub const MAX_AGE_SETTING: &str = "backup_max_age_days";
+
+/// How many backups of each config file to keep, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Backups kept per file; 0 turns backups off.
+    pub keep: usize,
+    /// Backups older than this are removed; 0 keeps them regardless of age.
+    pub max_age_days: u64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            keep: 20,
+            max_age_days: 90,
+        }
+    }
+}
+
+/// A saved copy of a config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    /// `<file key>/<milliseconds>`, as accepted by `diff` and `restore`.
+    pub id: String,
+    /// The config file this is a copy of.
+    pub path: String,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    pub size: u64,
+}
+
+/// One line of a backup compared with the current file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "text", rename_all = "lowercase")]
+pub enum DiffLine {
+    Same(String),
+    /// Only in the current file.
+    Added(String),
+    /// Only in the backup.
+    Removed(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupDiff {
+    pub backup: Backup,
+    /// Whether the config file still exists.
+    pub current_exists: bool,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Copies config files into `backups/` in the app data directory before GAM
+/// overwrites them, pruning old copies by the retention policy.
+pub struct BackupService {
+    dir: PathBuf,
+    retention: Retention,
+}
+
+impl BackupService {
+    /// Backs up into the app data directory, keeping as many copies as
+    /// `settings` asks for.
+    pub fn new(settings: &SettingsService) -> Self {
+        let defaults = Retention::default();
+        let retention = Retention {
+            keep: settings
+                .get(KEEP_SETTING)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.keep),
+            max_age_days: settings
+                .get(MAX_AGE_SETTING)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_age_days),
+        };
+        Self::with_dir(backup_dir(), retention)
+    }
+
+    pub fn with_dir(dir: PathBuf, retention: Retention) -> Self {
+        Self { dir, retention }
+    }
+
+    pub fn retention(&self) -> Retention {
+        self.retention
+    }
+
+    pub fn set_retention(&mut self, retention: Retention) {
+        self.retention = retention;
+    }
+
+    /// Saves the `before` contents of each change that overwrites or removes
+    /// an existing file, then prunes that file's old backups.
+    pub fn snapshot(&self, changes: &[FileChange]) -> Result<(), GamError> {
+        if self.retention.keep == 0 {
+            return Ok(());
+        }
+        for change in changes {
+            let Some(contents) = &change.before else {
+                continue;
+            };
+            let key_dir = self.dir.join(file_key(&change.path));
+            let failed = |e: std::io::Error| GamError::File {
+                path: change.path.clone(),
+                message: format!("Could not back up {}: {}", change.path.display(), e),
+            };
+            fs::create_dir_all(&key_dir).map_err(failed)?;
+            fs::write(
+                key_dir.join("source"),
+                change.path.to_string_lossy().as_bytes(),
+            )
+            .map_err(failed)?;
+
+            let mut stamp = now_millis();
+            while key_dir.join(format!("{}.gitconfig", stamp)).exists() {
+                stamp += 1;
+            }
+            fs::write(key_dir.join(format!("{}.gitconfig", stamp)), contents).map_err(failed)?;
+            self.prune(&key_dir);
+        }
+        Ok(())
+    }
+
+    /// Backups of `path`, or of every file, newest first.
+    pub fn list(&self, path: Option<&str>) -> Vec<Backup> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut backups: Vec<Backup> = entries
+            .flatten()
+            .filter(|entry| path.is_none_or(|p| entry.file_name() == *file_key(Path::new(p))))
+            .flat_map(|entry| self.backups_in(&entry.path()))
+            .collect();
+        backups.sort_by(|a, b| b.id_stamp().cmp(&a.id_stamp()).then(a.path.cmp(&b.path)));
+        backups
+    }
+
+    /// Compares a backup (old) with the current contents of its file (new).
+    pub fn diff(&self, id: &str) -> Result<BackupDiff, GamError> {
+        let (backup, contents) = self.read(id)?;
+        let current = git_config::read_if_exists(Path::new(&backup.path))?
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+        Ok(BackupDiff {
+            current_exists: current.is_some(),
+            lines: diff_lines(&contents, current.as_deref().unwrap_or_default()),
+            backup,
+        })
+    }
+
+    /// Writes a backup over its config file, backing up the current contents
+    /// first. Returns the change, or nothing if the file already matches.
+    pub fn restore(&self, id: &str) -> Result<Vec<FileChange>, GamError> {
+        let (backup, contents) = self.read(id)?;
+        let path = PathBuf::from(&backup.path);
+        let change = FileChange {
+            before: git_config::read_if_exists(&path)?
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+            after: Some(contents),
+            path,
+        };
+        if change.before == change.after {
+            return Ok(Vec::new());
+        }
+        let changes = vec![change];
+        git_config::write_files(&changes, |changes| self.snapshot(changes))?;
+        Ok(changes)
+    }
+
+    fn read(&self, id: &str) -> Result<(Backup, String), GamError> {
+        let not_found = || GamError::NotFound(format!("backup {}", id));
+        let (key, stamp) = id.split_once('/').ok_or_else(not_found)?;
+        let valid = |part: &str| {
+            !part.is_empty()
+                && !part.starts_with('.')
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        };
+        if !valid(key) || !stamp.chars().all(|c| c.is_ascii_digit()) || stamp.is_empty() {
+            return Err(not_found());
+        }
+        let backup = self
+            .backups_in(&self.dir.join(key))
+            .into_iter()
+            .find(|b| b.id == id)
+            .ok_or_else(not_found)?;
+        let file = self.dir.join(key).join(format!("{}.gitconfig", stamp));
+        let contents = fs::read_to_string(&file).map_err(|e| GamError::file("read", &file, e))?;
+        Ok((backup, contents))
+    }
+
+    /// The backups in one file's directory.
+    fn backups_in(&self, key_dir: &Path) -> Vec<Backup> {
+        let (Some(key), Ok(source), Ok(entries)) = (
+            key_dir
+                .file_name()
+                .map(|k| k.to_string_lossy().into_owned()),
+            fs::read_to_string(key_dir.join("source")),
+            fs::read_dir(key_dir),
+        ) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let stamp: u64 = name.strip_suffix(".gitconfig")?.parse().ok()?;
+                Some(Backup {
+                    id: format!("{}/{}", key, stamp),
+                    path: source.clone(),
+                    created: stamp / 1000,
+                    size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    /// Removes the backups beyond `keep` and those older than `max_age_days`.
+    fn prune(&self, key_dir: &Path) {
+        let mut backups = self.backups_in(key_dir);
+        backups.sort_by_key(|b| std::cmp::Reverse(b.id_stamp()));
+        let cutoff = (self.retention.max_age_days > 0)
+            .then(|| now_millis().saturating_sub(self.retention.max_age_days * 86_400_000));
+        for (i, backup) in backups.iter().enumerate() {
+            // The newest backup survives the age limit
+            let expired = i > 0 && cutoff.is_some_and(|c| backup.id_stamp() < c);
+            if i >= self.retention.keep || expired {
+                let _ = fs::remove_file(key_dir.join(format!("{}.gitconfig", backup.id_stamp())));
+            }
+        }
+    }
+}
+
+impl Backup {
+    fn id_stamp(&self) -> u64 {
+        self.id
+            .rsplit_once('/')
+            .and_then(|(_, stamp)| stamp.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// Where backups go.
+fn backup_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("com.github.zintaen.gam")
+        .join("backups")
+}
+
+/// A directory name for the backups of `path`: its file name plus a hash of
+/// the full path, since many repositories have a `.git/config`.
+fn file_key(path: &Path) -> String {
+    let name: String = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // FNV-1a, which stays the same across Rust versions unlike `DefaultHasher`
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{}-{:016x}", name, hash)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Line diff from `old` to `new` through their longest common subsequence.
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let (a, b): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(DiffLine::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("gam_test_backup_{}_{}", std::process::id(), id));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn overwrite(path: &Path, after: &str) -> FileChange {
+        FileChange {
+            path: path.to_path_buf(),
+            before: fs::read_to_string(path).ok(),
+            after: Some(after.to_string()),
+        }
+    }
+
+    #[test]
+    fn snapshots_are_listed_diffed_and_restored() {
+        let dir = temp_dir();
+        let config = dir.join("gitconfig");
+        fs::write(&config, "[alias]\n\tco = checkout\n\tst = status\n").unwrap();
+        let backups = BackupService::with_dir(dir.join("backups"), Retention::default());
+
+        let change = overwrite(&config, "[alias]\n\tco = switch\n\tst = status\n");
+        git_config::write_files(&[change], |changes| backups.snapshot(changes)).unwrap();
+
+        let list = backups.list(config.to_str());
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].path, config.to_string_lossy());
+        assert!(backups.list(Some("/elsewhere/gitconfig")).is_empty());
+
+        let diff = backups.diff(&list[0].id).unwrap();
+        assert_eq!(
+            diff.lines,
+            vec![
+                DiffLine::Same("[alias]".to_string()),
+                DiffLine::Removed("\tco = checkout".to_string()),
+                DiffLine::Added("\tco = switch".to_string()),
+                DiffLine::Same("\tst = status".to_string()),
+            ]
+        );
+
+        let restored = backups.restore(&list[0].id).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "[alias]\n\tco = checkout\n\tst = status\n"
+        );
+        // Restoring backed up the overwritten contents as well
+        assert_eq!(backups.list(None).len(), 2);
+        assert!(backups.restore("../../etc/passwd").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prunes_beyond_the_retention_limit() {
+        let dir = temp_dir();
+        let config = dir.join("config");
+        fs::write(&config, "[alias]\n").unwrap();
+        let retention = Retention {
+            keep: 3,
+            max_age_days: 0,
+        };
+        let backups = BackupService::with_dir(dir.join("backups"), retention);
+        for _ in 0..5 {
+            backups.snapshot(&[overwrite(&config, "")]).unwrap();
+        }
+        assert_eq!(backups.list(None).len(), 3);
+
+        // New files aren't backed up, and keep = 0 turns backups off
+        backups
+            .snapshot(&[overwrite(&dir.join("new"), "")])
+            .unwrap();
+        let off = BackupService::with_dir(
+            dir.join("off"),
+            Retention {
+                keep: 0,
+                max_age_days: 0,
+            },
+        );
+        off.snapshot(&[overwrite(&config, "")]).unwrap();
+        assert_eq!(backups.list(None).len(), 3);
+        assert!(off.list(None).is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
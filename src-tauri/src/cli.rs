@@ -0,0 +1,669 @@
+use std::path::Path;
+
+use crate::error::GamError;
+use crate::export_schema;
+use crate::file_service::{ConflictResolution, ExportFormat, FileService, ImportChoice};
+use crate::git_environment::GitEnvironment;
+use crate::git_service::{GitService, Scope};
+use crate::group_service::{GroupData, GroupService};
+use crate::journal_service::{GroupChange, JournalService};
+use crate::settings_service::SettingsService;
+
+const USAGE: &str = "\
+Usage: gam [<folder>]
+       gam <command> [options]
+
+Commands:
+  list [--scope <scope>|all] [--repo <path>] [--json]
+  add <name> <command>... [--scope <scope>] [--repo <path>] [--group <name>]
+  rm <name> [--scope <scope>] [--repo <path>]
+  export <file> [--scope <scope>|all] [--repo <path>] [--format <format>] [--prefix <prefix>]
+  import <file|dir> [--scope <scope>] [--repo <path>] [--on-conflict skip|overwrite|rename]
+  schema
+  help
+  version
+
+Scopes: system, global (default), local, worktree. Repository scopes use
+--repo, or the current directory when it is omitted. Put options that belong
+to the alias command after `--`, or quote the whole command.
+
+Export formats: json (default), gitconfig, markdown, and zsh, bash or fish
+for `g<name>` shell shortcuts (--prefix changes the `g`). Without --format
+the file extension decides (.gitconfig/.inc, .md, .zsh, .bash/.sh, .fish).
+`schema` prints the JSON Schema of the json format.
+
+Import reads GAM exports (migrating older schema versions), gitconfig files
+and dotfiles repository folders; aliases that already exist with a different
+command are skipped unless --on-conflict says otherwise.";
+
+/// Every option; each subcommand takes some of them.
+const OPTIONS: &[&str] = &[
+    "--scope",
+    "--repo",
+    "--group",
+    "--format",
+    "--prefix",
+    "--json",
+    "--on-conflict",
+    "--overwrite",
+];
+
+/// Exit code for a command that ran but failed.
+const EXIT_FAILURE: i32 = 1;
+/// Exit code for a malformed command line.
+const EXIT_USAGE: i32 = 2;
+
+/// A headless `gam` invocation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliCommand {
+    List {
+        scope: Option<Scope>,
+        repo: Option<String>,
+        json: bool,
+    },
+    Add {
+        name: String,
+        command: String,
+        scope: Scope,
+        repo: Option<String>,
+        group: Option<String>,
+    },
+    Remove {
+        name: String,
+        scope: Scope,
+        repo: Option<String>,
+    },
+    Export {
+        file: String,
+        scope: Option<Scope>,
+        repo: Option<String>,
+        format: Option<ExportFormat>,
+        prefix: Option<String>,
+    },
+    Import {
+        file: String,
+        scope: Option<Scope>,
+        repo: Option<String>,
+        on_conflict: ConflictResolution,
+    },
+    Schema,
+    Help,
+    Version,
+}
+
+/// Runs `args` (without the program name) as a headless command and returns
+/// its exit code, or `None` when they don't name a subcommand and the GUI
+/// should start instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = match parse(args) {
+        Ok(Some(command)) => command,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("gam: {}\n\n{}", e, USAGE);
+            return Some(EXIT_USAGE);
+        }
+    };
+    let settings = SettingsService::new();
+    GitEnvironment::configure(&settings);
+    let mut services = Services {
+        git: GitService::new(&settings),
+        groups: GroupService::new(),
+        journal: JournalService::new(),
+    };
+    Some(match execute(command, &mut services) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("gam: {}", e);
+            EXIT_FAILURE
+        }
+    })
+}
+
+/// What a command reads and changes: the user's data when run from a shell,
+/// scratch copies in tests.
+struct Services {
+    git: GitService,
+    groups: GroupService,
+    journal: JournalService,
+}
+
+pub fn parse(args: &[String]) -> Result<Option<CliCommand>, String> {
+    let Some((sub, rest)) = args.split_first() else {
+        return Ok(None);
+    };
+    // The options each subcommand takes, and whether it takes arguments
+    let (allowed, takes_arguments): (&[&str], bool) = match sub.as_str() {
+        "list" | "ls" => (&["--scope", "--repo", "--json"], false),
+        "add" => (&["--scope", "--repo", "--group"], true),
+        "rm" | "remove" => (&["--scope", "--repo"], true),
+        "export" => (&["--scope", "--repo", "--format", "--prefix"], true),
+        "import" => (&["--scope", "--repo", "--on-conflict", "--overwrite"], true),
+        "schema" | "help" | "--help" | "-h" | "version" | "--version" | "-V" => (&[], false),
+        _ => return Ok(None),
+    };
+
+    let mut positional: Vec<String> = Vec::new();
+    let mut scope: Option<String> = None;
+    let mut repo: Option<String> = None;
+    let mut group: Option<String> = None;
+    let mut format: Option<String> = None;
+    let mut prefix: Option<String> = None;
+    let mut json = false;
+    let mut on_conflict: Option<String> = None;
+
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if flag.starts_with("--") && flag != "--" && !allowed.contains(&flag) {
+            let mut problem = if OPTIONS.contains(&flag) {
+                format!("{} doesn't take {}", sub, flag)
+            } else {
+                format!("unknown option {}", flag)
+            };
+            if sub == "add" {
+                problem.push_str("; options of the alias command go after `--`");
+            }
+            return Err(problem);
+        }
+        let mut value = |target: &mut Option<String>| -> Result<(), String> {
+            let v = inline
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", flag))?;
+            *target = Some(v);
+            Ok(())
+        };
+        match flag {
+            "--scope" => value(&mut scope)?,
+            "--repo" => value(&mut repo)?,
+            "--group" => value(&mut group)?,
+            "--format" => value(&mut format)?,
+            "--prefix" => value(&mut prefix)?,
+            "--json" => json = true,
+            "--on-conflict" => value(&mut on_conflict)?,
+            "--overwrite" => on_conflict = Some("overwrite".to_string()),
+            "--" => {
+                positional.extend(iter.by_ref().cloned());
+                break;
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if !takes_arguments && let Some(extra) = positional.first() {
+        return Err(format!("{} takes no arguments, got \"{}\"", sub, extra));
+    }
+
+    let parse_scope = |all_allowed: bool| -> Result<Option<Scope>, String> {
+        match scope.as_deref() {
+            None => Ok(None),
+            Some("all") if all_allowed => Ok(None),
+            Some(s) => s.parse().map(Some).map_err(String::from),
+        }
+    };
+    let one = |what: &str| -> Result<String, String> {
+        match positional.as_slice() {
+            [value] => Ok(value.clone()),
+            [] => Err(format!("{} requires a {}", sub, what)),
+            _ => Err(format!("{} takes a single {}", sub, what)),
+        }
+    };
+
+    let command = match sub.as_str() {
+        "list" | "ls" => CliCommand::List {
+            scope: parse_scope(true)?,
+            repo,
+            json,
+        },
+        "add" => {
+            let (name, command) = positional
+                .split_first()
+                .filter(|(_, command)| !command.is_empty())
+                .ok_or_else(|| "add requires a name and a command".to_string())?;
+            CliCommand::Add {
+                name: name.clone(),
+                command: command.join(" "),
+                scope: parse_scope(false)?.unwrap_or_default(),
+                repo,
+                group,
+            }
+        }
+        "rm" | "remove" => CliCommand::Remove {
+            name: one("alias name")?,
+            scope: parse_scope(false)?.unwrap_or_default(),
+            repo,
+        },
+        "export" => CliCommand::Export {
+            file: one("file")?,
+            scope: parse_scope(true)?,
+            repo,
+            format: format.as_deref().map(str::parse).transpose()?,
+            prefix,
+        },
+        "import" => CliCommand::Import {
+            file: one("file")?,
+            scope: parse_scope(false)?,
+            repo,
+            on_conflict: on_conflict
+                .as_deref()
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or_default(),
+        },
+        "schema" => CliCommand::Schema,
+        "help" | "--help" | "-h" => CliCommand::Help,
+        _ => CliCommand::Version,
+    };
+    Ok(Some(command))
+}
+
+fn execute(command: CliCommand, services: &mut Services) -> Result<(), String> {
+    match command {
+        CliCommand::Help => println!("{}", USAGE),
+        CliCommand::Version => {
+            println!("gam {}", env!("CARGO_PKG_VERSION"));
+            let git = GitEnvironment::current();
+            match (&git.version_output, &git.path) {
+                (Some(version), Some(path)) => println!("{} ({})", version, path.display()),
+                _ => return Err(git.error.unwrap_or_else(|| "git not found".to_string())),
+            }
+            for warning in &git.warnings {
+                eprintln!("gam: {}", warning);
+            }
+        }
+        CliCommand::Schema => {
+            let schema = serde_json::to_string_pretty(&export_schema::json_schema())
+                .map_err(|e| format!("Failed to serialize: {}", e))?;
+            println!("{}", schema);
+        }
+        CliCommand::List { scope, repo, json } => {
+            let git = &mut services.git;
+            select_repo(git, repo.as_deref(), scope)?;
+            let aliases = git.get_aliases(scope)?;
+            if json {
+                let out = serde_json::to_string_pretty(&aliases)
+                    .map_err(|e| format!("Failed to serialize: {}", e))?;
+                println!("{}", out);
+            } else {
+                for alias in &aliases {
+                    println!("{:<8} {} = {}", alias.scope, alias.name, alias.command);
+                }
+            }
+        }
+        CliCommand::Add {
+            name,
+            command,
+            scope,
+            repo,
+            group,
+        } => {
+            let git = &mut services.git;
+            select_repo(git, repo.as_deref(), Some(scope))?;
+            let groups = &mut services.groups;
+            let group_id = group.map(|g| find_group(groups, &g)).transpose()?;
+            let files = git.add_alias(&name, &command, scope, None)?;
+            let (group_change, group_error) = match group_id {
+                Some(id) => {
+                    let before = groups.get_data();
+                    let mut ids = groups.get_alias_groups(&name);
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                    match groups.set_alias_groups(&name, ids) {
+                        Ok(()) => {
+                            let after = groups.get_data();
+                            (Some(GroupChange { before, after }), None)
+                        }
+                        Err(e) => (None, Some(e)),
+                    }
+                }
+                None => (None, None),
+            };
+            // The alias is written either way, so it stays undoable
+            services.journal.record(&format!("Add alias \"{}\"", name), files, group_change)?;
+            if let Some(e) = group_error {
+                return Err(e.context("Alias added, but ").into());
+            }
+        }
+        CliCommand::Remove { name, scope, repo } => {
+            let git = &mut services.git;
+            select_repo(git, repo.as_deref(), Some(scope))?;
+            let files = git.delete_alias(&name, scope, None, None)?;
+            services.journal.record(&format!("Delete alias \"{}\"", name), files, None)?;
+        }
+        CliCommand::Export {
+            file,
+            scope,
+            repo,
+            format,
+            prefix,
+        } => {
+            let git = &mut services.git;
+            select_repo(git, repo.as_deref(), scope)?;
+            let aliases = git.get_aliases(scope)?;
+            let group_data = services.groups.get_data();
+            FileService::export_aliases(
+                &aliases,
+                &file,
+                Some(&group_data),
+                format,
+                prefix.as_deref(),
+            )?;
+            eprintln!("Exported {} aliases to {}", aliases.len(), file);
+        }
+        CliCommand::Import {
+            file,
+            scope,
+            repo,
+            on_conflict,
+        } => {
+            let git = &mut services.git;
+            select_repo(git, repo.as_deref(), scope)?;
+            let mut aliases = FileService::import_aliases(&file)?;
+            if let Some(scope) = scope {
+                for alias in &mut aliases {
+                    alias.scope = scope;
+                    alias.local_path = None;
+                }
+            }
+            let preview = FileService::preview_import(git, aliases.clone())?;
+            let choices: Vec<ImportChoice> = preview
+                .conflicts
+                .iter()
+                .map(|c| ImportChoice {
+                    name: c.incoming.name.clone(),
+                    scope: c.incoming.scope,
+                    resolution: on_conflict,
+                    new_name: None,
+                })
+                .collect();
+            let mut summary = FileService::apply_import(git, aliases, &choices)?;
+            let (group_change, group_error) = match import_groups(&mut services.groups, &file) {
+                Ok(change) => (change, None),
+                Err(e) => (None, Some(e)),
+            };
+            // The aliases are written either way, so they stay undoable
+            let imported = summary.added.len() + summary.overwritten.len() + summary.renamed.len();
+            services.journal.record(
+                &format!("Import {} aliases", imported),
+                std::mem::take(&mut summary.changes),
+                group_change,
+            )?;
+            for name in &summary.skipped {
+                eprintln!("Skipped \"{}\"", name);
+            }
+            eprintln!(
+                "Imported {} aliases ({} overwritten, {} renamed), skipped {}",
+                imported,
+                summary.overwritten.len(),
+                summary.renamed.len(),
+                summary.skipped.len()
+            );
+            if let Some(e) = group_error {
+                return Err(e.context("Aliases imported, but ").into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Points `git` at `repo`, or at the current directory when a repository
+/// scope is requested without one. Neither is remembered.
+fn select_repo(
+    git: &mut GitService,
+    repo: Option<&str>,
+    scope: Option<Scope>,
+) -> Result<(), String> {
+    let repo = match repo {
+        Some(repo) => Some(repo.to_string()),
+        None if scope.is_some_and(Scope::is_repo_scoped) => Some(
+            std::env::current_dir()
+                .map_err(|e| e.to_string())?
+                .to_string_lossy()
+                .to_string(),
+        ),
+        None => None,
+    };
+    if let Some(repo) = repo {
+        let path = Path::new(&repo)
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", repo, e))?;
+        git.select_local_path(Some(path.to_string_lossy().to_string()));
+    }
+    Ok(())
+}
+
+fn find_group(groups: &GroupService, name_or_id: &str) -> Result<String, String> {
+    groups
+        .get_groups()
+        .into_iter()
+        .find(|g| g.id == name_or_id || g.name.eq_ignore_ascii_case(name_or_id))
+        .map(|g| g.id)
+        .ok_or_else(|| format!("Group not found: {}", name_or_id))
+}
+
+/// Imports the groups of a GAM export, returning the change for the journal.
+fn import_groups(groups: &mut GroupService, file: &str) -> Result<Option<GroupChange>, GamError> {
+    let Some(export_data) = std::fs::read_to_string(file)
+        .ok()
+        .and_then(|content| export_schema::parse_export(&content).ok())
+        .filter(|data| data.groups.is_some() || data.assignments.is_some())
+    else {
+        return Ok(None);
+    };
+    let before = groups.get_data();
+    groups.import_data(GroupData {
+        groups: export_data.groups.unwrap_or_default(),
+        assignments: export_data.assignments.unwrap_or_default(),
+    })?;
+    Ok(Some(GroupChange {
+        before,
+        after: groups.get_data(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn non_subcommands_launch_the_gui() {
+        assert_eq!(parse(&args(&[])), Ok(None));
+        assert_eq!(parse(&args(&["."])), Ok(None));
+        assert_eq!(parse(&args(&["/home/me/project"])), Ok(None));
+    }
+
+    #[test]
+    fn parses_list() {
+        assert_eq!(
+            parse(&args(&["list", "--scope", "all", "--json"])),
+            Ok(Some(CliCommand::List {
+                scope: None,
+                repo: None,
+                json: true
+            }))
+        );
+        assert_eq!(
+            parse(&args(&["ls", "--scope=local", "--repo", "/tmp/r"])),
+            Ok(Some(CliCommand::List {
+                scope: Some(Scope::Local),
+                repo: Some("/tmp/r".to_string()),
+                json: false
+            }))
+        );
+    }
+
+    #[test]
+    fn parses_add_with_multi_word_command() {
+        assert_eq!(
+            parse(&args(&[
+                "add",
+                "lg",
+                "log",
+                "-g",
+                "--group",
+                "team",
+                "--",
+                "--oneline",
+                "--graph"
+            ])),
+            Ok(Some(CliCommand::Add {
+                name: "lg".to_string(),
+                command: "log -g --oneline --graph".to_string(),
+                scope: Scope::Global,
+                repo: None,
+                group: Some("team".to_string()),
+            }))
+        );
+        assert!(parse(&args(&["add", "lg"])).is_err());
+    }
+
+    #[test]
+    fn rejects_options_the_subcommand_does_not_take() {
+        let err = parse(&args(&["add", "lg", "log", "--format=%h"])).unwrap_err();
+        assert!(err.contains("add doesn't take --format"), "{}", err);
+        assert!(err.contains("after `--`"), "{}", err);
+        assert!(parse(&args(&["rm", "co", "--json"])).is_err());
+        assert!(parse(&args(&["list", "--prefix", "x"])).is_err());
+        assert!(parse(&args(&["export", "a.json", "--group", "team"])).is_err());
+        assert!(parse(&args(&["import", "a.json", "--json"])).is_err());
+        assert!(parse(&args(&["version", "--json"])).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert_eq!(parse(&args(&["list", "--jsn"])), Err("unknown option --jsn".to_string()));
+        assert!(parse(&args(&["add", "lg", "log", "--oneline"])).is_err());
+        assert!(parse(&args(&["rm", "co", "--force"])).is_err());
+        assert!(parse(&args(&["export", "a.json", "--formt=json"])).is_err());
+    }
+
+    #[test]
+    fn rejects_stray_arguments() {
+        assert!(parse(&args(&["list", "global"])).is_err());
+        assert!(parse(&args(&["list", "--", "x"])).is_err());
+        assert!(parse(&args(&["schema", "out.json"])).is_err());
+        assert!(parse(&args(&["version", "now"])).is_err());
+    }
+
+    fn scratch_services(dir: &Path) -> Services {
+        Services {
+            git: GitService::scratch(),
+            groups: GroupService::open(dir.join("groups.json")),
+            journal: JournalService::open(dir.join("journal.json")),
+        }
+    }
+
+    fn run_line(services: &mut Services, line: &[&str]) -> Result<(), String> {
+        execute(parse(&args(line))?.expect("a subcommand"), services)
+    }
+
+    #[test]
+    fn add_rm_and_import_write_the_repository_config() {
+        let dir = std::env::temp_dir().join(format!("gam_test_cli_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let repo = dir.join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        let config_path = repo.join(".git").join("config");
+        std::fs::write(&config_path, "").unwrap();
+        let repo_str = repo.to_str().unwrap();
+        let config = || std::fs::read_to_string(&config_path).unwrap();
+        let mut services = scratch_services(&dir);
+        let local = ["--scope", "local", "--repo", repo_str];
+
+        let add = [&["add", "gamtestlg", "log"][..], &local, &["--", "--oneline"]].concat();
+        run_line(&mut services, &add).unwrap();
+        assert!(config().contains("gamtestlg = log --oneline"));
+        assert_eq!(services.journal.history().undo.len(), 1);
+
+        // A group that doesn't exist fails before anything is written
+        let grouped = [&["add", "gamtestst", "status", "--group", "nope"][..], &local].concat();
+        assert!(run_line(&mut services, &grouped).is_err());
+        assert!(!config().contains("gamtestst"));
+
+        let rm = [&["rm", "gamtestlg"][..], &local].concat();
+        run_line(&mut services, &rm).unwrap();
+        assert!(!config().contains("gamtestlg"));
+        assert!(run_line(&mut services, &rm).is_err());
+
+        let export = dir.join("team.json");
+        std::fs::write(
+            &export,
+            r#"{"version":"1.0.0","exportedAt":"2026-01-01T00:00:00Z","aliases":[{"name":"gamtestco","command":"checkout"}]}"#,
+        )
+        .unwrap();
+        let import = [&["import", export.to_str().unwrap()][..], &local].concat();
+        run_line(&mut services, &import).unwrap();
+        assert!(config().contains("gamtestco = checkout"));
+        assert_eq!(services.journal.history().undo.len(), 3);
+        // Importing again finds nothing to change
+        run_line(&mut services, &import).unwrap();
+        assert_eq!(services.journal.history().undo.len(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_bad_scopes_and_arity() {
+        assert!(parse(&args(&["rm", "co", "--scope", "all"])).is_err());
+        assert!(parse(&args(&["rm", "co", "--scope", "galaxy"])).is_err());
+        assert!(parse(&args(&["rm"])).is_err());
+        assert!(parse(&args(&["export", "a.json", "b.json"])).is_err());
+        assert!(parse(&args(&["list", "--scope"])).is_err());
+    }
+
+    #[test]
+    fn parses_export_format() {
+        assert_eq!(
+            parse(&args(&["export", "team.inc", "--format", "gitconfig"])),
+            Ok(Some(CliCommand::Export {
+                file: "team.inc".to_string(),
+                scope: None,
+                repo: None,
+                format: Some(ExportFormat::Gitconfig),
+                prefix: None,
+            }))
+        );
+        assert_eq!(
+            parse(&args(&["export", "gam-aliases.fish", "--prefix=gg"])),
+            Ok(Some(CliCommand::Export {
+                file: "gam-aliases.fish".to_string(),
+                scope: None,
+                repo: None,
+                format: None,
+                prefix: Some("gg".to_string()),
+            }))
+        );
+        assert!(parse(&args(&["export", "a.txt", "--format=yaml"])).is_err());
+    }
+
+    #[test]
+    fn parses_import() {
+        assert_eq!(
+            parse(&args(&["import", "team.json", "--overwrite"])),
+            Ok(Some(CliCommand::Import {
+                file: "team.json".to_string(),
+                scope: None,
+                repo: None,
+                on_conflict: ConflictResolution::Overwrite,
+            }))
+        );
+        assert_eq!(
+            parse(&args(&["import", "dotfiles", "--on-conflict", "rename"])),
+            Ok(Some(CliCommand::Import {
+                file: "dotfiles".to_string(),
+                scope: None,
+                repo: None,
+                on_conflict: ConflictResolution::Rename,
+            }))
+        );
+        assert!(parse(&args(&["import", "x", "--on-conflict", "merge"])).is_err());
+    }
+}
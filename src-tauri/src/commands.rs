@@ -1,14 +1,27 @@
 use std::path::Path;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use tauri::State;
 use tauri_plugin_dialog::DialogExt;
 
-use crate::file_service::FileService;
-use crate::git_service::{GitAlias, ValidationResult};
+use crate::alias_diff::{self, AliasDiff, AliasSet, MergeResult};
+use crate::alias_graph::AliasExpansion;
+use crate::backup_service::{Backup, BackupDiff, Retention, KEEP_SETTING, MAX_AGE_SETTING};
+use crate::error::{ErrorDetails, GamError};
+use crate::file_service::{ExportFormat, FileService, ImportChoice, ImportPreview, ImportSummary};
+use crate::git_config::FileChange;
+use crate::git_environment::{GitEnvironment, GIT_PATH_SETTING};
+use crate::git_service::{AliasOperation, AliasResolution, GitAlias, Scope, ValidationResult};
+use crate::group_service::{GroupData, GroupService};
+use crate::journal_service::{GroupChange, History, HistoryItem};
+use crate::ranking_service::{AliasScores, AliasSuggestion, RepoUsage, HALF_LIFE_SETTING};
+use crate::sandbox_service::{DryRunReport, SandboxService, DEFAULT_TIMEOUT};
 use crate::AppState;
 
-/// Standard IPC result returned by all Tauri commands.
+/// Standard IPC result returned by all Tauri commands. Failures carry the
+/// message to show, a stable `code` (see `GamError::code`) and, when there
+/// are any, structured `details` such as the file path or alias name.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpcResult<T: Serialize> {
     pub success: bool,
@@ -16,6 +29,10 @@ pub struct IpcResult<T: Serialize> {
     pub data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<ErrorDetails>,
 }
 
 impl<T: Serialize> IpcResult<T> {
@@ -24,24 +41,46 @@ impl<T: Serialize> IpcResult<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
+            details: None,
         }
     }
 
-    fn err(msg: String) -> Self {
+    fn err(err: GamError) -> Self {
+        let details = err.details();
         Self {
             success: false,
             data: None,
-            error: Some(msg),
+            error: Some(err.to_string()),
+            code: Some(err.code().to_string()),
+            details: (!details.is_empty()).then_some(details),
         }
     }
 }
 
 // ── Alias CRUD ──────────────────────────────────────────────
 
+/// Records config file changes in the undo journal.
+fn record_files(
+    state: &State<'_, AppState>,
+    description: &str,
+    files: Vec<FileChange>,
+) -> Result<(), GamError> {
+    let mut journal = state.journal_service.write().unwrap_or_else(|e| e.into_inner());
+    journal.record(description, files, None)
+}
+
 #[tauri::command]
 pub fn get_aliases(state: State<'_, AppState>, scope: String) -> IpcResult<Vec<GitAlias>> {
+    let scope = match scope.as_str() {
+        "all" => None,
+        other => match other.parse::<Scope>() {
+            Ok(scope) => Some(scope),
+            Err(e) => return IpcResult::err(e),
+        },
+    };
     let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
-    match git.get_aliases(&scope) {
+    match git.get_aliases(scope) {
         Ok(aliases) => IpcResult::ok(aliases),
         Err(e) => IpcResult::err(e),
     }
@@ -55,9 +94,18 @@ pub fn add_alias(
     scope: String,
     local_path: Option<String>,
 ) -> IpcResult<bool> {
+    let scope = match scope.parse::<Scope>() {
+        Ok(scope) => scope,
+        Err(e) => return IpcResult::err(e),
+    };
     let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
-    match git.add_alias(&name, &command, &scope, local_path.as_deref()) {
-        Ok(()) => IpcResult::ok(true),
+    match git.add_alias(&name, &command, scope, local_path.as_deref()) {
+        Ok(files) => {
+            match record_files(&state, &format!("Add alias \"{}\"", name), files) {
+                Ok(()) => IpcResult::ok(true),
+                Err(e) => IpcResult::err(e),
+            }
+        }
         Err(e) => IpcResult::err(e),
     }
 }
@@ -70,10 +118,32 @@ pub fn update_alias(
     command: String,
     scope: String,
     local_path: Option<String>,
+    origin: Option<String>,
 ) -> IpcResult<bool> {
+    let scope = match scope.parse::<Scope>() {
+        Ok(scope) => scope,
+        Err(e) => return IpcResult::err(e),
+    };
     let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
-    match git.update_alias(&old_name, &name, &command, &scope, local_path.as_deref()) {
-        Ok(()) => IpcResult::ok(true),
+    match git.update_alias(
+        &old_name,
+        &name,
+        &command,
+        scope,
+        local_path.as_deref(),
+        origin.as_deref(),
+    ) {
+        Ok(files) => {
+            let description = if old_name == name {
+                format!("Update alias \"{}\"", name)
+            } else {
+                format!("Rename alias \"{}\" to \"{}\"", old_name, name)
+            };
+            match record_files(&state, &description, files) {
+                Ok(()) => IpcResult::ok(true),
+                Err(e) => IpcResult::err(e),
+            }
+        }
         Err(e) => IpcResult::err(e),
     }
 }
@@ -84,23 +154,105 @@ pub fn delete_alias(
     name: String,
     scope: String,
     local_path: Option<String>,
+    origin: Option<String>,
 ) -> IpcResult<bool> {
+    let scope = match scope.parse::<Scope>() {
+        Ok(scope) => scope,
+        Err(e) => return IpcResult::err(e),
+    };
     let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
-    match git.delete_alias(&name, &scope, local_path.as_deref()) {
-        Ok(()) => IpcResult::ok(true),
+    match git.delete_alias(&name, scope, local_path.as_deref(), origin.as_deref()) {
+        Ok(files) => {
+            match record_files(&state, &format!("Delete alias \"{}\"", name), files) {
+                Ok(()) => IpcResult::ok(true),
+                Err(e) => IpcResult::err(e),
+            }
+        }
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn resolve_aliases(
+    state: State<'_, AppState>,
+    local_path: Option<String>,
+    name: Option<String>,
+) -> IpcResult<Vec<AliasResolution>> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    IpcResult::ok(git.resolve_aliases(local_path.as_deref(), name.as_deref()))
+}
+
+#[tauri::command]
+pub fn expand_alias(
+    state: State<'_, AppState>,
+    invocation: String,
+    local_path: Option<String>,
+) -> IpcResult<AliasExpansion> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    match git.expand_alias(&invocation, local_path.as_deref()) {
+        Ok(expansion) => IpcResult::ok(expansion),
         Err(e) => IpcResult::err(e),
     }
 }
 
+// Every argument comes from the dry-run form
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn dry_run_alias(
+    state: State<'_, AppState>,
+    name: String,
+    command: Option<String>,
+    args: Vec<String>,
+    local_path: Option<String>,
+    clone_repo: bool,
+    timeout_secs: Option<u64>,
+    allow_shell: Option<bool>,
+) -> Result<IpcResult<DryRunReport>, String> {
+    // Plan under the lock, then run without holding it.
+    let plan = {
+        let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+        git.dry_run_plan(&name, command.as_deref(), local_path.as_deref(), clone_repo)
+    };
+    let timeout = timeout_secs.map(Duration::from_secs).unwrap_or(DEFAULT_TIMEOUT);
+    let allow_shell = allow_shell.unwrap_or(false);
+    match plan.and_then(|plan| SandboxService::dry_run(&plan, &args, timeout, allow_shell)) {
+        Ok(report) => Ok(IpcResult::ok(report)),
+        Err(e) => Ok(IpcResult::err(e)),
+    }
+}
+
 // ── Validation ──────────────────────────────────────────────
 
 #[tauri::command]
 pub fn validate_command(
     state: State<'_, AppState>,
     command: String,
+    name: Option<String>,
+    local_path: Option<String>,
+    old_name: Option<String>,
 ) -> IpcResult<ValidationResult> {
     let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
-    IpcResult::ok(git.validate_command(&command))
+    match name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => IpcResult::ok(git.validate_alias(
+            &name,
+            &command,
+            local_path.as_deref(),
+            old_name.as_deref(),
+        )),
+        None => IpcResult::ok(git.validate_command(&command)),
+    }
+}
+
+#[tauri::command]
+pub fn validate_alias(
+    state: State<'_, AppState>,
+    name: String,
+    command: String,
+    local_path: Option<String>,
+    old_name: Option<String>,
+) -> IpcResult<ValidationResult> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    IpcResult::ok(git.validate_alias(&name, &command, local_path.as_deref(), old_name.as_deref()))
 }
 
 // ── Local Scope Folder Selection ─────────────────────────────
@@ -122,8 +274,10 @@ pub async fn select_folder(
         Some(path) => {
             let path_str = path.into_path().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
             let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
-            git.set_local_path(Some(path_str.clone()));
-            Ok(IpcResult::ok(path_str))
+            match git.set_local_path(Some(path_str.clone())) {
+                Ok(()) => Ok(IpcResult::ok(path_str)),
+                Err(e) => Ok(IpcResult::err(e)),
+            }
         }
         None => {
             let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
@@ -145,11 +299,13 @@ pub fn set_local_path(
     path: String,
 ) -> IpcResult<String> {
     if !path.is_empty() && !Path::new(&path).exists() {
-        return IpcResult::err(format!("Directory does not exist: {}", path));
+        return IpcResult::err(GamError::NotFound(format!("directory {}", path)));
     }
     let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
-    git.set_local_path(if path.is_empty() { None } else { Some(path.clone()) });
-    IpcResult::ok(path)
+    match git.set_local_path(if path.is_empty() { None } else { Some(path.clone()) }) {
+        Ok(()) => IpcResult::ok(path),
+        Err(e) => IpcResult::err(e),
+    }
 }
 
 // ── Import / Export ─────────────────────────────────────────
@@ -159,18 +315,38 @@ pub async fn export_aliases(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
     aliases: Vec<GitAlias>,
+    format: Option<String>,
+    prefix: Option<String>,
 ) -> Result<IpcResult<String>, String> {
+    let format = match format.as_deref().map(str::parse::<ExportFormat>).transpose() {
+        Ok(format) => format,
+        Err(e) => return Ok(IpcResult::err(e)),
+    };
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
-    let default_name = format!("gam-{}.json", now);
-
-    let file_path = app
-        .dialog()
-        .file()
-        .set_title("Export Git Aliases")
-        .add_filter("JSON Files", &["json"])
+    let default_name = match format {
+        Some(f) if f.is_shell() => format!("gam-aliases.{}", f.extension()),
+        _ => format!("gam-{}.{}", now, format.unwrap_or_default().extension()),
+    };
+
+    // Offer only the requested format, or every format when none was given
+    let filters = [
+        (ExportFormat::Json, "JSON Files"),
+        (ExportFormat::Gitconfig, "Git Config Fragments"),
+        (ExportFormat::Markdown, "Markdown Cheat Sheets"),
+        (ExportFormat::Zsh, "Zsh Aliases"),
+        (ExportFormat::Bash, "Bash Aliases"),
+        (ExportFormat::Fish, "Fish Abbreviations"),
+    ];
+    let mut dialog = app.dialog().file().set_title("Export Git Aliases");
+    for (filter_format, label) in filters {
+        if format.is_none_or(|f| f == filter_format) {
+            dialog = dialog.add_filter(label, &[filter_format.extension()]);
+        }
+    }
+    let file_path = dialog
         .add_filter("All Files", &["*"])
         .set_file_name(&default_name)
         .blocking_save_file();
@@ -179,64 +355,401 @@ pub async fn export_aliases(
         Some(path) => {
             let path_str = path.into_path().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
             let group_data = state.group_service.read().unwrap_or_else(|e| e.into_inner()).get_data();
-            match FileService::export_aliases(&aliases, &path_str, Some(&group_data)) {
+            match FileService::export_aliases(&aliases, &path_str, Some(&group_data), format, prefix.as_deref()) {
                 Ok(p) => Ok(IpcResult::ok(p)),
                 Err(e) => Ok(IpcResult::err(e)),
             }
         }
-        None => Ok(IpcResult::err("Export cancelled".to_string())),
+        None => Ok(IpcResult::err(GamError::Cancelled("Export".to_string()))),
     }
 }
 
+/// Asks for a file to import, or a folder treated as a dotfiles repository.
+fn pick_import_path(app: &tauri::AppHandle, folder: Option<bool>) -> Option<String> {
+    let dialog = app.dialog().file().set_title("Import Git Aliases");
+    let file_path = if folder.unwrap_or(false) {
+        dialog.blocking_pick_folder()
+    } else {
+        dialog
+            .add_filter("GAM Exports", &["json"])
+            .add_filter("Git Config Files", &["gitconfig", "inc", "conf", "cfg"])
+            .add_filter("All Files", &["*"])
+            .blocking_pick_file()
+    };
+    file_path.map(|path| {
+        path.into_path()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    })
+}
+
+/// The group data of a JSON export, if it has any.
+fn export_groups(path: &str) -> Option<GroupData> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let export_data = crate::export_schema::parse_export(&content).ok()?;
+    (export_data.groups.is_some() || export_data.assignments.is_some()).then(|| GroupData {
+        groups: export_data.groups.unwrap_or_default(),
+        assignments: export_data.assignments.unwrap_or_default(),
+    })
+}
+
 #[tauri::command]
 pub async fn import_aliases(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
+    folder: Option<bool>,
 ) -> Result<IpcResult<Vec<GitAlias>>, String> {
-    let file_path = app
-        .dialog()
-        .file()
-        .set_title("Import Git Aliases")
-        .add_filter("JSON Files", &["json"])
-        .add_filter("All Files", &["*"])
-        .blocking_pick_file();
+    let Some(path_str) = pick_import_path(&app, folder) else {
+        return Ok(IpcResult::err(GamError::Cancelled("Import".to_string())));
+    };
+    let aliases = match FileService::import_aliases(&path_str) {
+        Ok(aliases) => aliases,
+        Err(e) => return Ok(IpcResult::err(e)),
+    };
+    // The caller adds the aliases one by one; groups are merged right away
+    if let Some(incoming) = export_groups(&path_str)
+        && let Err(e) = change_groups(&state, "Import groups", |g| g.import_data(incoming))
+    {
+        return Ok(IpcResult::err(e));
+    }
+    Ok(IpcResult::ok(aliases))
+}
 
-    match file_path {
-        Some(path) => {
-            let path_str = path.into_path().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+#[tauri::command]
+pub async fn preview_import(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    folder: Option<bool>,
+) -> Result<IpcResult<ImportPreview>, String> {
+    let Some(path_str) = pick_import_path(&app, folder) else {
+        return Ok(IpcResult::err(GamError::Cancelled("Import".to_string())));
+    };
+    let aliases = match FileService::import_aliases(&path_str) {
+        Ok(aliases) => aliases,
+        Err(e) => return Ok(IpcResult::err(e)),
+    };
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match FileService::preview_import(&mut git, aliases) {
+        Ok(mut preview) => {
+            // Group data is only applied with the aliases, after the preview
+            preview.groups = export_groups(&path_str);
+            Ok(IpcResult::ok(preview))
+        }
+        Err(e) => Ok(IpcResult::err(e)),
+    }
+}
+
+#[tauri::command]
+pub fn import_shell_aliases(
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> IpcResult<ImportPreview> {
+    let aliases = match FileService::import_shell_aliases(path.as_deref()) {
+        Ok(aliases) => aliases,
+        Err(e) => return IpcResult::err(e),
+    };
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match FileService::preview_import(&mut git, aliases) {
+        Ok(preview) => IpcResult::ok(preview),
+        Err(e) => IpcResult::err(e),
+    }
+}
 
-            // Also import group data if present in the file
-            if let Ok(content) = std::fs::read_to_string(&path_str)
-                && let Ok(export_data) = serde_json::from_str::<crate::file_service::ExportData>(&content)
-                    && (export_data.groups.is_some() || export_data.assignments.is_some()) {
-                        let incoming = crate::group_service::GroupData {
-                            groups: export_data.groups.unwrap_or_default(),
-                            assignments: export_data.assignments.unwrap_or_default(),
-                        };
-                        let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
-                        group_svc.import_data(incoming);
+#[tauri::command]
+pub fn apply_import(
+    state: State<'_, AppState>,
+    aliases: Vec<GitAlias>,
+    choices: Vec<ImportChoice>,
+    groups: Option<GroupData>,
+) -> IpcResult<ImportSummary> {
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match FileService::apply_import(&mut git, aliases, &choices) {
+        Ok(mut summary) => {
+            let (groups, group_error) = match groups {
+                Some(incoming) => {
+                    let mut group_svc =
+                        state.group_service.write().unwrap_or_else(|e| e.into_inner());
+                    let before = group_svc.get_data();
+                    match group_svc.import_data(incoming) {
+                        Ok(()) => {
+                            let after = group_svc.get_data();
+                            (Some(GroupChange { before, after }), None)
+                        }
+                        Err(e) => (None, Some(e)),
                     }
+                }
+                None => (None, None),
+            };
+            // The aliases are written either way, so they stay undoable
+            let imported = summary.added.len() + summary.overwritten.len() + summary.renamed.len();
+            let mut journal = state.journal_service.write().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = journal.record(
+                &format!("Import {} aliases", imported),
+                std::mem::take(&mut summary.changes),
+                groups,
+            ) {
+                return IpcResult::err(e);
+            }
+            match group_error {
+                Some(e) => IpcResult::err(e.context("Aliases imported, but ")),
+                None => IpcResult::ok(summary),
+            }
+        }
+        Err(e) => IpcResult::err(e),
+    }
+}
 
-            match FileService::import_aliases(&path_str) {
-                Ok(aliases) => Ok(IpcResult::ok(aliases)),
-                Err(e) => Ok(IpcResult::err(e)),
+#[tauri::command]
+pub fn apply_batch(state: State<'_, AppState>, operations: Vec<AliasOperation>) -> IpcResult<bool> {
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match git.apply_batch(&operations) {
+        Ok(files) => {
+            let description = match operations.as_slice() {
+                [op] => capitalize(&op.to_string()),
+                _ => format!("Apply {} alias changes", operations.len()),
+            };
+            match record_files(&state, &description, files) {
+                Ok(()) => IpcResult::ok(true),
+                Err(e) => IpcResult::err(e),
             }
         }
-        None => Ok(IpcResult::err("Import cancelled".to_string())),
+        Err(e) => IpcResult::err(e),
     }
 }
 
+/// `add "co" (global)` → `Add "co" (global)`.
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Every alias in every scope with the saved groups, used when a diff or
+/// merge side is omitted.
+fn current_alias_set(state: &State<'_, AppState>) -> Result<AliasSet, GamError> {
+    let aliases = state.git_service.write().unwrap_or_else(|e| e.into_inner()).get_aliases(None)?;
+    let groups = state.group_service.read().unwrap_or_else(|e| e.into_inner()).get_data();
+    Ok(AliasSet { aliases, groups })
+}
+
+#[tauri::command]
+pub fn diff_aliases(
+    state: State<'_, AppState>,
+    old: Option<AliasSet>,
+    new: AliasSet,
+) -> IpcResult<AliasDiff> {
+    let old = match old.map_or_else(|| current_alias_set(&state), Ok) {
+        Ok(old) => old,
+        Err(e) => return IpcResult::err(e),
+    };
+    IpcResult::ok(alias_diff::diff(&old, &new))
+}
+
+#[tauri::command]
+pub fn merge_aliases(
+    state: State<'_, AppState>,
+    base: AliasSet,
+    ours: Option<AliasSet>,
+    theirs: AliasSet,
+) -> IpcResult<MergeResult> {
+    let ours = match ours.map_or_else(|| current_alias_set(&state), Ok) {
+        Ok(ours) => ours,
+        Err(e) => return IpcResult::err(e),
+    };
+    IpcResult::ok(alias_diff::merge(&base, &ours, &theirs))
+}
+
+// ── Undo / redo ────────────────────────────────────────────
+
+#[tauri::command]
+pub fn undo(state: State<'_, AppState>) -> IpcResult<HistoryItem> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    let mut journal = state.journal_service.write().unwrap_or_else(|e| e.into_inner());
+    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
+    match journal.undo(&group_svc.get_data(), git.backups()) {
+        Ok(entry) => {
+            if let Some(groups) = &entry.groups
+                && let Err(e) = group_svc.replace_data(groups.before.clone())
+            {
+                return IpcResult::err(e.context("Config files restored, but "));
+            }
+            IpcResult::ok(entry.summary())
+        }
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn redo(state: State<'_, AppState>) -> IpcResult<HistoryItem> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    let mut journal = state.journal_service.write().unwrap_or_else(|e| e.into_inner());
+    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
+    match journal.redo(&group_svc.get_data(), git.backups()) {
+        Ok(entry) => {
+            if let Some(groups) = &entry.groups
+                && let Err(e) = group_svc.replace_data(groups.after.clone())
+            {
+                return IpcResult::err(e.context("Config files restored, but "));
+            }
+            IpcResult::ok(entry.summary())
+        }
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn get_history(state: State<'_, AppState>) -> IpcResult<History> {
+    let journal = state.journal_service.read().unwrap_or_else(|e| e.into_inner());
+    IpcResult::ok(journal.history())
+}
+
+// ── Config backups ─────────────────────────────────────────
+
+#[tauri::command]
+pub fn list_backups(state: State<'_, AppState>, path: Option<String>) -> IpcResult<Vec<Backup>> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    IpcResult::ok(git.backups().list(path.as_deref()))
+}
+
+#[tauri::command]
+pub fn diff_backup(state: State<'_, AppState>, id: String) -> IpcResult<BackupDiff> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    match git.backups().diff(&id) {
+        Ok(diff) => IpcResult::ok(diff),
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn restore_backup(state: State<'_, AppState>, id: String) -> IpcResult<bool> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    match git.backups().restore(&id) {
+        Ok(files) => {
+            let description = match files.first() {
+                Some(change) => format!("Restore backup of {}", change.path.display()),
+                None => String::new(),
+            };
+            match record_files(&state, &description, files) {
+                Ok(()) => IpcResult::ok(true),
+                Err(e) => IpcResult::err(e),
+            }
+        }
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn get_backup_retention(state: State<'_, AppState>) -> IpcResult<Retention> {
+    let git = state.git_service.read().unwrap_or_else(|e| e.into_inner());
+    IpcResult::ok(git.backups().retention())
+}
+
+#[tauri::command]
+pub fn set_backup_retention(state: State<'_, AppState>, retention: Retention) -> IpcResult<bool> {
+    let mut settings = state.settings_service.write().unwrap_or_else(|e| e.into_inner());
+    let saved = settings
+        .set(KEEP_SETTING, &retention.keep.to_string())
+        .and_then(|_| settings.set(MAX_AGE_SETTING, &retention.max_age_days.to_string()));
+    if let Err(e) = saved {
+        return IpcResult::err(e);
+    }
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    git.backups_mut().set_retention(retention);
+    IpcResult::ok(true)
+}
+
+// ── Ranking ────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_alias_scores(state: State<'_, AppState>) -> IpcResult<AliasScores> {
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match git.alias_scores() {
+        Ok(scores) => IpcResult::ok(scores),
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn suggest_aliases(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> IpcResult<Vec<AliasSuggestion>> {
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match git.alias_suggestions(limit.unwrap_or(10)) {
+        Ok(suggestions) => IpcResult::ok(suggestions),
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn get_repo_stats(state: State<'_, AppState>, limit: Option<usize>) -> IpcResult<Vec<RepoUsage>> {
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    match git.repo_stats(limit.unwrap_or(10)) {
+        Ok(stats) => IpcResult::ok(stats),
+        Err(e) => IpcResult::err(e),
+    }
+}
+
+#[tauri::command]
+pub fn set_ranking_half_life(state: State<'_, AppState>, days: f64) -> IpcResult<bool> {
+    if !days.is_finite() || days <= 0.0 {
+        return IpcResult::err(GamError::Invalid(format!(
+            "Half-life must be a positive number of days, got {}",
+            days
+        )));
+    }
+    let mut settings = state.settings_service.write().unwrap_or_else(|e| e.into_inner());
+    if let Err(e) = settings.set(HALF_LIFE_SETTING, &days.to_string()) {
+        return IpcResult::err(e);
+    }
+    let mut git = state.git_service.write().unwrap_or_else(|e| e.into_inner());
+    git.ranking_mut().set_half_life_days(days);
+    IpcResult::ok(true)
+}
+
+// ── Git environment ────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_git_environment(refresh: Option<bool>) -> IpcResult<GitEnvironment> {
+    if refresh.unwrap_or(false) {
+        IpcResult::ok(GitEnvironment::refresh())
+    } else {
+        IpcResult::ok(GitEnvironment::current())
+    }
+}
+
+#[tauri::command]
+pub fn set_git_path(state: State<'_, AppState>, path: Option<String>) -> IpcResult<GitEnvironment> {
+    let path = path.filter(|p| !p.trim().is_empty());
+    let env = GitEnvironment::probe(path.as_deref().map(Path::new));
+    // A custom path is only saved once it turns out to be a working git
+    if path.is_some()
+        && let Some(error) = &env.error
+    {
+        return IpcResult::err(GamError::Invalid(error.clone()));
+    }
+    let mut settings = state.settings_service.write().unwrap_or_else(|e| e.into_inner());
+    if let Err(e) = settings.set(GIT_PATH_SETTING, path.as_deref().unwrap_or_default()) {
+        return IpcResult::err(e);
+    }
+    GitEnvironment::configure(&settings);
+    GitEnvironment::set_current(env.clone());
+    IpcResult::ok(env)
+}
+
 // ── Open folder / URL ──────────────────────────────────────
 
 #[tauri::command]
 pub fn open_local_folder(path: String) -> IpcResult<bool> {
     let p = Path::new(&path);
     if !p.exists() || !p.is_dir() {
-        return IpcResult::err(format!("Not a valid directory: {}", path));
+        return IpcResult::err(GamError::Invalid(format!("Not a valid directory: {}", path)));
     }
     match open::that(&path) {
         Ok(()) => IpcResult::ok(true),
-        Err(e) => IpcResult::err(format!("Failed to open folder: {}", e)),
+        Err(e) => IpcResult::err(GamError::file("open", p, e)),
     }
 }
 
@@ -244,11 +757,11 @@ pub fn open_local_folder(path: String) -> IpcResult<bool> {
 pub fn open_external(url: String) -> IpcResult<bool> {
     // Only allow HTTPS URLs — block file://, ssh://, custom protocols
     if !url.starts_with("https://") {
-        return IpcResult::err("Only HTTPS URLs are allowed".to_string());
+        return IpcResult::err(GamError::Invalid("Only HTTPS URLs are allowed".to_string()));
     }
     match open::that(&url) {
         Ok(()) => IpcResult::ok(true),
-        Err(e) => IpcResult::err(format!("Failed to open URL: {}", e)),
+        Err(e) => IpcResult::err(GamError::Invalid(format!("Failed to open URL: {}", e))),
     }
 }
 
@@ -266,12 +779,33 @@ pub fn get_theme(state: State<'_, AppState>) -> IpcResult<String> {
 #[tauri::command]
 pub fn set_theme(state: State<'_, AppState>, theme_id: String) -> IpcResult<bool> {
     let mut settings = state.settings_service.write().unwrap_or_else(|e| e.into_inner());
-    settings.set("theme", &theme_id);
-    IpcResult::ok(true)
+    match settings.set("theme", &theme_id) {
+        Ok(()) => IpcResult::ok(true),
+        Err(e) => IpcResult::err(e),
+    }
 }
 
 // ── Group management ───────────────────────────────────────
 
+/// Runs a group mutation and records it in the undo journal.
+fn change_groups<T>(
+    state: &State<'_, AppState>,
+    description: &str,
+    change: impl FnOnce(&mut GroupService) -> Result<T, GamError>,
+) -> Result<T, GamError> {
+    let (result, groups) = {
+        let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
+        let before = group_svc.get_data();
+        // A failed change leaves the groups as they were: nothing to journal
+        let result = change(&mut group_svc)?;
+        let after = group_svc.get_data();
+        (result, GroupChange { before, after })
+    };
+    let mut journal = state.journal_service.write().unwrap_or_else(|e| e.into_inner());
+    journal.record(description, Vec::new(), Some(groups))?;
+    Ok(result)
+}
+
 #[tauri::command]
 pub fn get_groups(state: State<'_, AppState>) -> IpcResult<Vec<crate::group_service::AliasGroup>> {
     let group_svc = state.group_service.read().unwrap_or_else(|e| e.into_inner());
@@ -284,8 +818,11 @@ pub fn create_group(
     name: String,
     color: String,
 ) -> IpcResult<crate::group_service::AliasGroup> {
-    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
-    IpcResult::ok(group_svc.create_group(&name, &color))
+    let description = format!("Create group \"{}\"", name);
+    match change_groups(&state, &description, |g| g.create_group(&name, &color)) {
+        Ok(group) => IpcResult::ok(group),
+        Err(e) => IpcResult::err(e),
+    }
 }
 
 #[tauri::command]
@@ -294,8 +831,8 @@ pub fn rename_group(
     group_id: String,
     new_name: String,
 ) -> IpcResult<bool> {
-    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
-    match group_svc.rename_group(&group_id, &new_name) {
+    let description = format!("Rename group to \"{}\"", new_name);
+    match change_groups(&state, &description, |g| g.rename_group(&group_id, &new_name)) {
         Ok(()) => IpcResult::ok(true),
         Err(e) => IpcResult::err(e),
     }
@@ -307,8 +844,7 @@ pub fn set_group_color(
     group_id: String,
     color: String,
 ) -> IpcResult<bool> {
-    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
-    match group_svc.set_group_color(&group_id, &color) {
+    match change_groups(&state, "Change group color", |g| g.set_group_color(&group_id, &color)) {
         Ok(()) => IpcResult::ok(true),
         Err(e) => IpcResult::err(e),
     }
@@ -316,8 +852,12 @@ pub fn set_group_color(
 
 #[tauri::command]
 pub fn delete_group(state: State<'_, AppState>, group_id: String) -> IpcResult<bool> {
-    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
-    match group_svc.delete_group(&group_id) {
+    let name = {
+        let group_svc = state.group_service.read().unwrap_or_else(|e| e.into_inner());
+        group_svc.get_groups().into_iter().find(|g| g.id == group_id).map(|g| g.name)
+    };
+    let description = format!("Delete group \"{}\"", name.as_deref().unwrap_or(&group_id));
+    match change_groups(&state, &description, |g| g.delete_group(&group_id)) {
         Ok(()) => IpcResult::ok(true),
         Err(e) => IpcResult::err(e),
     }
@@ -329,9 +869,11 @@ pub fn set_alias_groups(
     alias_name: String,
     group_ids: Vec<String>,
 ) -> IpcResult<bool> {
-    let mut group_svc = state.group_service.write().unwrap_or_else(|e| e.into_inner());
-    group_svc.set_alias_groups(&alias_name, group_ids);
-    IpcResult::ok(true)
+    let description = format!("Change groups of \"{}\"", alias_name);
+    match change_groups(&state, &description, |g| g.set_alias_groups(&alias_name, group_ids)) {
+        Ok(()) => IpcResult::ok(true),
+        Err(e) => IpcResult::err(e),
+    }
 }
 
 #[tauri::command]
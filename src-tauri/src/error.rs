@@ -1,4 +1,9 @@
 use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::git_service::Scope;
 
 /// Structured error type for all GAM backend operations.
 #[derive(Debug)]
@@ -6,26 +11,107 @@ pub enum GamError {
     /// Git CLI not found or not installed.
     GitNotFound,
     /// Git command execution failed.
-    GitFailed(String),
+    GitFailed {
+        message: String,
+        /// Exit status, when git ran and exited with one.
+        status: Option<i32>,
+    },
     /// Filesystem I/O error.
     Io(std::io::Error),
+    /// Reading or writing a specific file failed.
+    File { path: PathBuf, message: String },
     /// JSON serialization / deserialization error.
     Serde(String),
     /// Requested resource (alias, group, etc.) not found.
     NotFound(String),
     /// RwLock was poisoned.
     LockPoisoned(String),
+    /// Input rejected before anything was changed: alias names, scopes,
+    /// prefixes, file formats, commands.
+    Invalid(String),
+    /// A config file git itself would refuse to read.
+    ConfigSyntax { path: Option<PathBuf>, line: usize },
+    /// An alias with this name is already defined in the scope.
+    AliasExists {
+        name: String,
+        scope: Scope,
+        path: Option<PathBuf>,
+    },
+    /// The alias isn't defined in the scope, or not in `path`.
+    AliasNotFound {
+        name: String,
+        scope: Scope,
+        path: Option<PathBuf>,
+    },
+    /// Another process (usually git) holds `<path>.lock`.
+    FileLocked { path: PathBuf },
+    /// The file changed on disk since GAM read it.
+    FileChanged { path: PathBuf },
+    /// Operations of a batch that failed; nothing was written.
+    Batch(Vec<BatchError>),
+    /// The user closed a dialog; holds what was cancelled, e.g. "Export".
+    Cancelled(String),
+}
+
+/// One failed operation of a batch.
+#[derive(Debug)]
+pub struct BatchError {
+    /// 1-based position in the batch.
+    pub index: usize,
+    /// The operation as text, e.g. `add "co" (global)`.
+    pub operation: String,
+    pub error: GamError,
 }
 
 impl fmt::Display for GamError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             GamError::GitNotFound => write!(f, "Git is not installed or not found in PATH"),
-            GamError::GitFailed(msg) => write!(f, "Git error: {msg}"),
+            GamError::GitFailed { message, .. } => write!(f, "Git error: {message}"),
             GamError::Io(err) => write!(f, "I/O error: {err}"),
+            GamError::File { message, .. } => write!(f, "{message}"),
             GamError::Serde(msg) => write!(f, "Serialization error: {msg}"),
             GamError::NotFound(what) => write!(f, "Not found: {what}"),
             GamError::LockPoisoned(what) => write!(f, "Lock poisoned: {what}"),
+            GamError::Invalid(msg) => write!(f, "{msg}"),
+            GamError::ConfigSyntax { path: None, line } => write!(f, "bad config line {line}"),
+            GamError::ConfigSyntax {
+                path: Some(path),
+                line,
+            } => write!(f, "bad config line {line} in file {}", path.display()),
+            GamError::AliasExists { name, scope, path } => {
+                write!(f, "Alias \"{name}\" already exists in {scope} config")?;
+                match path {
+                    Some(path) => write!(f, " ({})", path.display()),
+                    None => Ok(()),
+                }
+            }
+            GamError::AliasNotFound {
+                name,
+                path: Some(path),
+                ..
+            } => write!(f, "Alias \"{name}\" is not defined in {}", path.display()),
+            GamError::AliasNotFound { name, scope, .. } => {
+                write!(f, "Alias \"{name}\" not found in {scope} config")
+            }
+            GamError::FileLocked { path } => write!(
+                f,
+                "Could not lock config file {}: another git process seems to be running",
+                path.display()
+            ),
+            GamError::FileChanged { path } => write!(
+                f,
+                "{} was modified by another program; no changes were applied",
+                path.display()
+            ),
+            GamError::Batch(errors) => {
+                write!(f, "No changes were applied:")?;
+                for e in errors {
+                    write!(f, "\n#{} {}: {}", e.index, e.operation, e.error)?;
+                }
+                Ok(())
+            }
+            GamError::Cancelled(what) => write!(f, "{what} cancelled"),
         }
     }
 }
@@ -59,15 +145,142 @@ impl GamError {
     pub fn code(&self) -> &'static str {
         match self {
             GamError::GitNotFound => "GIT_NOT_FOUND",
-            GamError::GitFailed(_) => "GIT_FAILED",
-            GamError::Io(_) => "IO_ERROR",
+            GamError::GitFailed { .. } => "GIT_FAILED",
+            GamError::Io(_) | GamError::File { .. } => "IO_ERROR",
             GamError::Serde(_) => "SERDE_ERROR",
             GamError::NotFound(_) => "NOT_FOUND",
             GamError::LockPoisoned(_) => "LOCK_POISONED",
+            GamError::Invalid(_) => "INVALID_INPUT",
+            GamError::ConfigSyntax { .. } => "CONFIG_SYNTAX",
+            GamError::AliasExists { .. } => "ALIAS_EXISTS",
+            GamError::AliasNotFound { .. } => "ALIAS_NOT_FOUND",
+            GamError::FileLocked { .. } => "FILE_LOCKED",
+            GamError::FileChanged { .. } => "FILE_CHANGED",
+            GamError::Batch(_) => "BATCH_FAILED",
+            GamError::Cancelled(_) => "CANCELLED",
+        }
+    }
+
+    /// A failed file operation: `action` is e.g. "read" or "write".
+    pub fn file(action: &str, path: &Path, err: impl fmt::Display) -> Self {
+        GamError::File {
+            path: path.to_path_buf(),
+            message: format!("Failed to {} {}: {}", action, path.display(), err),
+        }
+    }
+
+    /// Prefixes the message of a file error, e.g. with what already succeeded
+    /// before the file couldn't be saved.
+    pub fn context(self, prefix: &str) -> Self {
+        match self {
+            GamError::File { path, message } => GamError::File {
+                path,
+                message: format!("{}{}", prefix, message),
+            },
+            other => other,
+        }
+    }
+
+    /// Attaches the file a config syntax error was found in.
+    pub fn in_file(self, file: &Path) -> Self {
+        match self {
+            GamError::ConfigSyntax { path: None, line } => GamError::ConfigSyntax {
+                path: Some(file.to_path_buf()),
+                line,
+            },
+            other => other,
+        }
+    }
+
+    /// The structured fields the frontend can act on.
+    pub fn details(&self) -> ErrorDetails {
+        let path = |p: &Path| Some(p.display().to_string());
+        match self {
+            GamError::GitFailed { status, .. } => ErrorDetails {
+                status: *status,
+                ..Default::default()
+            },
+            GamError::File { path: p, .. }
+            | GamError::FileLocked { path: p }
+            | GamError::FileChanged { path: p } => ErrorDetails {
+                path: path(p),
+                ..Default::default()
+            },
+            GamError::ConfigSyntax { path: p, line } => ErrorDetails {
+                path: p.as_deref().and_then(path),
+                line: Some(*line),
+                ..Default::default()
+            },
+            GamError::AliasExists {
+                name,
+                scope,
+                path: p,
+            }
+            | GamError::AliasNotFound {
+                name,
+                scope,
+                path: p,
+            } => ErrorDetails {
+                path: p.as_deref().and_then(path),
+                alias: Some(name.clone()),
+                scope: Some(*scope),
+                ..Default::default()
+            },
+            GamError::Batch(errors) => ErrorDetails {
+                operations: errors
+                    .iter()
+                    .map(|e| OperationError {
+                        index: e.index,
+                        operation: e.operation.clone(),
+                        code: e.error.code().to_string(),
+                        message: e.error.to_string(),
+                        details: e.error.details(),
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            _ => ErrorDetails::default(),
         }
     }
 }
 
+/// Structured details sent with an error over IPC; unset fields are omitted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Scope>,
+    /// 1-based line of a config syntax error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Git's exit status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<i32>,
+    /// The failed operations of a batch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operations: Vec<OperationError>,
+}
+
+impl ErrorDetails {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A failed batch operation, as sent over IPC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationError {
+    pub index: usize,
+    pub operation: String,
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+    pub details: ErrorDetails,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,9 +294,13 @@ mod tests {
 
     #[test]
     fn display_git_failed() {
-        let err = GamError::GitFailed("fatal: not a git repository".into());
+        let err = GamError::GitFailed {
+            message: "fatal: not a git repository".into(),
+            status: Some(128),
+        };
         assert!(err.to_string().contains("fatal"));
         assert_eq!(err.code(), "GIT_FAILED");
+        assert_eq!(err.details().status, Some(128));
     }
 
     #[test]
@@ -114,4 +331,24 @@ mod tests {
         assert!(err.to_string().contains("git_service"));
         assert_eq!(err.code(), "LOCK_POISONED");
     }
+
+    #[test]
+    fn batch_details_carry_each_operation() {
+        let err = GamError::Batch(vec![BatchError {
+            index: 2,
+            operation: "add \"co\" (global)".into(),
+            error: GamError::AliasExists {
+                name: "co".into(),
+                scope: Scope::Global,
+                path: Some(PathBuf::from("/home/me/.gitconfig")),
+            },
+        }]);
+        assert!(err.to_string().contains("#2 add \"co\" (global): Alias \"co\" already exists"));
+
+        let json = serde_json::to_value(err.details()).unwrap();
+        assert_eq!(json["operations"][0]["code"], "ALIAS_EXISTS");
+        assert_eq!(json["operations"][0]["details"]["alias"], "co");
+        assert_eq!(json["operations"][0]["details"]["scope"], "global");
+        assert!(json.get("path").is_none());
+    }
 }
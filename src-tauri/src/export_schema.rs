@@ -0,0 +1,184 @@
+use serde_json::{Map, Value};
+
+use crate::error::GamError;
+use crate::file_service::ExportData;
+
+/// Schema version `FileService::export_aliases` writes into JSON exports.
+pub const CURRENT_VERSION: &str = "1.1.0";
+
+/// An upgrade of an export from one schema version to the next.
+struct Migration {
+    from: &'static str,
+    to: &'static str,
+    apply: fn(&mut Map<String, Value>),
+}
+
+/// Every migration, oldest first. Files from any `from` version are upgraded
+/// step by step until they reach `CURRENT_VERSION`.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: "1.0.0",
+    to: "1.1.0",
+    apply: migrate_1_0_to_1_1,
+}];
+
+/// Schema versions `parse_export` can read, oldest first.
+pub fn supported_versions() -> Vec<&'static str> {
+    MIGRATIONS
+        .iter()
+        .map(|m| m.from)
+        .chain([CURRENT_VERSION])
+        .collect()
+}
+
+/// Parses a GAM JSON export of any supported schema version, migrating it to
+/// `CURRENT_VERSION`. Files from a newer GAM are rejected rather than read
+/// with fields silently dropped.
+pub fn parse_export(content: &str) -> Result<ExportData, GamError> {
+    migrate(content).map_err(GamError::Invalid)
+}
+
+fn migrate(content: &str) -> Result<ExportData, String> {
+    let value: Value =
+        serde_json::from_str(content).map_err(|_| "Invalid JSON file".to_string())?;
+    let Value::Object(mut data) = value else {
+        return Err("Invalid export file: expected a JSON object".to_string());
+    };
+
+    let mut version = match data.get("version") {
+        Some(Value::String(v)) => v.clone(),
+        Some(_) => return Err("Invalid export file: \"version\" must be a string".to_string()),
+        None => return Err("Invalid export file: missing \"version\" field".to_string()),
+    };
+    let current = parse_version(CURRENT_VERSION)?;
+    if release(parse_version(&version)?) > release(current) {
+        return Err(format!(
+            "This file uses export schema {}, which is newer than the {} this version of GAM reads. Update GAM to import it.",
+            version, CURRENT_VERSION
+        ));
+    }
+
+    while release(parse_version(&version)?) != release(current) {
+        let from = release(parse_version(&version)?);
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| parse_version(m.from).map(release) == Ok(from))
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported export schema version {} (supported: {})",
+                    version,
+                    supported_versions().join(", ")
+                )
+            })?;
+        (migration.apply)(&mut data);
+        version = migration.to.to_string();
+    }
+    data.insert(
+        "version".to_string(),
+        Value::String(CURRENT_VERSION.to_string()),
+    );
+
+    serde_json::from_value(Value::Object(data)).map_err(|e| format!("Invalid export file: {}", e))
+}
+
+/// JSON Schema for `ExportData`, derived from the Rust types, so other tools
+/// can validate GAM exports.
+pub fn json_schema() -> Value {
+    let mut schema = schemars::schema_for!(ExportData).to_value();
+    if let Value::Object(map) = &mut schema {
+        map.insert("title".to_string(), Value::from("GAM alias export"));
+        map.insert(
+            "description".to_string(),
+            Value::from(format!(
+                "Aliases and groups exported by Git Alias Manager, schema version {}.",
+                CURRENT_VERSION
+            )),
+        );
+    }
+    schema
+}
+
+/// Parses a `major.minor.patch` version.
+fn parse_version(version: &str) -> Result<(u64, u64, u64), String> {
+    let invalid = || format!("Invalid export schema version: {}", version);
+    let mut parts = version.split('.').map(|p| p.parse::<u64>());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Ok((major, minor, patch)),
+        _ => Err(invalid()),
+    }
+}
+
+/// The part of a version that changes the format; patch releases only ever
+/// fix how GAM writes the same fields.
+fn release((major, minor, _): (u64, u64, u64)) -> (u64, u64) {
+    (major, minor)
+}
+
+/// 1.0 exports carried runtime data next to each alias (the history `score`,
+/// and the `origin` file and `include_chain` of the exporting machine) and
+/// wrote the global scope as an empty string. 1.1 drops the runtime data and
+/// always names the scope.
+fn migrate_1_0_to_1_1(data: &mut Map<String, Value>) {
+    let Some(Value::Array(aliases)) = data.get_mut("aliases") else {
+        return;
+    };
+    for alias in aliases.iter_mut().filter_map(Value::as_object_mut) {
+        for field in ["score", "origin", "include_chain"] {
+            alias.remove(field);
+        }
+        let scope = alias
+            .get("scope")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if scope.is_empty() {
+            alias.insert("scope".to_string(), Value::from("global"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_service::Scope;
+
+    #[test]
+    fn migrates_1_0_exports() {
+        let json = r#"{"version":"1.0.0","exportedAt":"2026-01-01T00:00:00Z","aliases":[{"name":"co","command":"checkout","scope":"","score":3.5,"origin":"/home/me/.gitconfig"},{"name":"st","command":"status","scope":"local","local_path":"/tmp/repo"}]}"#;
+        let data = parse_export(json).unwrap();
+        assert_eq!(data.version, CURRENT_VERSION);
+        assert_eq!(data.aliases[0].scope, Scope::Global);
+        assert_eq!(data.aliases[0].score, None);
+        assert_eq!(data.aliases[0].origin, None);
+        assert_eq!(data.aliases[1].local_path.as_deref(), Some("/tmp/repo"));
+    }
+
+    #[test]
+    fn rejects_newer_and_unknown_versions() {
+        let newer = r#"{"version":"1.2.0","exportedAt":"2030-01-01T00:00:00Z","aliases":[]}"#;
+        assert!(parse_export(newer).unwrap_err().to_string().contains("Update GAM"));
+
+        let patch = r#"{"version":"1.1.7","exportedAt":"2026-01-01T00:00:00Z","aliases":[]}"#;
+        assert!(parse_export(patch).is_ok());
+
+        let old = r#"{"version":"0.9.0","exportedAt":"2025-01-01T00:00:00Z","aliases":[]}"#;
+        assert!(
+            parse_export(old)
+                .unwrap_err()
+                .to_string()
+                .contains("supported: 1.0.0, 1.1.0")
+        );
+
+        let garbled = r#"{"version":"one","aliases":[]}"#;
+        assert!(parse_export(garbled).is_err());
+    }
+
+    #[test]
+    fn checked_in_schema_is_current() {
+        let checked_in: Value =
+            serde_json::from_str(include_str!("../../docs/gam-export.schema.json")).unwrap();
+        assert_eq!(
+            checked_in,
+            json_schema(),
+            "regenerate docs/gam-export.schema.json with `gam schema`"
+        );
+    }
+}
@@ -1,19 +1,197 @@
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::SystemTime;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::git_service::GitAlias;
+use crate::alias_graph;
+use crate::error::GamError;
+use crate::export_schema;
+use crate::git_config::{self, FileChange, IncludeContext};
+use crate::git_service::{AliasOperation, GitAlias, GitService, Scope};
 use crate::group_service::{AliasGroup, GroupData};
+use crate::ranking_service::{RankingService, ShellAlias};
 
+/// Prefix for the shell shortcuts of the shell export formats (`gco`).
+pub const DEFAULT_SHELL_PREFIX: &str = "g";
+
+/// Where dotfiles repositories usually keep their gitconfig, relative to the
+/// repository root. Later files override earlier ones.
+const DOTFILE_CONFIGS: &[&str] = &[
+    "gitconfig",
+    ".gitconfig",
+    "git/config",
+    "git/gitconfig",
+    ".config/git/config",
+    ".gitconfig.local",
+];
+
+/// What to do with an incoming alias whose name is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictResolution {
+    #[default]
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl FromStr for ConflictResolution {
+    type Err = GamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(ConflictResolution::Skip),
+            "overwrite" => Ok(ConflictResolution::Overwrite),
+            "rename" => Ok(ConflictResolution::Rename),
+            _ => Err(GamError::Invalid(format!(
+                "Unsupported conflict resolution: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// An incoming alias whose name is already defined, with a different
+/// command, in the scope it would be imported into.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportConflict {
+    pub incoming: GitAlias,
+    pub existing: GitAlias,
+    /// A free name to use when the conflict is resolved by renaming.
+    pub suggested_name: String,
+}
+
+/// Incoming aliases sorted by what importing them would do.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportPreview {
+    /// Aliases that can be added as they are.
+    pub aliases: Vec<GitAlias>,
+    pub conflicts: Vec<ImportConflict>,
+    /// Aliases already defined with the same command.
+    pub unchanged: Vec<GitAlias>,
+    /// Groups and assignments carried by a GAM export, imported by
+    /// `apply_import` together with the aliases.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<GroupData>,
+}
+
+/// The user's decision for one conflicting alias.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportChoice {
+    pub name: String,
+    #[serde(default, deserialize_with = "Scope::deserialize_lenient")]
+    pub scope: Scope,
+    pub resolution: ConflictResolution,
+    /// Name to use with `rename`; defaults to the suggested name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub added: Vec<String>,
+    pub overwritten: Vec<String>,
+    /// New names of renamed aliases.
+    pub renamed: Vec<String>,
+    pub skipped: Vec<String>,
+    /// Config files the import changed, for the undo journal.
+    #[serde(skip)]
+    pub changes: Vec<FileChange>,
+}
+
+/// File formats `FileService::export_aliases` can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// GAM's own `ExportData`, which `import_aliases` reads back.
+    #[default]
+    Json,
+    /// An `[alias]` section that can be `[include]`d from any gitconfig.
+    Gitconfig,
+    /// A cheat sheet with one table per group.
+    Markdown,
+    /// `alias g<name>='git <name>'` lines to `source` from `~/.zshrc`.
+    Zsh,
+    /// The same `alias` lines for `~/.bashrc`.
+    Bash,
+    /// A fish `conf.d` file defining `abbr`s.
+    Fish,
+}
+
+impl ExportFormat {
+    /// Picks the format from a file extension, falling back to JSON.
+    pub fn from_path(path: &str) -> Self {
+        let ext = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "gitconfig" | "inc" | "conf" | "cfg" => ExportFormat::Gitconfig,
+            "md" | "markdown" => ExportFormat::Markdown,
+            "zsh" => ExportFormat::Zsh,
+            "bash" | "sh" => ExportFormat::Bash,
+            "fish" => ExportFormat::Fish,
+            _ if Path::new(path)
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with(".gitconfig")) =>
+            {
+                ExportFormat::Gitconfig
+            }
+            _ => ExportFormat::Json,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Gitconfig => "gitconfig",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Zsh => "zsh",
+            ExportFormat::Bash => "bash",
+            ExportFormat::Fish => "fish",
+        }
+    }
+
+    /// Whether the export defines shell shortcuts rather than git aliases.
+    pub fn is_shell(self) -> bool {
+        matches!(
+            self,
+            ExportFormat::Zsh | ExportFormat::Bash | ExportFormat::Fish
+        )
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = GamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ExportFormat::Json),
+            "gitconfig" => Ok(ExportFormat::Gitconfig),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "zsh" => Ok(ExportFormat::Zsh),
+            "bash" | "sh" => Ok(ExportFormat::Bash),
+            "fish" => Ok(ExportFormat::Fish),
+            _ => Err(GamError::Invalid(format!("Unsupported export format: {}", s))),
+        }
+    }
+}
+
+/// A GAM JSON export. Read it through `export_schema::parse_export`, which
+/// migrates files written by older versions.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ExportData {
+    /// Schema version, `major.minor.patch`.
     pub version: String,
     #[serde(rename = "exportedAt")]
     pub exported_at: String,
     pub aliases: Vec<GitAlias>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub groups: Option<Vec<AliasGroup>>,
+    /// Alias name → IDs of the groups it belongs to.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub assignments: Option<std::collections::HashMap<String, Vec<String>>>,
 }
@@ -22,72 +200,595 @@ pub struct ExportData {
 pub struct FileService;
 
 impl FileService {
+    /// Writes `aliases` to `file_path`. Without an explicit `format` it is
+    /// chosen from the file extension. `prefix` names the shell shortcuts of
+    /// the shell formats (`g` + `co` → `gco`) and defaults to
+    /// [`DEFAULT_SHELL_PREFIX`].
     pub fn export_aliases(
         aliases: &[GitAlias],
         file_path: &str,
         group_data: Option<&GroupData>,
-    ) -> Result<String, String> {
-        let export_data = ExportData {
-            version: "1.0.0".to_string(),
-            exported_at: iso8601_now(),
-            aliases: aliases.to_vec(),
-            groups: group_data.map(|gd| gd.groups.clone()),
-            assignments: group_data.map(|gd| gd.assignments.clone()),
+        format: Option<ExportFormat>,
+        prefix: Option<&str>,
+    ) -> Result<String, GamError> {
+        let format = format.unwrap_or_else(|| ExportFormat::from_path(file_path));
+        let content = match format {
+            ExportFormat::Json => {
+                // Scores and origin files only mean something on this machine
+                let aliases = aliases
+                    .iter()
+                    .map(|a| GitAlias {
+                        score: None,
+                        origin: None,
+                        include_chain: Vec::new(),
+                        ..a.clone()
+                    })
+                    .collect();
+                let export_data = ExportData {
+                    version: export_schema::CURRENT_VERSION.to_string(),
+                    exported_at: iso8601_now(),
+                    aliases,
+                    groups: group_data.map(|gd| gd.groups.clone()),
+                    assignments: group_data.map(|gd| gd.assignments.clone()),
+                };
+                serde_json::to_string_pretty(&export_data)?
+            }
+            ExportFormat::Gitconfig => render_gitconfig(aliases, group_data),
+            ExportFormat::Markdown => render_markdown(aliases, group_data),
+            ExportFormat::Zsh | ExportFormat::Bash | ExportFormat::Fish => render_shell(
+                aliases,
+                group_data,
+                format,
+                prefix.unwrap_or(DEFAULT_SHELL_PREFIX),
+            )?,
         };
 
-        let json = serde_json::to_string_pretty(&export_data)
-            .map_err(|e| format!("Failed to serialize: {}", e))?;
-
-        fs::write(file_path, &json).map_err(|e| format!("Failed to write file: {}", e))?;
+        fs::write(file_path, &content)
+            .map_err(|e| GamError::file("write", Path::new(file_path), e))?;
 
         Ok(file_path.to_string())
     }
 
-    pub fn import_aliases(file_path: &str) -> Result<Vec<GitAlias>, String> {
+    /// Reads aliases from a GAM JSON export, a gitconfig file (following its
+    /// includes) or a dotfiles repository directory.
+    pub fn import_aliases(file_path: &str) -> Result<Vec<GitAlias>, GamError> {
+        if Path::new(file_path).is_dir() {
+            return Self::import_dotfiles(Path::new(file_path));
+        }
+
         // Cap file size to prevent OOM on maliciously large files
         const MAX_IMPORT_SIZE: u64 = 10 * 1024 * 1024; // 10 MB
+        let read_failed = |e| GamError::file("read", Path::new(file_path), e);
         let meta = fs::metadata(file_path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
+            .map_err(read_failed)?;
         if meta.len() > MAX_IMPORT_SIZE {
-            return Err(format!(
+            return Err(GamError::Invalid(format!(
                 "File too large ({:.1} MB). Maximum import size is 10 MB.",
                 meta.len() as f64 / 1_048_576.0
-            ));
+            )));
         }
 
-        let content =
-            fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let content = fs::read_to_string(file_path).map_err(read_failed)?;
 
-        let data: ExportData =
-            serde_json::from_str(&content).map_err(|_| "Invalid JSON file".to_string())?;
+        if !content.trim_start().starts_with('{') {
+            return Self::import_gitconfig(Path::new(file_path));
+        }
+
+        let data = export_schema::parse_export(&content)?;
 
         // Validate structure
         for alias in &data.aliases {
             if alias.name.is_empty() {
-                return Err("Invalid alias: missing or invalid \"name\" field".to_string());
+                return Err(GamError::Invalid(
+                    "Invalid alias: missing or invalid \"name\" field".to_string(),
+                ));
             }
             if alias.command.is_empty() {
-                return Err(format!(
+                return Err(GamError::Invalid(format!(
                     "Invalid alias \"{}\": missing or invalid \"command\" field",
                     alias.name
-                ));
+                )));
             }
         }
 
-        // Default scope to global if not specified
-        let aliases: Vec<GitAlias> = data
-            .aliases
-            .into_iter()
-            .map(|mut a| {
-                if a.scope.is_empty() {
-                    a.scope = "global".to_string();
-                }
-                a
+        // A missing or empty scope already defaults to global on deserialization
+        Ok(data.aliases)
+    }
+
+    /// Reads the `[alias]` entries of a gitconfig file as global aliases.
+    /// Relative include paths resolve against the including file; `includeIf`
+    /// conditions don't match since the file isn't read from a repository.
+    fn import_gitconfig(path: &Path) -> Result<Vec<GitAlias>, GamError> {
+        let entries = git_config::read_with_includes(path, &IncludeContext::default())
+            .map_err(|e| match e {
+                GamError::ConfigSyntax { path: Some(path), line } => GamError::File {
+                    message: format!(
+                        "Not a GAM export or gitconfig file: bad config line {} in file {}",
+                        line,
+                        path.display()
+                    ),
+                    path,
+                },
+                e => e,
+            })?;
+        let aliases = GitService::aliases_from_entries(&entries, Scope::Global, None);
+        // Like git, keep only the last definition of each name
+        Ok(aliases
+            .iter()
+            .enumerate()
+            .filter(|(i, a)| {
+                !aliases[i + 1..]
+                    .iter()
+                    .any(|later| later.name.eq_ignore_ascii_case(&a.name))
             })
-            .collect();
+            .map(|(_, a)| a.clone())
+            .collect())
+    }
 
+    fn import_dotfiles(dir: &Path) -> Result<Vec<GitAlias>, GamError> {
+        let mut aliases: Vec<GitAlias> = Vec::new();
+        let mut found = false;
+        for candidate in DOTFILE_CONFIGS {
+            let path = dir.join(candidate);
+            if !path.is_file() {
+                continue;
+            }
+            found = true;
+            for alias in Self::import_gitconfig(&path)? {
+                aliases.retain(|a| !a.name.eq_ignore_ascii_case(&alias.name));
+                aliases.push(alias);
+            }
+        }
+        if !found {
+            return Err(GamError::NotFound(format!(
+                "gitconfig file in {}",
+                dir.display()
+            )));
+        }
         Ok(aliases)
     }
+
+    /// Proposes git aliases for the shell aliases and fish abbreviations in
+    /// `path`, or in the usual shell startup files, that wrap `git ...`.
+    pub fn import_shell_aliases(path: Option<&str>) -> Result<Vec<GitAlias>, GamError> {
+        let files = match path {
+            Some(path) => vec![PathBuf::from(path)],
+            None => RankingService::shell_alias_files(),
+        };
+        if files.is_empty() {
+            return Err(GamError::NotFound("shell startup files".to_string()));
+        }
+
+        let mut shell_aliases: Vec<ShellAlias> = Vec::new();
+        for file in &files {
+            for alias in RankingService::read_shell_aliases(file)? {
+                // A later definition replaces an earlier one, as in the shell
+                shell_aliases.retain(|a| a.name != alias.name);
+                shell_aliases.push(alias);
+            }
+        }
+        Ok(shell_to_git_aliases(&shell_aliases))
+    }
+
+    /// Sorts incoming aliases into new ones, conflicts and ones already present.
+    ///
+    /// This isn't `alias_diff::diff`: an import only adds, so nothing counts
+    /// as removed, and an alias under another scope or in another repository
+    /// is a new alias rather than a moved one. The diff matches by name and
+    /// scope alone and would pair aliases of different repositories.
+    pub fn preview_import(
+        git: &mut GitService,
+        incoming: Vec<GitAlias>,
+    ) -> Result<ImportPreview, GamError> {
+        let mut existing = git.get_aliases(None)?;
+        let selected = git.get_local_path();
+        let incoming = last_definitions(incoming, selected.as_deref());
+        // Repositories GAM hasn't seen yet aren't covered by get_aliases
+        let mut repos: Vec<&str> = incoming
+            .iter()
+            .filter(|a| a.scope.is_repo_scoped())
+            .filter_map(|a| a.local_path.as_deref())
+            .collect();
+        repos.sort();
+        repos.dedup();
+        for repo in repos {
+            if !existing
+                .iter()
+                .any(|a| a.local_path.as_deref() == Some(repo))
+            {
+                for scope in [Scope::Local, Scope::Worktree] {
+                    existing.extend(git.read_scope(scope, Some(repo)));
+                }
+            }
+        }
+        let mut taken: Vec<String> = existing
+            .iter()
+            .chain(incoming.iter())
+            .map(|a| a.name.to_lowercase())
+            .collect();
+
+        let mut preview = ImportPreview::default();
+        for alias in incoming {
+            match find_existing(&existing, &alias, selected.as_deref()) {
+                None => preview.aliases.push(alias),
+                Some(current) if current.command == alias.command => preview.unchanged.push(alias),
+                Some(current) => {
+                    let suggested_name = free_name(&alias.name, &taken);
+                    taken.push(suggested_name.to_lowercase());
+                    preview.conflicts.push(ImportConflict {
+                        existing: current.clone(),
+                        incoming: alias,
+                        suggested_name,
+                    });
+                }
+            }
+        }
+        Ok(preview)
+    }
+
+    /// Imports `incoming`, resolving conflicts with `choices`. Conflicts are
+    /// re-checked against the current config; those without a choice are
+    /// skipped. The changes are applied as one batch, so either every alias
+    /// is imported or none is.
+    pub fn apply_import(
+        git: &mut GitService,
+        incoming: Vec<GitAlias>,
+        choices: &[ImportChoice],
+    ) -> Result<ImportSummary, GamError> {
+        let preview = Self::preview_import(git, incoming)?;
+        let mut summary = ImportSummary {
+            skipped: preview.unchanged.iter().map(|a| a.name.clone()).collect(),
+            ..Default::default()
+        };
+        let add = |name: &str, alias: &GitAlias| AliasOperation::Add {
+            name: name.to_string(),
+            command: alias.command.clone(),
+            scope: alias.scope,
+            local_path: alias.local_path.clone(),
+        };
+
+        let mut operations = Vec::new();
+        for alias in &preview.aliases {
+            operations.push(add(&alias.name, alias));
+            summary.added.push(alias.name.clone());
+        }
+
+        for conflict in &preview.conflicts {
+            let alias = &conflict.incoming;
+            let choice = choices
+                .iter()
+                .find(|c| c.scope == alias.scope && c.name.eq_ignore_ascii_case(&alias.name));
+            match choice.map(|c| c.resolution).unwrap_or_default() {
+                ConflictResolution::Skip => summary.skipped.push(alias.name.clone()),
+                ConflictResolution::Overwrite => {
+                    operations.push(AliasOperation::Update {
+                        old_name: conflict.existing.name.clone(),
+                        name: alias.name.clone(),
+                        command: alias.command.clone(),
+                        scope: alias.scope,
+                        local_path: alias.local_path.clone(),
+                        origin: conflict.existing.origin.clone(),
+                    });
+                    summary.overwritten.push(alias.name.clone());
+                }
+                ConflictResolution::Rename => {
+                    let name = choice
+                        .and_then(|c| c.new_name.clone())
+                        .unwrap_or_else(|| conflict.suggested_name.clone());
+                    operations.push(add(&name, alias));
+                    summary.renamed.push(name);
+                }
+            }
+        }
+
+        // All or nothing: a failure leaves every config file as it was
+        summary.changes = git.apply_batch(&operations)?;
+        Ok(summary)
+    }
+}
+
+/// Converts shell aliases that run git into global git aliases. `gco` becomes
+/// `co` unless that is a built-in or already proposed, in which case the
+/// shell name is kept.
+fn shell_to_git_aliases(shell_aliases: &[ShellAlias]) -> Vec<GitAlias> {
+    let mut out: Vec<GitAlias> = Vec::new();
+    for shell_alias in shell_aliases {
+        let Some(command) = git_command_for(&shell_alias.expansion) else {
+            continue;
+        };
+        let taken = |name: &str| {
+            alias_graph::is_builtin(name)
+                || GitService::validate_alias_name(name).is_err()
+                || out.iter().any(|a| a.name.eq_ignore_ascii_case(name))
+        };
+        let short = shell_alias.name.strip_prefix('g').filter(|n| !n.is_empty());
+        let name = match short {
+            Some(short) if !taken(short) => short.to_string(),
+            _ if !taken(&shell_alias.name) => shell_alias.name.clone(),
+            _ => continue,
+        };
+        out.push(GitAlias {
+            name,
+            command,
+            scope: Scope::Global,
+            local_path: None,
+            score: None,
+            origin: Some(shell_alias.source.display().to_string()),
+            include_chain: Vec::new(),
+        });
+    }
+    out
+}
+
+/// The git alias command for a shell expansion such as `git checkout`, or
+/// `None` when it doesn't run git. Expansions using shell syntax become `!`
+/// aliases, unless they call commands other than git through `$(...)`, which
+/// usually means shell functions git can't see.
+fn git_command_for(expansion: &str) -> Option<String> {
+    let rest = expansion.trim().strip_prefix("git")?;
+    if !rest.starts_with(char::is_whitespace) || rest.trim().is_empty() {
+        return None;
+    }
+    if !expansion.contains(['|', '&', ';', '<', '>', '$', '`', '(']) {
+        return Some(rest.trim().to_string());
+    }
+    let substitutions_run_git = expansion
+        .split("$(")
+        .skip(1)
+        .chain(expansion.split('`').skip(1).step_by(2))
+        .all(|inner| inner.trim_start().starts_with("git "));
+    substitutions_run_git.then(|| format!("!{}", expansion.trim()))
+}
+
+/// The alias `incoming` would collide with: same name and scope, and for
+/// repository scopes the same repository.
+fn find_existing<'a>(
+    existing: &'a [GitAlias],
+    incoming: &GitAlias,
+    selected: Option<&str>,
+) -> Option<&'a GitAlias> {
+    let repo = incoming.local_path.as_deref().or(selected);
+    existing.iter().rev().find(|a| {
+        a.scope == incoming.scope
+            && a.name.eq_ignore_ascii_case(&incoming.name)
+            && (!a.scope.is_repo_scoped() || a.local_path.as_deref() == repo)
+    })
+}
+
+/// The last definition of each name per scope and repository, the one git
+/// uses. An export lists a shadowed alias once for every definition.
+fn last_definitions(aliases: Vec<GitAlias>, selected: Option<&str>) -> Vec<GitAlias> {
+    let repo = |a: &GitAlias| {
+        a.scope
+            .is_repo_scoped()
+            .then(|| a.local_path.clone().or_else(|| selected.map(str::to_string)))
+    };
+    let mut kept: Vec<GitAlias> = Vec::new();
+    for alias in aliases {
+        kept.retain(|a| {
+            a.scope != alias.scope
+                || !a.name.eq_ignore_ascii_case(&alias.name)
+                || repo(a) != repo(&alias)
+        });
+        kept.push(alias);
+    }
+    kept
+}
+
+/// `name-2`, `name-3`, ... whichever is not in `taken` (lowercase names).
+fn free_name(name: &str, taken: &[String]) -> String {
+    (2..)
+        .map(|n| format!("{}-{}", name, n))
+        .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Splits aliases into `(group, aliases)` sections in group order, followed
+/// by a `None` section for ungrouped aliases. With `each_once`, an alias in
+/// several groups only appears under the first one.
+fn sections<'a>(
+    aliases: &'a [GitAlias],
+    group_data: Option<&'a GroupData>,
+    each_once: bool,
+) -> Vec<(Option<&'a AliasGroup>, Vec<&'a GitAlias>)> {
+    let groups: &[AliasGroup] = group_data
+        .map(|gd| gd.groups.as_slice())
+        .unwrap_or_default();
+    let group_ids = |alias: &GitAlias| -> Vec<String> {
+        group_data
+            .and_then(|gd| gd.assignments.get(&alias.name))
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let mut placed = vec![false; aliases.len()];
+    let mut out = Vec::new();
+    for group in groups {
+        let mut members: Vec<&GitAlias> = Vec::new();
+        for (i, alias) in aliases.iter().enumerate() {
+            if !(each_once && placed[i]) && group_ids(alias).contains(&group.id) {
+                placed[i] = true;
+                members.push(alias);
+            }
+        }
+        if !members.is_empty() {
+            out.push((Some(group), members));
+        }
+    }
+    let rest: Vec<&GitAlias> = aliases
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !placed[*i])
+        .map(|(_, a)| a)
+        .collect();
+    if !rest.is_empty() {
+        out.push((None, rest));
+    }
+    out
+}
+
+/// Keeps only the last (effective) alias of each name, for aliases exported
+/// from several scopes.
+fn effective_aliases(aliases: &[GitAlias]) -> Vec<GitAlias> {
+    aliases
+        .iter()
+        .enumerate()
+        .filter(|(i, a)| {
+            !aliases[i + 1..]
+                .iter()
+                .any(|later| later.name.eq_ignore_ascii_case(&a.name))
+        })
+        .map(|(_, a)| a.clone())
+        .collect()
+}
+
+/// Renders an `[alias]` section that can be `[include]`d directly. When a
+/// name is exported from several scopes, only the last (effective) one is kept.
+fn render_gitconfig(aliases: &[GitAlias], group_data: Option<&GroupData>) -> String {
+    let unique = effective_aliases(aliases);
+
+    let mut out = format!(
+        "# Git aliases exported by GAM on {}\n# Add to your config with:\n#   [include]\n#       path = <this file>\n[alias]\n",
+        iso8601_now()
+    );
+    let sections = sections(&unique, group_data, true);
+    let grouped = sections.iter().any(|(group, _)| group.is_some());
+    for (group, members) in sections {
+        if grouped {
+            let title = group.map(|g| g.name.as_str()).unwrap_or("Ungrouped");
+            out.push_str(&format!("\t# {}\n", title.replace('\n', " ")));
+        }
+        for alias in members {
+            out.push_str(&git_config::format_entry(&alias.name, &alias.command));
+        }
+    }
+    out
+}
+
+/// Renders a Markdown cheat sheet with a table per group.
+fn render_markdown(aliases: &[GitAlias], group_data: Option<&GroupData>) -> String {
+    let mut out = format!("# Git Aliases\n\n_Exported by GAM on {}._\n", iso8601_now());
+    let sections = sections(aliases, group_data, false);
+    let grouped = sections.iter().any(|(group, _)| group.is_some());
+    for (group, members) in sections {
+        out.push('\n');
+        if grouped {
+            let title = group.map(|g| g.name.as_str()).unwrap_or("Ungrouped");
+            out.push_str(&format!("## {}\n\n", title));
+        }
+        out.push_str("| Alias | Command | Scope |\n| ----- | ------- | ----- |\n");
+        for alias in members {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                markdown_code(&format!("git {}", alias.name)),
+                markdown_code(&alias.command),
+                alias.scope
+            ));
+        }
+    }
+    out
+}
+
+/// Renders `<prefix><name>` shortcuts for `git <name>`: `alias` lines for
+/// zsh/bash, or `abbr`s in a fish `conf.d` file. Groups become comment
+/// sections, and a shortcut that hides a command on `PATH` is flagged.
+fn render_shell(
+    aliases: &[GitAlias],
+    group_data: Option<&GroupData>,
+    format: ExportFormat,
+    prefix: &str,
+) -> Result<String, GamError> {
+    if !prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(GamError::Invalid(format!(
+            "Invalid shortcut prefix \"{}\": use letters, digits, '-' or '_'",
+            prefix
+        )));
+    }
+
+    let fish = format == ExportFormat::Fish;
+    let mut out = format!("# Git shortcuts exported by GAM on {}\n", iso8601_now());
+    let indent = if fish {
+        out.push_str(
+            "# Save as ~/.config/fish/conf.d/gam-aliases.fish\nif status is-interactive\n",
+        );
+        "    "
+    } else {
+        let rc = if format == ExportFormat::Zsh {
+            "~/.zshrc"
+        } else {
+            "~/.bashrc"
+        };
+        out.push_str(&format!(
+            "# Load from {} with:\n#   source <this file>\n",
+            rc
+        ));
+        ""
+    };
+
+    let unique = effective_aliases(aliases);
+    let sections = sections(&unique, group_data, true);
+    let grouped = sections.iter().any(|(group, _)| group.is_some());
+    for (group, members) in sections {
+        if grouped {
+            let title = group.map(|g| g.name.as_str()).unwrap_or("Ungrouped");
+            out.push_str(&format!("\n{}# {}\n", indent, title.replace('\n', " ")));
+        }
+        for alias in members {
+            let shortcut = format!("{}{}", prefix, alias.name);
+            let target = format!("git {}", alias.name);
+            if let Some(existing) = alias_graph::find_executable(&shortcut) {
+                out.push_str(&format!(
+                    "{}# {} hides {}\n",
+                    indent,
+                    shortcut,
+                    existing.display()
+                ));
+            }
+            if fish {
+                out.push_str(&format!(
+                    "{}abbr --add {} {}\n",
+                    indent,
+                    shortcut,
+                    fish_quote(&target)
+                ));
+            } else {
+                out.push_str(&format!(
+                    "alias {}={}\n",
+                    shortcut,
+                    alias_graph::shell_quote(&target)
+                ));
+            }
+        }
+    }
+    if fish {
+        out.push_str("end\n");
+    }
+    Ok(out)
+}
+
+/// Quotes `word` for fish, which only knows `\\` and `\'` inside single quotes.
+fn fish_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Wraps `text` in a code span that survives a GFM table cell.
+fn markdown_code(text: &str) -> String {
+    let text = text.replace('\n', "\\n").replace('|', "\\|");
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest + 1);
+    if longest > 0 {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
 }
 
 /// Produce a proper ISO 8601 timestamp (UTC) without the chrono crate.
@@ -128,6 +829,7 @@ fn iso8601_now() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git_service::Scope;
     use std::fs;
 
     #[test]
@@ -149,15 +851,17 @@ mod tests {
         let aliases = vec![GitAlias {
             name: "co".to_string(),
             command: "checkout".to_string(),
-            scope: "global".to_string(),
+            scope: Scope::Global,
             local_path: None,
             score: None,
+            origin: None,
+            include_chain: Vec::new(),
         }];
-        FileService::export_aliases(&aliases, tmp.to_str().unwrap(), None).unwrap();
+        FileService::export_aliases(&aliases, tmp.to_str().unwrap(), None, None, None).unwrap();
 
         let content = fs::read_to_string(&tmp).unwrap();
         let data: ExportData = serde_json::from_str(&content).unwrap();
-        assert_eq!(data.version, "1.0.0");
+        assert_eq!(data.version, export_schema::CURRENT_VERSION);
         assert_eq!(data.aliases.len(), 1);
         assert_eq!(data.aliases[0].name, "co");
         assert!(data.exported_at.ends_with('Z'));
@@ -172,7 +876,7 @@ mod tests {
         fs::write(&tmp, json).unwrap();
 
         let err = FileService::import_aliases(tmp.to_str().unwrap()).unwrap_err();
-        assert!(err.contains("missing or invalid \"name\""));
+        assert!(err.to_string().contains("missing or invalid \"name\""));
 
         fs::remove_file(&tmp).ok();
     }
@@ -184,7 +888,7 @@ mod tests {
         fs::write(&tmp, json).unwrap();
 
         let aliases = FileService::import_aliases(tmp.to_str().unwrap()).unwrap();
-        assert_eq!(aliases[0].scope, "global");
+        assert_eq!(aliases[0].scope, Scope::Global);
 
         fs::remove_file(&tmp).ok();
     }
@@ -196,20 +900,24 @@ mod tests {
             GitAlias {
                 name: "co".to_string(),
                 command: "checkout".to_string(),
-                scope: "global".to_string(),
+                scope: Scope::Global,
                 local_path: None,
                 score: None,
+                origin: None,
+                include_chain: Vec::new(),
             },
             GitAlias {
                 name: "st".to_string(),
                 command: "status -sb".to_string(),
-                scope: "local".to_string(),
+                scope: Scope::Local,
                 local_path: Some("/tmp/repo".to_string()),
                 score: None,
+                origin: None,
+                include_chain: Vec::new(),
             },
         ];
 
-        FileService::export_aliases(&original, tmp.to_str().unwrap(), None).unwrap();
+        FileService::export_aliases(&original, tmp.to_str().unwrap(), None, None, None).unwrap();
         let imported = FileService::import_aliases(tmp.to_str().unwrap()).unwrap();
 
         assert_eq!(imported.len(), 2);
@@ -218,4 +926,393 @@ mod tests {
 
         fs::remove_file(&tmp).ok();
     }
+
+    #[test]
+    fn import_accepts_system_and_worktree_scopes() {
+        let tmp = std::env::temp_dir().join("gam_test_import_new_scopes.json");
+        let json = r#"{"version":"1.0.0","exportedAt":"2026-01-01T00:00:00Z","aliases":[{"name":"co","command":"checkout","scope":"system"},{"name":"st","command":"status","scope":"worktree","local_path":"/tmp/repo"},{"name":"lg","command":"log"}]}"#;
+        fs::write(&tmp, json).unwrap();
+
+        let aliases = FileService::import_aliases(tmp.to_str().unwrap()).unwrap();
+        assert_eq!(aliases[0].scope, Scope::System);
+        assert_eq!(aliases[1].scope, Scope::Worktree);
+        assert_eq!(aliases[2].scope, Scope::Global);
+
+        fs::remove_file(&tmp).ok();
+    }
+
+    #[test]
+    fn import_rejects_unknown_scope() {
+        let tmp = std::env::temp_dir().join("gam_test_import_bad_scope.json");
+        let json = r#"{"version":"1.0.0","exportedAt":"2026-01-01T00:00:00Z","aliases":[{"name":"co","command":"checkout","scope":"everywhere"}]}"#;
+        fs::write(&tmp, json).unwrap();
+
+        assert!(FileService::import_aliases(tmp.to_str().unwrap()).is_err());
+
+        fs::remove_file(&tmp).ok();
+    }
+
+    fn sample_aliases() -> Vec<GitAlias> {
+        let alias = |name: &str, command: &str, scope: Scope| GitAlias {
+            name: name.to_string(),
+            command: command.to_string(),
+            scope,
+            local_path: None,
+            score: None,
+            origin: None,
+            include_chain: Vec::new(),
+        };
+        vec![
+            alias("co", "checkout", Scope::System),
+            alias("co", "switch", Scope::Global),
+            alias("lg", "log --graph --format='%h | %s'", Scope::Global),
+            alias("sync", "!git fetch && git rebase # upstream", Scope::Global),
+        ]
+    }
+
+    fn sample_groups() -> GroupData {
+        GroupData {
+            groups: vec![AliasGroup {
+                id: "g-1".to_string(),
+                name: "History".to_string(),
+                color: "#fff".to_string(),
+            }],
+            assignments: [("lg".to_string(), vec!["g-1".to_string()])]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn export_format_from_path_and_name() {
+        assert_eq!(ExportFormat::from_path("/tmp/a.json"), ExportFormat::Json);
+        assert_eq!(
+            ExportFormat::from_path("/tmp/team.inc"),
+            ExportFormat::Gitconfig
+        );
+        assert_eq!(
+            ExportFormat::from_path("/home/me/.gitconfig"),
+            ExportFormat::Gitconfig
+        );
+        assert_eq!(
+            ExportFormat::from_path("CHEATSHEET.MD"),
+            ExportFormat::Markdown
+        );
+        assert_eq!(ExportFormat::from_path("aliases"), ExportFormat::Json);
+        assert_eq!("md".parse::<ExportFormat>().unwrap(), ExportFormat::Markdown);
+        assert!("yaml".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn export_gitconfig_parses_back_with_groups_as_comments() {
+        let tmp = std::env::temp_dir().join("gam_test_export.gitconfig");
+        let groups = sample_groups();
+        FileService::export_aliases(
+            &sample_aliases(),
+            tmp.to_str().unwrap(),
+            Some(&groups),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let content = fs::read_to_string(&tmp).unwrap();
+        assert!(content.contains("\t# History\n\tlg = "));
+        assert!(content.contains("\t# Ungrouped\n"));
+        let config = crate::git_config::ConfigFile::parse(&content).unwrap();
+        let get = |name: &str| {
+            config
+                .get("alias", None, name)
+                .and_then(|e| e.value.clone())
+        };
+        assert_eq!(get("co").as_deref(), Some("switch"));
+        assert_eq!(get("lg").as_deref(), Some("log --graph --format='%h | %s'"));
+        assert_eq!(
+            get("sync").as_deref(),
+            Some("!git fetch && git rebase # upstream")
+        );
+        assert_eq!(content.matches("\tco = ").count(), 1);
+
+        fs::remove_file(&tmp).ok();
+    }
+
+    #[test]
+    fn export_markdown_tables_per_group() {
+        let tmp = std::env::temp_dir().join("gam_test_export_cheatsheet.txt");
+        let groups = sample_groups();
+        FileService::export_aliases(
+            &sample_aliases(),
+            tmp.to_str().unwrap(),
+            Some(&groups),
+            Some(ExportFormat::Markdown),
+            None,
+        )
+        .unwrap();
+
+        let content = fs::read_to_string(&tmp).unwrap();
+        let history = content.find("## History").unwrap();
+        let ungrouped = content.find("## Ungrouped").unwrap();
+        assert!(history < ungrouped);
+        assert!(content.contains("| `git lg` | `log --graph --format='%h \\| %s'` | global |"));
+        assert!(content.contains("| `git co` | `checkout` | system |"));
+
+        fs::remove_file(&tmp).ok();
+    }
+
+    #[test]
+    fn markdown_code_handles_backticks() {
+        assert_eq!(markdown_code("echo `date`"), "`` echo `date` ``");
+        assert_eq!(markdown_code("log"), "`log`");
+    }
+
+    #[test]
+    fn export_shell_shortcuts_with_prefix_and_groups() {
+        let dir = temp_dir("shell_export");
+        let groups = sample_groups();
+
+        let zsh = dir.join("gam-aliases.zsh");
+        FileService::export_aliases(
+            &sample_aliases(),
+            zsh.to_str().unwrap(),
+            Some(&groups),
+            None,
+            Some("gam_"),
+        )
+        .unwrap();
+        let content = fs::read_to_string(&zsh).unwrap();
+        assert!(content.contains("#   source <this file>\n"));
+        assert!(content.contains("\n# History\nalias gam_lg='git lg'\n"));
+        assert!(content.contains("\n# Ungrouped\n"));
+        assert_eq!(content.matches("alias gam_co='git co'\n").count(), 1);
+
+        let fish = dir.join("gam-aliases.fish");
+        FileService::export_aliases(&sample_aliases(), fish.to_str().unwrap(), None, None, None)
+            .unwrap();
+        let content = fs::read_to_string(&fish).unwrap();
+        assert!(content.contains("if status is-interactive\n    abbr --add gco 'git co'\n"));
+        assert!(content.ends_with("    abbr --add gsync 'git sync'\nend\n"));
+
+        let err = FileService::export_aliases(
+            &sample_aliases(),
+            dir.join("a.bash").to_str().unwrap(),
+            None,
+            None,
+            Some("g;"),
+        );
+        assert!(err.is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn fish_quote_escapes_backslashes_and_quotes() {
+        assert_eq!(fish_quote("git it's"), r"'git it\'s'");
+        assert_eq!(fish_quote(r"a\b"), r"'a\\b'");
+    }
+
+    fn temp_dir(label: &str) -> std::path::PathBuf {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "gam_test_import_{}_{}_{}",
+            label,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn import_gitconfig_follows_relative_includes() {
+        let dir = temp_dir("gitconfig");
+        fs::create_dir_all(dir.join("git")).unwrap();
+        fs::write(
+            dir.join("main.gitconfig"),
+            "[user]\n\tname = Me\n[alias]\n\tco = checkout\n\tst = status\n[include]\n\tpath = git/team.inc\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("git").join("team.inc"),
+            "[alias]\n\tco = switch\n\tlg = \"log --format='%h %s'\"\n",
+        )
+        .unwrap();
+
+        let aliases =
+            FileService::import_aliases(dir.join("main.gitconfig").to_str().unwrap()).unwrap();
+        let names: Vec<&str> = aliases.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["st", "co", "lg"]);
+        assert_eq!(aliases[1].command, "switch");
+        assert_eq!(aliases[2].command, "log --format='%h %s'");
+        assert!(aliases.iter().all(|a| a.scope == Scope::Global));
+        assert_eq!(aliases[1].include_chain.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn import_dotfiles_directory() {
+        let dir = temp_dir("dotfiles");
+        fs::write(dir.join(".gitconfig"), "[alias]\n\tco = checkout\n").unwrap();
+        fs::write(
+            dir.join(".gitconfig.local"),
+            "[alias]\n\tco = switch\n\tst = status\n",
+        )
+        .unwrap();
+
+        let aliases = FileService::import_aliases(dir.to_str().unwrap()).unwrap();
+        assert_eq!(aliases.len(), 2);
+        assert!(
+            aliases
+                .iter()
+                .any(|a| a.name == "co" && a.command == "switch")
+        );
+
+        let empty = temp_dir("dotfiles_empty");
+        assert!(FileService::import_aliases(empty.to_str().unwrap()).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(&empty);
+    }
+
+    #[test]
+    fn import_reports_conflicts_and_applies_choices() {
+        let repo = temp_dir("conflicts");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(
+            repo.join(".git").join("config"),
+            "[alias]\n\tgamtestco = checkout\n\tgamtestst = status\n\tgamtestco-2 = taken\n",
+        )
+        .unwrap();
+        let local = |name: &str, command: &str| GitAlias {
+            name: name.to_string(),
+            command: command.to_string(),
+            scope: Scope::Local,
+            local_path: Some(repo.to_string_lossy().to_string()),
+            score: None,
+            origin: None,
+            include_chain: Vec::new(),
+        };
+        let incoming = vec![
+            local("gamtestco", "switch"),
+            local("gamtestst", "status"),
+            local("gamtestlg", "log"),
+        ];
+
+        let mut git = GitService::scratch();
+        let preview = FileService::preview_import(&mut git, incoming.clone()).unwrap();
+        assert_eq!(preview.aliases.len(), 1);
+        assert_eq!(preview.unchanged.len(), 1);
+        assert_eq!(preview.conflicts.len(), 1);
+        assert_eq!(preview.conflicts[0].existing.command, "checkout");
+        assert_eq!(preview.conflicts[0].suggested_name, "gamtestco-3");
+
+        let choices = vec![ImportChoice {
+            name: "gamtestco".to_string(),
+            scope: Scope::Local,
+            resolution: ConflictResolution::Rename,
+            new_name: None,
+        }];
+        let summary = FileService::apply_import(&mut git, incoming, &choices).unwrap();
+        assert_eq!(summary.added, vec!["gamtestlg"]);
+        assert_eq!(summary.renamed, vec!["gamtestco-3"]);
+        assert_eq!(summary.skipped, vec!["gamtestst"]);
+
+        let config = fs::read_to_string(repo.join(".git").join("config")).unwrap();
+        assert!(config.contains("gamtestco = checkout"));
+        assert!(config.contains("gamtestco-3 = switch"));
+        assert!(config.contains("gamtestlg = log"));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn import_keeps_the_last_of_duplicated_names() {
+        let repo = temp_dir("duplicates");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("config"), "").unwrap();
+        let path = repo.to_string_lossy();
+        // A shadowed alias is exported once per definition
+        let export = serde_json::json!({
+            "version": "1.0.0",
+            "exportedAt": "2026-01-01T00:00:00Z",
+            "aliases": [
+                {"name": "gamtestdup", "command": "log", "scope": "local", "local_path": path},
+                {"name": "GamTestDup", "command": "log --oneline", "scope": "local", "local_path": path},
+            ],
+        });
+        let file = repo.join("export.json");
+        fs::write(&file, export.to_string()).unwrap();
+
+        let incoming = FileService::import_aliases(file.to_str().unwrap()).unwrap();
+        assert_eq!(incoming.len(), 2);
+        let mut git = GitService::scratch();
+        let summary = FileService::apply_import(&mut git, incoming, &[]).unwrap();
+        assert_eq!(summary.added, vec!["GamTestDup"]);
+
+        let config = fs::read_to_string(repo.join(".git").join("config")).unwrap();
+        assert!(config.contains("GamTestDup = log --oneline"));
+        assert!(!config.contains("= log\n"));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn git_command_for_shell_expansions() {
+        assert_eq!(git_command_for("git checkout").as_deref(), Some("checkout"));
+        assert_eq!(
+            git_command_for("git log --format='%h %s'").as_deref(),
+            Some("log --format='%h %s'")
+        );
+        assert_eq!(
+            git_command_for("git fetch && git rebase").as_deref(),
+            Some("!git fetch && git rebase")
+        );
+        assert_eq!(
+            git_command_for("git push -u origin $(git branch --show-current)").as_deref(),
+            Some("!git push -u origin $(git branch --show-current)")
+        );
+        assert_eq!(
+            git_command_for("git push origin $(git_current_branch)"),
+            None
+        );
+        assert_eq!(git_command_for("git"), None);
+        assert_eq!(git_command_for("gitk --all"), None);
+        assert_eq!(git_command_for("ls -la"), None);
+    }
+
+    #[test]
+    fn import_shell_aliases_proposes_git_aliases() {
+        let dir = temp_dir("shell");
+        let rc = dir.join("aliases.zsh");
+        fs::write(
+            &rc,
+            "alias gco='git checkout'\nalias glog='git log --oneline'\nalias ll='ls -la'\nalias co='git commit'\nalias gco='git checkout -b'\n",
+        )
+        .unwrap();
+
+        let aliases = FileService::import_shell_aliases(rc.to_str()).unwrap();
+        let pairs: Vec<(&str, &str)> = aliases
+            .iter()
+            .map(|a| (a.name.as_str(), a.command.as_str()))
+            .collect();
+        // `log` is a built-in and `co` is claimed by the earlier `co` alias
+        assert_eq!(
+            pairs,
+            vec![
+                ("glog", "log --oneline"),
+                ("co", "commit"),
+                ("gco", "checkout -b")
+            ]
+        );
+        assert_eq!(aliases[0].origin.as_deref(), rc.to_str());
+
+        let fish = dir.join("git.fish");
+        fs::write(&fish, "abbr -a gst git status -sb\n").unwrap();
+        let aliases = FileService::import_shell_aliases(fish.to_str()).unwrap();
+        assert_eq!(aliases[0].name, "st");
+        assert_eq!(aliases[0].command, "status -sb");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
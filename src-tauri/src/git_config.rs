@@ -0,0 +1,1394 @@
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::GamError;
+
+/// A single `name = value` entry inside a config section.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEntry {
+    /// Section name, lowercased (`alias` for `[Alias]`).
+    pub section: String,
+    /// Subsection name, case preserved (`origin` for `[remote "origin"]`).
+    pub subsection: Option<String>,
+    /// Variable name, lowercased the way git matches it.
+    pub name: String,
+    /// Decoded value; `None` for a bare `name` (implicit boolean true).
+    pub value: Option<String>,
+    /// 1-based line number the entry starts on.
+    pub line: usize,
+    /// Byte range of the entry in the source, including its line terminator.
+    span: Range<usize>,
+}
+
+impl ConfigEntry {
+    fn matches(&self, section: &str, subsection: Option<&str>, name: &str) -> bool {
+        self.section.eq_ignore_ascii_case(section)
+            && self.subsection.as_deref() == subsection
+            && self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+/// Location of a section header and the end of its body in the source.
+#[derive(Debug, Clone)]
+struct SectionSpan {
+    section: String,
+    subsection: Option<String>,
+    /// Offset right after the closing `]`.
+    header_end: usize,
+    /// Offset after the last entry of this section (or `header_end`).
+    body_end: usize,
+}
+
+/// A parsed gitconfig file that keeps the original text around,
+/// so edits preserve comments, blank lines and indentation.
+///
+/// Tokenization follows git's `config.c`: case-insensitive section and
+/// variable names, quoted subsections, `"…"` quoting inside values,
+/// `\n` / `\t` / `\b` / `\\` / `\"` escapes, `;` and `#` comments and
+/// backslash-newline continuations.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    source: String,
+    entries: Vec<ConfigEntry>,
+    sections: Vec<SectionSpan>,
+    /// Set when the file wasn't valid UTF-8 and `source` replaced some bytes.
+    lossy: bool,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> Result<Self, GamError> {
+        let mut p = Parser::new(text);
+        if text.starts_with('\u{feff}') {
+            p.pos = '\u{feff}'.len_utf8();
+        }
+
+        let mut entries = Vec::new();
+        let mut sections: Vec<SectionSpan> = Vec::new();
+        let mut comment = false;
+
+        loop {
+            let start = p.pos;
+            let line = p.line;
+            let Some(c) = p.next_char() else { break };
+
+            match c {
+                b'\n' => comment = false,
+                _ if comment => {}
+                c if is_space(c) => {}
+                b'#' | b';' => comment = true,
+                b'[' => {
+                    let (section, subsection) = p.section_header().ok_or_else(|| bad_line(line))?;
+                    sections.push(SectionSpan {
+                        section,
+                        subsection,
+                        header_end: p.pos,
+                        body_end: p.pos,
+                    });
+                }
+                c if c.is_ascii_alphabetic() => {
+                    let current = sections.last_mut().ok_or_else(|| bad_line(line))?;
+                    let (name, value) = p.key_value(c).ok_or_else(|| bad_line(line))?;
+
+                    // Own the leading indentation when the key starts its line,
+                    // so removing the entry doesn't leave stray whitespace behind.
+                    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+                    let span_start = if text.as_bytes()[line_start..start]
+                        .iter()
+                        .all(|&b| is_space(b))
+                        && line_start >= current.header_end
+                    {
+                        line_start
+                    } else {
+                        start
+                    };
+
+                    current.body_end = p.pos;
+                    entries.push(ConfigEntry {
+                        section: current.section.clone(),
+                        subsection: current.subsection.clone(),
+                        name,
+                        value,
+                        line,
+                        span: span_start..p.pos,
+                    });
+                }
+                _ => return Err(bad_line(line)),
+            }
+        }
+
+        Ok(Self {
+            source: text.to_string(),
+            entries,
+            sections,
+            lossy: false,
+        })
+    }
+
+    /// Reads and parses `path`. A missing file yields an empty config. A file
+    /// that isn't valid UTF-8 can be read but not saved.
+    pub fn load(path: &Path) -> Result<Self, GamError> {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let text = String::from_utf8_lossy(&bytes);
+                let mut config = Self::parse(&text).map_err(|e| e.in_file(path))?;
+                config.lossy = std::str::from_utf8(&bytes).is_err();
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(GamError::file("read", path, e)),
+        }
+    }
+
+    /// Writes the config through `<path>.lock` and renames it into place,
+    /// honoring the same lock file git uses.
+    pub fn save(&self, path: &Path) -> Result<(), GamError> {
+        if self.lossy {
+            return Err(not_utf8(path));
+        }
+        let mut lock = ConfigLock::acquire(path)?;
+        lock.write(self.source.as_bytes())?;
+        lock.commit()
+    }
+
+    /// Last value for a key, matching git's "last one wins" semantics.
+    pub fn get(
+        &self,
+        section: &str,
+        subsection: Option<&str>,
+        name: &str,
+    ) -> Option<&ConfigEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.matches(section, subsection, name))
+    }
+
+    /// Sets `section[.subsection].name = value`, replacing every existing
+    /// occurrence like `git config --replace-all`. New keys are appended to
+    /// the last matching section, or to a new section at the end of the file.
+    pub fn set(
+        &mut self,
+        section: &str,
+        subsection: Option<&str>,
+        name: &str,
+        value: &str,
+    ) -> Result<(), GamError> {
+        let line = format_entry(name, value);
+        let matching: Vec<Range<usize>> = self
+            .entries
+            .iter()
+            .filter(|e| e.matches(section, subsection, name))
+            .map(|e| e.span.clone())
+            .collect();
+
+        let mut out = self.source.clone();
+        if let Some((last, earlier)) = matching.split_last() {
+            let replacement = if self.source[..last.end].ends_with('\n') {
+                line
+            } else {
+                line.trim_end_matches('\n').to_string()
+            };
+            out.replace_range(last.clone(), &replacement);
+            for span in earlier.iter().rev() {
+                out.replace_range(span.clone(), "");
+            }
+        } else if let Some(sec) = self.sections.iter().rev().find(|s| {
+            s.section.eq_ignore_ascii_case(section) && s.subsection.as_deref() == subsection
+        }) {
+            let mut at = sec.body_end;
+            let mut prefix = "";
+            if !self.source[..at].ends_with('\n') {
+                // Empty section: skip the rest of the header line if it's only
+                // whitespace or a comment, otherwise start a new line.
+                let rest = &self.source[at..];
+                let eol = rest.find('\n');
+                let tail = rest[..eol.unwrap_or(rest.len())].trim();
+                match eol {
+                    Some(i) if tail.is_empty() || tail.starts_with(['#', ';']) => at += i + 1,
+                    _ => prefix = "\n",
+                }
+            }
+            out.insert_str(at, &format!("{}{}", prefix, line));
+        } else {
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&format_section_header(section, subsection));
+            out.push_str(&line);
+        }
+
+        self.reparse(&out)
+    }
+
+    /// Removes every occurrence of a key. Returns whether anything was removed.
+    pub fn unset(
+        &mut self,
+        section: &str,
+        subsection: Option<&str>,
+        name: &str,
+    ) -> Result<bool, GamError> {
+        let matching: Vec<Range<usize>> = self
+            .entries
+            .iter()
+            .filter(|e| e.matches(section, subsection, name))
+            .map(|e| e.span.clone())
+            .collect();
+        if matching.is_empty() {
+            return Ok(false);
+        }
+
+        let mut out = self.source.clone();
+        for span in matching.iter().rev() {
+            // An entry sharing its line with the section header keeps the newline.
+            let keep_newline = span.start > 0
+                && !out[..span.start].ends_with('\n')
+                && out[..span.end].ends_with('\n');
+            let end = if keep_newline { span.end - 1 } else { span.end };
+            out.replace_range(span.start..end, "");
+        }
+
+        self.reparse(&out)?;
+        Ok(true)
+    }
+
+    /// Replaces the source with an edited one, still refusing to save a file
+    /// that wasn't UTF-8.
+    fn reparse(&mut self, source: &str) -> Result<(), GamError> {
+        let lossy = self.lossy;
+        *self = Self::parse(source)?;
+        self.lossy = lossy;
+        Ok(())
+    }
+}
+
+/// A config file locked the way git locks it: `<path>.lock` is created
+/// exclusively, receives the new contents and is renamed over `path` on
+/// `commit`. Dropping an uncommitted lock removes the lock file.
+pub struct ConfigLock {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<fs::File>,
+}
+
+impl ConfigLock {
+    pub fn acquire(path: &Path) -> Result<Self, GamError> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => GamError::FileLocked {
+                    path: path.to_path_buf(),
+                },
+                _ => GamError::file("lock", path, e),
+            })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            lock_path,
+            file: Some(file),
+        })
+    }
+
+    /// Writes the new contents into the lock file.
+    pub fn write(&mut self, contents: &[u8]) -> Result<(), GamError> {
+        let file = self
+            .file
+            .as_mut()
+            .ok_or_else(|| GamError::file("write", &self.path, "not locked"))?;
+        file.write_all(contents)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| GamError::file("write", &self.path, e))
+    }
+
+    /// Renames the lock file into place.
+    pub fn commit(mut self) -> Result<(), GamError> {
+        self.file.take();
+        fs::rename(&self.lock_path, &self.path).map_err(|e| {
+            let _ = fs::remove_file(&self.lock_path);
+            GamError::file("write", &self.path, e)
+        })
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
+
+/// The contents of a config file before and after a write; `None` when the
+/// file doesn't exist.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl FileChange {
+    /// The change that puts `before` back.
+    pub fn reversed(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            before: self.after.clone(),
+            after: self.before.clone(),
+        }
+    }
+}
+
+/// Applies `changes` as one unit. Every file is locked and checked to still
+/// hold `before`, then `backup` runs, then the files are replaced; if
+/// replacing one fails, the files already replaced are restored.
+pub fn write_files(
+    changes: &[FileChange],
+    backup: impl FnOnce(&[FileChange]) -> Result<(), GamError>,
+) -> Result<(), GamError> {
+    let mut locks = Vec::new();
+    for change in changes {
+        let mut lock = ConfigLock::acquire(&change.path)?;
+        // Rewriting a file that isn't UTF-8 would replace its other bytes
+        let current = read_if_exists(&change.path)?
+            .map(|bytes| String::from_utf8(bytes).map_err(|_| not_utf8(&change.path)))
+            .transpose()?;
+        if current.as_deref() != change.before.as_deref() {
+            return Err(GamError::FileChanged {
+                path: change.path.clone(),
+            });
+        }
+        if let Some(after) = &change.after {
+            lock.write(after.as_bytes())?;
+        }
+        locks.push(lock);
+    }
+    backup(changes)?;
+
+    let mut replaced: Vec<&FileChange> = Vec::new();
+    for (lock, change) in locks.into_iter().zip(changes) {
+        let result = match change.after {
+            Some(_) => lock.commit(),
+            // The lock stays held until the file is gone.
+            None => fs::remove_file(&change.path)
+                .map_err(|e| GamError::file("remove", &change.path, e)),
+        };
+        if let Err(e) = result {
+            let failed: Vec<String> = replaced
+                .iter()
+                .filter_map(|c| restore(&c.path, c.before.as_deref()).err())
+                .map(|e| e.to_string())
+                .collect();
+            let message = if failed.is_empty() {
+                format!("{}; all changes were rolled back", e)
+            } else {
+                format!("{}; rolling back failed: {}", e, failed.join("; "))
+            };
+            return Err(GamError::File {
+                path: change.path.clone(),
+                message,
+            });
+        }
+        replaced.push(change);
+    }
+    Ok(())
+}
+
+fn not_utf8(path: &Path) -> GamError {
+    GamError::File {
+        path: path.to_path_buf(),
+        message: format!(
+            "{} is not valid UTF-8; GAM doesn't rewrite it so no bytes are lost",
+            path.display()
+        ),
+    }
+}
+
+/// Reads `path`, or `None` if it doesn't exist.
+pub fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>, GamError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(GamError::file("read", path, e)),
+    }
+}
+
+/// Puts back the contents `write_files` found in `path`.
+fn restore(path: &Path, before: Option<&str>) -> Result<(), GamError> {
+    match before {
+        Some(contents) => {
+            let mut lock = ConfigLock::acquire(path)?;
+            lock.write(contents.as_bytes())?;
+            lock.commit()
+        }
+        None => fs::remove_file(path).map_err(|e| GamError::file("remove", path, e)),
+    }
+}
+
+impl fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The system-wide config file, or `None` when `GIT_CONFIG_NOSYSTEM` is set.
+///
+/// Honors `$GIT_CONFIG_SYSTEM`, otherwise uses the usual install locations.
+pub fn system_config_path() -> Option<PathBuf> {
+    if std::env::var_os("GIT_CONFIG_NOSYSTEM").is_some_and(|v| is_truthy(&v.to_string_lossy())) {
+        return None;
+    }
+    if let Some(path) = std::env::var_os("GIT_CONFIG_SYSTEM") {
+        return Some(PathBuf::from(path));
+    }
+    if cfg!(target_os = "windows") {
+        Some(PathBuf::from(r"C:\Program Files\Git\etc\gitconfig"))
+    } else {
+        Some(PathBuf::from("/etc/gitconfig"))
+    }
+}
+
+/// Candidate files for the global scope, lowest precedence first.
+///
+/// Mirrors git: `$GIT_CONFIG_GLOBAL` replaces both files when set,
+/// otherwise `$XDG_CONFIG_HOME/git/config` is read before `~/.gitconfig`.
+pub fn global_config_paths() -> Vec<PathBuf> {
+    if let Some(path) = std::env::var_os("GIT_CONFIG_GLOBAL") {
+        return vec![PathBuf::from(path)];
+    }
+
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"));
+
+    vec![xdg.join("git").join("config"), home.join(".gitconfig")]
+}
+
+/// The file `git config --global` writes to: `~/.gitconfig`, unless only
+/// the XDG file exists.
+pub fn global_write_path() -> PathBuf {
+    let paths = global_config_paths();
+    match paths.as_slice() {
+        [xdg, home] if !home.exists() && xdg.exists() => xdg.clone(),
+        _ => paths.last().cloned().unwrap_or_default(),
+    }
+}
+
+/// Locates the git directory for a working tree (or any directory inside it),
+/// following `.git` files used by worktrees and submodules.
+pub fn discover_git_dir(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let target = content.trim().strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        }
+        // Bare repository
+        if dir.join("HEAD").is_file()
+            && dir.join("objects").is_dir()
+            && dir.join("config").is_file()
+        {
+            return Some(dir.to_path_buf());
+        }
+    }
+    None
+}
+
+/// Resolves the shared git directory; linked worktrees point at it via `commondir`.
+pub fn common_git_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(content) => git_dir.join(content.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Path of the repository-level config (`--local`) for a working tree.
+pub fn local_config_path(repo: &Path) -> Option<PathBuf> {
+    discover_git_dir(repo).map(|dir| common_git_dir(&dir).join("config"))
+}
+
+/// Path of the per-worktree config (`--worktree`), available only when the
+/// repository enables `extensions.worktreeConfig`.
+pub fn worktree_config_path(repo: &Path) -> Option<PathBuf> {
+    let git_dir = discover_git_dir(repo)?;
+    let common = ConfigFile::load(&common_git_dir(&git_dir).join("config")).ok()?;
+    let enabled = common
+        .get("extensions", None, "worktreeconfig")
+        .is_some_and(|e| e.value.as_deref().is_none_or(is_truthy));
+    enabled.then(|| git_dir.join("config.worktree"))
+}
+
+/// Git's boolean parsing; a bare key (no value) is handled by the caller.
+pub fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "true" | "yes" | "on" | "1"
+    )
+}
+
+/// Git refuses to follow more nested includes than this.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// A config entry together with the file it was read from.
+#[derive(Debug, Clone)]
+pub struct ResolvedEntry {
+    pub entry: ConfigEntry,
+    /// File that contains the entry.
+    pub origin: PathBuf,
+    /// Files that included `origin`, outermost first. Empty for top-level files.
+    pub include_chain: Vec<PathBuf>,
+}
+
+/// Repository state used to evaluate `[includeIf]` conditions.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeContext {
+    /// Git directory of the repository being inspected, if any.
+    pub git_dir: Option<PathBuf>,
+    /// Short name of the checked-out branch, if `HEAD` is not detached.
+    pub branch: Option<String>,
+    /// Condition prefixes (`onbranch:`) the installed git doesn't know; they
+    /// never match.
+    pub ignored: Vec<&'static str>,
+}
+
+impl IncludeContext {
+    /// Builds the context git would see when run inside `repo`.
+    /// Outside a repository no `gitdir:` or `onbranch:` condition matches.
+    pub fn for_repo(repo: Option<&Path>) -> Self {
+        let Some(git_dir) = repo.and_then(discover_git_dir) else {
+            return Self::default();
+        };
+        let branch = fs::read_to_string(git_dir.join("HEAD"))
+            .ok()
+            .and_then(|head| {
+                head.trim()
+                    .strip_prefix("ref: refs/heads/")
+                    .map(|b| b.to_string())
+            });
+        Self {
+            git_dir: Some(git_dir),
+            branch,
+            ignored: Vec::new(),
+        }
+    }
+
+    fn matches(&self, condition: &str, including_file: &Path) -> bool {
+        if self.ignored.iter().any(|prefix| condition.starts_with(prefix)) {
+            false
+        } else if let Some(pattern) = condition.strip_prefix("gitdir:") {
+            self.gitdir_matches(pattern, including_file, false)
+        } else if let Some(pattern) = condition.strip_prefix("gitdir/i:") {
+            self.gitdir_matches(pattern, including_file, true)
+        } else if let Some(pattern) = condition.strip_prefix("onbranch:") {
+            let Some(branch) = &self.branch else {
+                return false;
+            };
+            let mut pattern = pattern.to_string();
+            if pattern.ends_with('/') {
+                pattern.push_str("**");
+            }
+            wildmatch(pattern.as_bytes(), branch.as_bytes(), false)
+        } else {
+            // `hasconfig:` and unknown conditions never match
+            false
+        }
+    }
+
+    fn gitdir_matches(&self, pattern: &str, including_file: &Path, icase: bool) -> bool {
+        let Some(git_dir) = &self.git_dir else {
+            return false;
+        };
+
+        let mut pattern = if let Some(rest) = pattern.strip_prefix("~/") {
+            let home = dirs::home_dir().unwrap_or_default();
+            format!("{}/{}", home.display(), rest)
+        } else if let Some(rest) = pattern.strip_prefix("./") {
+            let dir = including_file.parent().unwrap_or(Path::new("."));
+            format!("{}/{}", dir.display(), rest)
+        } else if Path::new(pattern).is_absolute() {
+            pattern.to_string()
+        } else {
+            format!("**/{}", pattern)
+        };
+        if pattern.ends_with('/') {
+            pattern.push_str("**");
+        }
+        let pattern = pattern.replace('\\', "/");
+
+        // Git tries both the path as given and with symlinks resolved
+        let mut candidates = vec![git_dir.clone()];
+        if let Ok(real) = git_dir.canonicalize() {
+            candidates.push(real);
+        }
+        candidates.iter().any(|dir| {
+            let text = dir.to_string_lossy().replace('\\', "/");
+            wildmatch(pattern.as_bytes(), text.as_bytes(), icase)
+        })
+    }
+}
+
+/// Reads `path` and every file it pulls in through `[include]` and matching
+/// `[includeIf]` sections, returning entries in the order git sees them.
+/// Missing files are skipped, like git does.
+pub fn read_with_includes(
+    path: &Path,
+    ctx: &IncludeContext,
+) -> Result<Vec<ResolvedEntry>, GamError> {
+    let mut out = Vec::new();
+    collect_entries(path, ctx, &mut Vec::new(), &mut out)?;
+    Ok(out)
+}
+
+fn collect_entries(
+    path: &Path,
+    ctx: &IncludeContext,
+    chain: &mut Vec<PathBuf>,
+    out: &mut Vec<ResolvedEntry>,
+) -> Result<(), GamError> {
+    if chain.len() > MAX_INCLUDE_DEPTH {
+        return Err(GamError::File {
+            path: path.to_path_buf(),
+            message: format!(
+                "exceeded maximum include depth ({}) while including {}",
+                MAX_INCLUDE_DEPTH,
+                path.display()
+            ),
+        });
+    }
+
+    let config = ConfigFile::load(path)?;
+    for entry in &config.entries {
+        out.push(ResolvedEntry {
+            entry: entry.clone(),
+            origin: path.to_path_buf(),
+            include_chain: chain.clone(),
+        });
+
+        let included = match (entry.section.as_str(), &entry.subsection) {
+            ("include", None) => true,
+            ("includeif", Some(condition)) => ctx.matches(condition, path),
+            _ => false,
+        };
+        if let (true, "path", Some(target)) = (included, entry.name.as_str(), &entry.value) {
+            chain.push(path.to_path_buf());
+            let result = collect_entries(&resolve_include_path(target, path), ctx, chain, out);
+            chain.pop();
+            result?;
+        }
+    }
+    Ok(())
+}
+
+/// Expands `~/` and resolves relative include paths against the including file.
+fn resolve_include_path(target: &str, including_file: &Path) -> PathBuf {
+    if let Some(rest) = target.strip_prefix("~/") {
+        return dirs::home_dir().unwrap_or_default().join(rest);
+    }
+    let target = Path::new(target);
+    if target.is_absolute() {
+        target.to_path_buf()
+    } else {
+        including_file
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(target)
+    }
+}
+
+/// Glob matching with git's `WM_PATHNAME` semantics: `*` and `?` stop at
+/// `/`, `**/` spans directories, `[...]` is a character class.
+fn wildmatch(pattern: &[u8], text: &[u8], icase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if icase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            match rest.first() {
+                None => true,
+                Some(b'/') => {
+                    // `**/` matches zero or more leading directories
+                    let rest = &rest[1..];
+                    wildmatch(rest, text, icase)
+                        || text
+                            .iter()
+                            .enumerate()
+                            .filter(|&(_, &c)| c == b'/')
+                            .any(|(i, _)| wildmatch(rest, &text[i + 1..], icase))
+                }
+                Some(_) => (0..=text.len()).any(|i| wildmatch(rest, &text[i..], icase)),
+            }
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if wildmatch(rest, &text[i..], icase) {
+                    return true;
+                }
+                if text.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => match text.first() {
+            Some(&c) if c != b'/' => wildmatch(&pattern[1..], &text[1..], icase),
+            _ => false,
+        },
+        Some(b'[') => {
+            let Some(&c) = text.first() else { return false };
+            let mut i = 1;
+            let negate = matches!(pattern.get(i), Some(b'!' | b'^'));
+            if negate {
+                i += 1;
+            }
+            let mut matched = false;
+            let mut first = true;
+            while let Some(&p) = pattern.get(i) {
+                if p == b']' && !first {
+                    break;
+                }
+                first = false;
+                if pattern.get(i + 1) == Some(&b'-')
+                    && pattern.get(i + 2).is_some_and(|&e| e != b']')
+                {
+                    let (lo, hi) = (p, pattern[i + 2]);
+                    let in_range = |x: u8| lo <= x && x <= hi;
+                    matched |= in_range(c)
+                        || (icase
+                            && (in_range(c.to_ascii_lowercase())
+                                || in_range(c.to_ascii_uppercase())));
+                    i += 3;
+                } else {
+                    matched |= eq(p, c);
+                    i += 1;
+                }
+            }
+            if i >= pattern.len() {
+                // Unterminated class: treat `[` literally
+                return c == b'[' && wildmatch(&pattern[1..], &text[1..], icase);
+            }
+            c != b'/' && matched != negate && wildmatch(&pattern[i + 1..], &text[1..], icase)
+        }
+        Some(b'\\') if pattern.len() > 1 => {
+            matches!(text.first(), Some(&c) if eq(pattern[1], c))
+                && wildmatch(&pattern[2..], &text[1..], icase)
+        }
+        Some(&p) => {
+            matches!(text.first(), Some(&c) if eq(p, c))
+                && wildmatch(&pattern[1..], &text[1..], icase)
+        }
+    }
+}
+
+/// Renders `\tname = value\n` with git's quoting and escaping rules.
+pub fn format_entry(name: &str, value: &str) -> String {
+    let needs_quotes =
+        value.starts_with(' ') || value.ends_with(' ') || value.contains([';', '#', '\r']);
+    let quote = if needs_quotes { "\"" } else { "" };
+
+    let mut out = format!("\t{} = {}", name, quote);
+    for c in value.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push_str(quote);
+    out.push('\n');
+    out
+}
+
+fn format_section_header(section: &str, subsection: Option<&str>) -> String {
+    match subsection {
+        Some(sub) => {
+            let escaped = sub.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("[{} \"{}\"]\n", section, escaped)
+        }
+        None => format!("[{}]\n", section),
+    }
+}
+
+fn bad_line(line: usize) -> GamError {
+    GamError::ConfigSyntax { path: None, line }
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c')
+}
+
+fn is_key_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'-'
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            src: text.as_bytes(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    /// Next byte with `\r\n` folded into `\n`; `None` at end of input.
+    fn next_char(&mut self) -> Option<u8> {
+        let mut c = *self.src.get(self.pos)?;
+        self.pos += 1;
+        if c == b'\r' && self.src.get(self.pos) == Some(&b'\n') {
+            self.pos += 1;
+            c = b'\n';
+        }
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// Parses the rest of a `[section]`, `[section "sub"]` or legacy
+    /// `[section.sub]` header after the opening bracket.
+    fn section_header(&mut self) -> Option<(String, Option<String>)> {
+        let mut name = String::new();
+        loop {
+            let c = self.next_char()?;
+            if c == b']' {
+                break;
+            }
+            if is_space(c) {
+                if c == b'\n' || name.is_empty() {
+                    return None;
+                }
+                let sub = self.quoted_subsection()?;
+                return Some((name, Some(sub)));
+            }
+            if !is_key_char(c) && c != b'.' {
+                return None;
+            }
+            name.push(c.to_ascii_lowercase() as char);
+        }
+
+        match name.split_once('.') {
+            _ if name.is_empty() => None,
+            Some(("", _)) => None,
+            Some((section, sub)) => Some((section.to_string(), Some(sub.to_string()))),
+            None => Some((name, None)),
+        }
+    }
+
+    fn quoted_subsection(&mut self) -> Option<String> {
+        let mut c = self.next_char()?;
+        while is_space(c) {
+            if c == b'\n' {
+                return None;
+            }
+            c = self.next_char()?;
+        }
+        if c != b'"' {
+            return None;
+        }
+
+        let mut sub = Vec::new();
+        loop {
+            let mut c = self.next_char()?;
+            match c {
+                b'\n' => return None,
+                b'"' => break,
+                b'\\' => {
+                    c = self.next_char()?;
+                    if c == b'\n' {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+            sub.push(c);
+        }
+
+        if self.next_char()? != b']' {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&sub).into_owned())
+    }
+
+    /// Parses `name [= value]` starting from its first character.
+    fn key_value(&mut self, first: u8) -> Option<(String, Option<String>)> {
+        let mut name = String::from(first.to_ascii_lowercase() as char);
+        let mut c = self.next_char();
+        while let Some(k) = c.filter(|&k| is_key_char(k)) {
+            name.push(k.to_ascii_lowercase() as char);
+            c = self.next_char();
+        }
+        while matches!(c, Some(b' ' | b'\t')) {
+            c = self.next_char();
+        }
+
+        match c {
+            None | Some(b'\n') => Some((name, None)),
+            Some(b'=') => Some((name, Some(self.value()?))),
+            Some(_) => None,
+        }
+    }
+
+    fn value(&mut self) -> Option<String> {
+        let mut value: Vec<u8> = Vec::new();
+        let mut quote = false;
+        let mut comment = false;
+        // Length to truncate to if only unquoted whitespace follows.
+        let mut trim_len: Option<usize> = None;
+
+        loop {
+            let c = self.next_char().unwrap_or(b'\n');
+            if c == b'\n' {
+                if quote {
+                    return None;
+                }
+                if let Some(len) = trim_len {
+                    value.truncate(len);
+                }
+                return Some(String::from_utf8_lossy(&value).into_owned());
+            }
+            if comment {
+                continue;
+            }
+            if is_space(c) && !quote {
+                // Like git, each unquoted whitespace character becomes a space
+                trim_len.get_or_insert(value.len());
+                if !value.is_empty() {
+                    value.push(b' ');
+                }
+                continue;
+            }
+            if !quote && (c == b';' || c == b'#') {
+                comment = true;
+                continue;
+            }
+            trim_len = None;
+            match c {
+                b'\\' => {
+                    let escaped = match self.next_char().unwrap_or(b'\n') {
+                        b'\n' => continue,
+                        b't' => b'\t',
+                        b'b' => b'\x08',
+                        b'n' => b'\n',
+                        e @ (b'\\' | b'"') => e,
+                        _ => return None,
+                    };
+                    value.push(escaped);
+                }
+                b'"' => quote = !quote,
+                _ => value.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(cfg: &ConfigFile) -> Vec<(String, Option<String>)> {
+        cfg.entries
+            .iter()
+            .filter(|e| e.section == "alias")
+            .map(|e| (e.name.clone(), e.value.clone()))
+            .collect()
+    }
+
+
+    #[test]
+    fn parses_simple_section() {
+        let cfg = ConfigFile::parse("[alias]\n\tco = checkout\n\tst = status -sb\n").unwrap();
+        assert_eq!(
+            aliases(&cfg),
+            vec![
+                ("co".to_string(), Some("checkout".to_string())),
+                ("st".to_string(), Some("status -sb".to_string())),
+            ]
+        );
+        assert_eq!(cfg.get("alias", None, "st").unwrap().line, 3);
+    }
+
+    #[test]
+    fn section_and_key_names_are_case_insensitive() {
+        let cfg = ConfigFile::parse("[Alias]\n\tCO = checkout\n").unwrap();
+        let entry = cfg.get("alias", None, "co").unwrap();
+        assert_eq!(
+            (entry.section.as_str(), entry.name.as_str()),
+            ("alias", "co")
+        );
+    }
+
+    #[test]
+    fn parses_subsections() {
+        let cfg = ConfigFile::parse(
+            "[remote \"Origin\"]\n\turl = x\n[branch.Main]\n\tremote = origin\n[a \"q\\\"uote\"]\n\tk = v\n",
+        )
+        .unwrap();
+        assert!(cfg.get("remote", Some("Origin"), "url").is_some());
+        assert!(cfg.get("branch", Some("main"), "remote").is_some());
+        assert!(cfg.get("a", Some("q\"uote"), "k").is_some());
+    }
+
+    #[test]
+    fn handles_quotes_escapes_and_comments() {
+        let cfg = ConfigFile::parse(
+            "[alias]\n\
+             \ta = \"  padded  \"\n\
+             \tb = log --format=\"%h # %s\" ; trailing comment\n\
+             \tc = echo \\\"hi\\\"\\tthere\\n\n\
+             \td = status   # comment\n\
+             \te = log\t--oneline \t -5\t\n",
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.get("alias", None, "a").unwrap().value.as_deref(),
+            Some("  padded  ")
+        );
+        assert_eq!(
+            cfg.get("alias", None, "b").unwrap().value.as_deref(),
+            Some("log --format=%h # %s")
+        );
+        assert_eq!(
+            cfg.get("alias", None, "c").unwrap().value.as_deref(),
+            Some("echo \"hi\"\tthere\n")
+        );
+        assert_eq!(
+            cfg.get("alias", None, "d").unwrap().value.as_deref(),
+            Some("status")
+        );
+        // Unquoted tabs become spaces, one per character, as in git
+        assert_eq!(
+            cfg.get("alias", None, "e").unwrap().value.as_deref(),
+            Some("log --oneline   -5")
+        );
+    }
+
+    #[test]
+    fn joins_line_continuations() {
+        let cfg = ConfigFile::parse(
+            "[alias]\n\tlg = log \\\n  --oneline \\\r\n  --graph\n\tst = status\n",
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.get("alias", None, "lg").unwrap().value.as_deref(),
+            Some("log   --oneline   --graph")
+        );
+        assert_eq!(cfg.get("alias", None, "st").unwrap().line, 5);
+    }
+
+    #[test]
+    fn bare_key_is_boolean() {
+        let cfg = ConfigFile::parse("[core]\n\tbare\n[alias] co = checkout").unwrap();
+        assert_eq!(cfg.get("core", None, "bare").unwrap().value, None);
+        assert_eq!(
+            cfg.get("alias", None, "co").unwrap().value.as_deref(),
+            Some("checkout")
+        );
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let cfg = ConfigFile::parse("[alias]\nco = checkout\n[alias]\nco = switch\n").unwrap();
+        assert_eq!(
+            cfg.get("alias", None, "co").unwrap().value.as_deref(),
+            Some("switch")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(ConfigFile::parse("co = checkout\n").is_err());
+        assert!(ConfigFile::parse("[alias\nco = checkout\n").is_err());
+        assert!(ConfigFile::parse("[alias]\nco = \"unterminated\n").is_err());
+        assert!(ConfigFile::parse("[alias]\nco = bad \\q escape\n").is_err());
+        let err = ConfigFile::parse("[alias]\n\n1co = x\n").unwrap_err();
+        assert!(matches!(err, GamError::ConfigSyntax { path: None, line: 3 }));
+        assert_eq!(err.to_string(), "bad config line 3");
+    }
+
+    #[test]
+    fn set_replaces_in_place_and_keeps_comments() {
+        let src = "# my config\n[alias]\n\tco = checkout ; old\n\t# keep me\n\tst = status\n";
+        let mut cfg = ConfigFile::parse(src).unwrap();
+        cfg.set("alias", None, "co", "switch").unwrap();
+        assert_eq!(
+            cfg.to_string(),
+            "# my config\n[alias]\n\tco = switch\n\t# keep me\n\tst = status\n"
+        );
+    }
+
+    #[test]
+    fn set_appends_to_existing_section() {
+        let mut cfg =
+            ConfigFile::parse("[alias]\n\tco = checkout\n\n[core]\n\teditor = vim\n").unwrap();
+        cfg.set("alias", None, "st", "status").unwrap();
+        assert_eq!(
+            cfg.to_string(),
+            "[alias]\n\tco = checkout\n\tst = status\n\n[core]\n\teditor = vim\n"
+        );
+    }
+
+    #[test]
+    fn set_creates_missing_section() {
+        let mut cfg = ConfigFile::parse("[core]\n\teditor = vim").unwrap();
+        cfg.set("alias", None, "co", "checkout").unwrap();
+        assert_eq!(
+            cfg.to_string(),
+            "[core]\n\teditor = vim\n[alias]\n\tco = checkout\n"
+        );
+    }
+
+    #[test]
+    fn set_quotes_and_escapes_like_git() {
+        let value = "!f() { echo \"$1\"; }; f";
+        let mut cfg = ConfigFile::default();
+        cfg.set("alias", None, "x", value).unwrap();
+        assert!(
+            cfg.to_string()
+                .contains("\tx = \"!f() { echo \\\"$1\\\"; }; f\"\n")
+        );
+        assert_eq!(
+            cfg.get("alias", None, "x").unwrap().value.as_deref(),
+            Some(value)
+        );
+
+        let multiline = " lead\n\ttab\\ ";
+        cfg.set("alias", None, "y", multiline).unwrap();
+        assert_eq!(
+            cfg.get("alias", None, "y").unwrap().value.as_deref(),
+            Some(multiline)
+        );
+    }
+
+    #[test]
+    fn unset_removes_entries_and_preserves_rest() {
+        let src = "[alias]\n\tco = checkout\n\t# note\n\tst = status\n\tco = switch\n";
+        let mut cfg = ConfigFile::parse(src).unwrap();
+        assert!(cfg.unset("alias", None, "CO").unwrap());
+        assert_eq!(cfg.to_string(), "[alias]\n\t# note\n\tst = status\n");
+        assert!(!cfg.unset("alias", None, "missing").unwrap());
+    }
+
+    #[test]
+    fn unset_inline_entry_keeps_header() {
+        let mut cfg = ConfigFile::parse("[alias] co = checkout\n[core]\n").unwrap();
+        cfg.unset("alias", None, "co").unwrap();
+        assert_eq!(cfg.to_string(), "[alias] \n[core]\n");
+    }
+
+    #[test]
+    fn save_and_load_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("gam_test_gitconfig_{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.join("config");
+
+        let mut cfg = ConfigFile::load(&path).unwrap();
+        assert!(cfg.entries.is_empty());
+        cfg.set("alias", None, "co", "checkout").unwrap();
+        cfg.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap();
+        assert_eq!(
+            loaded.get("alias", None, "co").unwrap().value.as_deref(),
+            Some("checkout")
+        );
+        assert!(!dir.join("config.lock").exists());
+
+        // A file with non-UTF-8 bytes is read but never rewritten
+        let latin1 = b"# caf\xe9\n[alias]\n\tco = checkout\n";
+        fs::write(&path, latin1).unwrap();
+        let mut cfg = ConfigFile::load(&path).unwrap();
+        assert!(cfg.get("alias", None, "co").is_some());
+        cfg.set("alias", None, "st", "status").unwrap();
+        assert!(cfg.save(&path).unwrap_err().to_string().contains("not valid UTF-8"));
+        let change = FileChange {
+            path: path.clone(),
+            before: Some(String::from_utf8_lossy(latin1).into_owned()),
+            after: Some(cfg.to_string()),
+        };
+        assert!(write_files(&[change], |_| Ok(())).is_err());
+        assert_eq!(fs::read(&path).unwrap(), latin1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn local_config_path_follows_gitdir_files() {
+        let root = std::env::temp_dir().join(format!("gam_test_gitdir_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let main_git = root.join("main").join(".git");
+        let wt_git = main_git.join("worktrees").join("wt");
+        fs::create_dir_all(&wt_git).unwrap();
+        fs::create_dir_all(root.join("wt").join("sub")).unwrap();
+        fs::write(wt_git.join("commondir"), "../..\n").unwrap();
+        fs::write(
+            root.join("wt").join(".git"),
+            format!("gitdir: {}\n", wt_git.display()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            local_config_path(&root.join("main")),
+            Some(main_git.join("config"))
+        );
+        let from_worktree = local_config_path(&root.join("wt").join("sub")).unwrap();
+        assert_eq!(from_worktree, wt_git.join("../..").join("config"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn wildmatch_follows_pathname_rules() {
+        assert!(wildmatch(b"**/work/**", b"/home/me/work/app/.git", false));
+        assert!(wildmatch(
+            b"/home/*/work/**",
+            b"/home/me/work/app/.git",
+            false
+        ));
+        assert!(!wildmatch(
+            b"/home/*/app/.git",
+            b"/home/me/work/app/.git",
+            false
+        ));
+        assert!(wildmatch(b"feature/**", b"feature/a/b", false));
+        assert!(wildmatch(b"rel-[0-9]?", b"rel-1a", false));
+        assert!(!wildmatch(b"rel-[!0-9]", b"rel-1", false));
+        assert!(wildmatch(b"**/Work/**", b"/x/work/y", true));
+        assert!(!wildmatch(b"**/Work/**", b"/x/work/y", false));
+    }
+
+    #[test]
+    fn read_with_includes_tracks_origin_and_chain() {
+        let root = std::env::temp_dir().join(format!("gam_test_includes_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("gitconfig.d")).unwrap();
+        fs::create_dir_all(root.join("work").join("app").join(".git")).unwrap();
+        fs::write(
+            root.join("work").join("app").join(".git").join("HEAD"),
+            "ref: refs/heads/main\n",
+        )
+        .unwrap();
+
+        let main = root.join("gitconfig");
+        fs::write(
+            &main,
+            "[alias]\n\tco = checkout\n\
+             [include]\n\tpath = gitconfig.d/team.inc\n\
+             [includeIf \"gitdir:./work/\"]\n\tpath = work.inc\n\
+             [includeIf \"onbranch:release/\"]\n\tpath = release.inc\n\
+             [include]\n\tpath = missing.inc\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("gitconfig.d").join("team.inc"),
+            "[alias]\n\tst = status\n[include]\n\tpath = nested.inc\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("gitconfig.d").join("nested.inc"),
+            "[alias]\n\tlg = log\n",
+        )
+        .unwrap();
+        fs::write(root.join("work.inc"), "[alias]\n\tco = switch\n").unwrap();
+        fs::write(root.join("release.inc"), "[alias]\n\trel = tag\n").unwrap();
+
+        let names = |entries: &[ResolvedEntry]| -> Vec<String> {
+            entries
+                .iter()
+                .filter(|r| r.entry.section == "alias")
+                .map(|r| r.entry.name.clone())
+                .collect()
+        };
+
+        let outside = read_with_includes(&main, &IncludeContext::default()).unwrap();
+        assert_eq!(names(&outside), vec!["co", "st", "lg"]);
+        let lg = outside.iter().find(|r| r.entry.name == "lg").unwrap();
+        assert_eq!(lg.origin, root.join("gitconfig.d").join("nested.inc"));
+        assert_eq!(
+            lg.include_chain,
+            vec![main.clone(), root.join("gitconfig.d").join("team.inc")]
+        );
+
+        let ctx = IncludeContext::for_repo(Some(&root.join("work").join("app")));
+        assert_eq!(ctx.branch.as_deref(), Some("main"));
+        let inside = read_with_includes(&main, &ctx).unwrap();
+        assert_eq!(names(&inside), vec!["co", "st", "lg", "co"]);
+        assert_eq!(
+            inside
+                .iter()
+                .rev()
+                .find(|r| r.entry.name == "co")
+                .unwrap()
+                .origin,
+            root.join("work.inc")
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn read_with_includes_stops_include_cycles() {
+        let root =
+            std::env::temp_dir().join(format!("gam_test_include_cycle_{}", std::process::id()));
+        let _ = fs::create_dir_all(&root);
+        fs::write(root.join("a"), "[include]\n\tpath = b\n").unwrap();
+        fs::write(root.join("b"), "[include]\n\tpath = a\n").unwrap();
+
+        let err = read_with_includes(&root.join("a"), &IncludeContext::default()).unwrap_err();
+        assert!(err.to_string().contains("maximum include depth"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn worktree_config_requires_extension() {
+        let repo = std::env::temp_dir().join(format!("gam_test_worktree_cfg_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&repo);
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        fs::write(repo.join(".git").join("config"), "[core]\n\tbare = false\n").unwrap();
+        assert_eq!(worktree_config_path(&repo), None);
+
+        fs::write(
+            repo.join(".git").join("config"),
+            "[extensions]\n\tworktreeConfig = true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            worktree_config_path(&repo),
+            Some(repo.join(".git").join("config.worktree"))
+        );
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alias_graph;
+use crate::error::GamError;
+use crate::settings_service::SettingsService;
+
+/// Settings key holding a custom path to the git executable.
+pub const GIT_PATH_SETTING: &str = "git_path";
+
+/// A git release, as printed by `git --version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GitVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl GitVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses `git version 2.39.2`, including vendor suffixes such as
+    /// `2.39.2 (Apple Git-143)`, `2.45.1.windows.1` or `2.46.0.rc1`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let word = output
+            .lines()
+            .next()?
+            .trim()
+            .strip_prefix("git version ")?
+            .split_whitespace()
+            .next()?;
+        let mut parts = word.split('.').map(|part| {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse::<u32>().ok()
+        });
+        let major = parts.next()??;
+        let minor = parts.next()??;
+        let patch = parts.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for GitVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Git features whose absence changes what GAM can show or do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    IncludeIf,
+    WorktreeConfig,
+    IncludeIfOnbranch,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [
+        Feature::IncludeIf,
+        Feature::WorktreeConfig,
+        Feature::IncludeIfOnbranch,
+    ];
+
+    /// The release that introduced the feature.
+    pub fn since(self) -> GitVersion {
+        match self {
+            Feature::IncludeIf => GitVersion::new(2, 13, 0),
+            Feature::WorktreeConfig => GitVersion::new(2, 20, 0),
+            Feature::IncludeIfOnbranch => GitVersion::new(2, 23, 0),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Feature::IncludeIf => "[includeIf]",
+            Feature::WorktreeConfig => "config.worktree",
+            Feature::IncludeIfOnbranch => "includeIf \"onbranch:\"",
+        }
+    }
+
+    /// What GAM does differently without the feature.
+    fn consequence(self) -> &'static str {
+        match self {
+            Feature::IncludeIf => "git ignores conditional includes, so GAM ignores them too",
+            Feature::WorktreeConfig => "git doesn't read it, so the worktree scope is unavailable",
+            Feature::IncludeIfOnbranch => {
+                "git ignores branch conditions, so GAM ignores them too"
+            }
+        }
+    }
+}
+
+/// Which features the installed git supports. All false when git wasn't found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub include_if: bool,
+    pub include_if_onbranch: bool,
+    pub worktree_config: bool,
+}
+
+/// The git executable GAM runs, its version and what it supports.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GitEnvironment {
+    /// The executable, if one was found.
+    pub path: Option<PathBuf>,
+    /// Whether `path` comes from the `git_path` setting rather than `PATH`.
+    pub custom_path: bool,
+    /// `None` when git couldn't be found or run.
+    pub version: Option<GitVersion>,
+    /// The first line of `git --version`.
+    pub version_output: Option<String>,
+    /// Where git keeps its own commands, from `git --exec-path`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec_path: Option<PathBuf>,
+    pub capabilities: Capabilities,
+    /// Features the installed git is too old for, and what that means.
+    pub warnings: Vec<String>,
+    /// Why git couldn't be used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn cache() -> &'static RwLock<Option<GitEnvironment>> {
+    static CACHE: OnceLock<RwLock<Option<GitEnvironment>>> = OnceLock::new();
+    CACHE.get_or_init(|| RwLock::new(None))
+}
+
+/// The custom git path from the settings, `None` for the git on `PATH`.
+fn custom_path() -> &'static RwLock<Option<PathBuf>> {
+    static CUSTOM: OnceLock<RwLock<Option<PathBuf>>> = OnceLock::new();
+    CUSTOM.get_or_init(|| RwLock::new(None))
+}
+
+impl GitEnvironment {
+    /// Finds git (`custom` if given, otherwise on `PATH`), runs
+    /// `git --version` and works out what it supports.
+    pub fn probe(custom: Option<&Path>) -> Self {
+        let mut env = Self {
+            custom_path: custom.is_some(),
+            ..Self::default()
+        };
+        let path = match custom {
+            Some(path) if path.is_file() => path.to_path_buf(),
+            Some(path) => {
+                env.error = Some(format!("Git executable not found: {}", path.display()));
+                return env;
+            }
+            None => match alias_graph::find_executable("git") {
+                Some(path) => path,
+                None => {
+                    env.error = Some(GamError::GitNotFound.to_string());
+                    return env;
+                }
+            },
+        };
+        env.path = Some(path.clone());
+
+        let output = Command::new(&path)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        let stdout = match output {
+            Ok(output) if output.status.success() => {
+                String::from_utf8_lossy(&output.stdout).to_string()
+            }
+            Ok(output) => {
+                env.error = Some(format!(
+                    "{} --version failed with exit code {:?}",
+                    path.display(),
+                    output.status.code()
+                ));
+                return env;
+            }
+            Err(e) => {
+                env.error = Some(format!("Failed to run {}: {}", path.display(), e));
+                return env;
+            }
+        };
+        env.version_output = stdout.lines().next().map(|line| line.trim().to_string());
+        let Some(version) = GitVersion::parse(&stdout) else {
+            env.error = Some(format!(
+                "{} is not git: unexpected --version output \"{}\"",
+                path.display(),
+                env.version_output.as_deref().unwrap_or_default()
+            ));
+            return env;
+        };
+        env.set_version(version);
+        env.exec_path = Command::new(&path)
+            .arg("--exec-path")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+            .filter(|dir| dir.is_dir());
+        env
+    }
+
+    /// Fills in the capabilities and warnings for `version`.
+    fn set_version(&mut self, version: GitVersion) {
+        let supports = |feature: Feature| version >= feature.since();
+        self.version = Some(version);
+        self.capabilities = Capabilities {
+            include_if: supports(Feature::IncludeIf),
+            include_if_onbranch: supports(Feature::IncludeIfOnbranch),
+            worktree_config: supports(Feature::WorktreeConfig),
+        };
+        self.warnings = Feature::ALL
+            .into_iter()
+            .filter(|f| !supports(*f))
+            .map(|f| {
+                format!(
+                    "Git {} is too old for {} (needs {}): {}",
+                    version,
+                    f.name(),
+                    f.since(),
+                    f.consequence()
+                )
+            })
+            .collect();
+    }
+
+    /// Probes with the `git_path` saved in `settings` from now on, e.g. at
+    /// startup or after it changed.
+    pub fn configure(settings: &SettingsService) {
+        let custom = settings
+            .get(GIT_PATH_SETTING)
+            .filter(|p| !p.trim().is_empty())
+            .map(PathBuf::from);
+        *custom_path().write().unwrap_or_else(|e| e.into_inner()) = custom;
+        *cache().write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// The environment probed with the configured `git_path`, probing on
+    /// first use.
+    pub fn current() -> Self {
+        if let Some(env) = cache().read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            return env.clone();
+        }
+        Self::refresh()
+    }
+
+    /// Probes git again, e.g. after it was installed or upgraded.
+    pub fn refresh() -> Self {
+        let custom = custom_path().read().unwrap_or_else(|e| e.into_inner()).clone();
+        let env = Self::probe(custom.as_deref());
+        Self::set_current(env.clone());
+        env
+    }
+
+    /// Replaces the cached environment.
+    pub fn set_current(env: Self) {
+        *cache().write().unwrap_or_else(|e| e.into_inner()) = Some(env);
+    }
+
+    /// True only when git was found and predates `feature`. An unknown git is
+    /// assumed to be current, since GAM reads the config files itself.
+    pub fn lacks(&self, feature: Feature) -> bool {
+        self.version.is_some_and(|v| v < feature.since())
+    }
+
+    /// `[includeIf]` condition prefixes this git doesn't understand.
+    pub fn ignored_include_conditions(&self) -> Vec<&'static str> {
+        if self.lacks(Feature::IncludeIf) {
+            vec!["gitdir:", "gitdir/i:", "onbranch:"]
+        } else if self.lacks(Feature::IncludeIfOnbranch) {
+            vec!["onbranch:"]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Fails with an explanation when this git is too old for `feature`.
+    pub fn require(&self, feature: Feature) -> Result<(), GamError> {
+        match self.version {
+            Some(version) if self.lacks(feature) => Err(GamError::Invalid(format!(
+                "Git {} is too old for {} (needs {}): {}",
+                version,
+                feature.name(),
+                feature.since(),
+                feature.consequence()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// A `git` command using the probed executable.
+    pub fn command(&self) -> Result<Command, GamError> {
+        match (&self.path, self.version) {
+            (Some(path), Some(_)) => Ok(Command::new(path)),
+            _ => Err(GamError::GitNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_version_output() {
+        let parse = GitVersion::parse;
+        assert_eq!(parse("git version 2.39.2\n"), Some(GitVersion::new(2, 39, 2)));
+        assert_eq!(
+            parse("git version 2.39.2 (Apple Git-143)"),
+            Some(GitVersion::new(2, 39, 2))
+        );
+        assert_eq!(
+            parse("git version 2.45.1.windows.1"),
+            Some(GitVersion::new(2, 45, 1))
+        );
+        assert_eq!(parse("git version 2.46.0.rc1"), Some(GitVersion::new(2, 46, 0)));
+        assert_eq!(parse("git version 2.9"), Some(GitVersion::new(2, 9, 0)));
+        assert_eq!(parse("hub version 2.14.2"), None);
+        assert_eq!(parse(""), None);
+        assert!(GitVersion::new(2, 9, 0) < GitVersion::new(2, 13, 0));
+    }
+
+    #[test]
+    fn old_git_degrades_and_warns() {
+        let mut env = GitEnvironment::default();
+        assert!(env.ignored_include_conditions().is_empty());
+        assert!(env.require(Feature::WorktreeConfig).is_ok());
+
+        env.set_version(GitVersion::new(2, 17, 1));
+        assert!(env.capabilities.include_if && !env.capabilities.worktree_config);
+        assert_eq!(env.ignored_include_conditions(), vec!["onbranch:"]);
+        assert!(
+            env.require(Feature::WorktreeConfig)
+                .unwrap_err()
+                .to_string()
+                .contains("needs 2.20.0")
+        );
+        assert_eq!(env.warnings.len(), 2);
+
+        env.set_version(GitVersion::new(2, 10, 0));
+        assert_eq!(env.ignored_include_conditions().len(), 3);
+
+        env.set_version(GitVersion::new(2, 43, 0));
+        assert!(env.warnings.is_empty());
+        assert_eq!(
+            env.capabilities,
+            Capabilities {
+                include_if: true,
+                include_if_onbranch: true,
+                worktree_config: true,
+            }
+        );
+    }
+
+    #[test]
+    fn probe_finds_the_exec_path() {
+        let env = GitEnvironment::probe(None);
+        assert!(env.exec_path.is_some_and(|dir| dir.join("git-sh-setup").is_file()));
+    }
+
+    #[test]
+    fn missing_custom_path_is_reported() {
+        let env = GitEnvironment::probe(Some(Path::new("/nonexistent/gam/git")));
+        assert!(env.custom_path && env.version.is_none());
+        assert!(env.error.unwrap().contains("/nonexistent/gam/git"));
+        assert!(matches!(
+            GitEnvironment::default().command(),
+            Err(GamError::GitNotFound)
+        ));
+    }
+}
@@ -1,10 +1,21 @@
-use std::process::Command;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::OnceLock;
 
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::alias_graph::{self, AliasExpansion, AliasGraph};
+use crate::backup_service::BackupService;
+use crate::error::{BatchError, GamError};
+use crate::git_config::{self, ConfigFile, FileChange, IncludeContext, ResolvedEntry};
+use crate::git_environment::{Feature, GitEnvironment};
 use crate::known_repos_service::KnownReposService;
-use crate::ranking_service::RankingService;
+use crate::ranking_service::{AliasScores, AliasSuggestion, RankingService, RepoUsage};
+use crate::sandbox_service::DryRunPlan;
+use crate::settings_service::SettingsService;
 
 /// Patterns in alias commands that are considered dangerous.
 static DANGEROUS_PATTERNS: &[(&str, &str)] = &[
@@ -16,15 +27,84 @@ static DANGEROUS_PATTERNS: &[(&str, &str)] = &[
     (r"branch\s+(?:\S.*)?-D", "Contains force branch delete (-D)"),
 ];
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A git config scope, ordered from lowest to highest precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// `$(prefix)/etc/gitconfig`, shared by every user of the machine.
+    System,
+    /// `~/.gitconfig` and `$XDG_CONFIG_HOME/git/config`.
+    #[default]
+    Global,
+    /// `.git/config` of a repository.
+    Local,
+    /// `.git/config.worktree`, when `extensions.worktreeConfig` is enabled.
+    Worktree,
+}
+
+impl Scope {
+    /// Every scope, lowest precedence first.
+    pub const ALL: [Scope; 4] = [Scope::System, Scope::Global, Scope::Local, Scope::Worktree];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::System => "system",
+            Scope::Global => "global",
+            Scope::Local => "local",
+            Scope::Worktree => "worktree",
+        }
+    }
+
+    /// Whether the scope's config lives inside a repository.
+    pub fn is_repo_scoped(self) -> bool {
+        matches!(self, Scope::Local | Scope::Worktree)
+    }
+
+    /// Like `Deserialize`, but an empty or missing scope means global,
+    /// which is what older exports contain.
+    pub fn deserialize_lenient<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Option::<String>::deserialize(deserializer)?.unwrap_or_default();
+        if raw.is_empty() {
+            return Ok(Scope::default());
+        }
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = GamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| GamError::Invalid(format!("Unsupported scope: {}", s)))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GitAlias {
     pub name: String,
     pub command: String,
-    pub scope: String,
+    #[serde(default, deserialize_with = "Scope::deserialize_lenient")]
+    pub scope: Scope,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub local_path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub score: Option<f64>,
+    /// Config file that defines the alias.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Files whose `[include]` / `[includeIf]` pulled in `origin`, outermost first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_chain: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,33 +112,175 @@ pub struct ValidationResult {
     pub valid: bool,
     pub warnings: Vec<String>,
     pub errors: Vec<String>,
+    /// The command line git will run, when it can be worked out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expansion: Option<String>,
+}
+
+/// One definition of an alias, flagged when a higher-precedence one overrides it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasDefinition {
+    #[serde(flatten)]
+    pub alias: GitAlias,
+    pub shadowed: bool,
+}
+
+/// How git resolves an alias name in a given repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasResolution {
+    pub name: String,
+    /// The definition git actually runs.
+    pub effective: GitAlias,
+    /// Every definition in the order git reads them; the last one wins.
+    pub definitions: Vec<AliasDefinition>,
+    /// Human-readable account of the precedence decision.
+    pub explanation: String,
+}
+
+/// One change applied by `GitService::apply_batch`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum AliasOperation {
+    Add {
+        name: String,
+        command: String,
+        #[serde(default, deserialize_with = "Scope::deserialize_lenient")]
+        scope: Scope,
+        #[serde(default)]
+        local_path: Option<String>,
+    },
+    Update {
+        old_name: String,
+        name: String,
+        command: String,
+        #[serde(default, deserialize_with = "Scope::deserialize_lenient")]
+        scope: Scope,
+        #[serde(default)]
+        local_path: Option<String>,
+        #[serde(default)]
+        origin: Option<String>,
+    },
+    Delete {
+        name: String,
+        #[serde(default, deserialize_with = "Scope::deserialize_lenient")]
+        scope: Scope,
+        #[serde(default)]
+        local_path: Option<String>,
+        #[serde(default)]
+        origin: Option<String>,
+    },
+}
+
+impl fmt::Display for AliasOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasOperation::Add { name, scope, .. } => write!(f, "add \"{}\" ({})", name, scope),
+            AliasOperation::Update {
+                old_name,
+                name,
+                scope,
+                ..
+            } if old_name != name => {
+                write!(f, "rename \"{}\" to \"{}\" ({})", old_name, name, scope)
+            }
+            AliasOperation::Update { name, scope, .. } => {
+                write!(f, "update \"{}\" ({})", name, scope)
+            }
+            AliasOperation::Delete { name, scope, .. } => {
+                write!(f, "delete \"{}\" ({})", name, scope)
+            }
+        }
+    }
+}
+
+/// Config files edited in memory by `apply_batch`, and where each alias is
+/// defined once the staged operations are applied.
+#[derive(Default)]
+struct Batch {
+    /// Each file with its contents when first read (`None` if it didn't exist).
+    files: Vec<(PathBuf, Option<String>, ConfigFile)>,
+    /// Defining file per (scope, repository, lowercase name); `None` once deleted.
+    defined: HashMap<(Scope, Option<String>, String), Option<PathBuf>>,
+}
+
+impl Batch {
+    fn file(&mut self, path: &Path) -> Result<&mut ConfigFile, GamError> {
+        let index = match self.files.iter().position(|(p, ..)| p == path) {
+            Some(index) => index,
+            None => {
+                let original = git_config::read_if_exists(path)?
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+                let config = match &original {
+                    Some(text) => ConfigFile::parse(text).map_err(|e| e.in_file(path))?,
+                    None => ConfigFile::default(),
+                };
+                self.files.push((path.to_path_buf(), original, config));
+                self.files.len() - 1
+            }
+        };
+        Ok(&mut self.files[index].2)
+    }
+
+    /// Backs up and writes every changed file with `git_config::write_files`,
+    /// and returns what changed.
+    fn commit(self, backups: &BackupService) -> Result<Vec<FileChange>, GamError> {
+        let changes: Vec<FileChange> = self
+            .files
+            .into_iter()
+            .map(|(path, before, config)| FileChange {
+                path,
+                before,
+                after: Some(config.to_string()),
+            })
+            .filter(|change| change.before != change.after)
+            .collect();
+        git_config::write_files(&changes, |changes| backups.snapshot(changes))?;
+        Ok(changes)
+    }
 }
 
 pub struct GitService {
     local_path: Option<String>,
     known_repos_service: KnownReposService,
     ranking_service: RankingService,
+    backup_service: BackupService,
 }
 
-impl Default for GitService {
-    fn default() -> Self {
-        Self::new()
+impl GitService {
+    pub fn new(settings: &SettingsService) -> Self {
+        Self::with_services(
+            KnownReposService::new(),
+            RankingService::new(settings),
+            BackupService::new(settings),
+        )
     }
-}
 
-impl GitService {
-    pub fn new() -> Self {
+    pub fn with_services(
+        known_repos_service: KnownReposService,
+        ranking_service: RankingService,
+        backup_service: BackupService,
+    ) -> Self {
         Self {
             local_path: None,
-            known_repos_service: KnownReposService::new(),
-            ranking_service: RankingService::new(),
+            known_repos_service,
+            ranking_service,
+            backup_service,
         }
     }
 
-    pub fn set_local_path(&mut self, path: Option<String>) {
-        if let Some(ref p) = path {
-            self.known_repos_service.add(p);
+    /// Selects the repository and remembers it; the selection holds even if
+    /// it can't be saved to the known repositories.
+    pub fn set_local_path(&mut self, path: Option<String>) -> Result<(), GamError> {
+        self.local_path = path.clone();
+        match path {
+            Some(p) => self.known_repos_service.add(&p),
+            None => Ok(()),
         }
+    }
+
+    /// Selects the repository for this service only, e.g. for one CLI
+    /// command, without adding it to the known repositories.
+    pub fn select_local_path(&mut self, path: Option<String>) {
         self.local_path = path;
     }
 
@@ -66,159 +288,235 @@ impl GitService {
         self.local_path.clone()
     }
 
-    fn exec_git(&self, args: &[&str], cwd: Option<&str>) -> Result<String, String> {
-        let mut cmd = Command::new("git");
-        cmd.args(args);
-        if let Some(dir) = cwd {
-            cmd.current_dir(dir);
-        }
-
-        match cmd.output() {
-            Ok(output) => {
-                if output.status.success() {
-                    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-                } else {
-                    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-                    // git config returns exit code 1 when no aliases exist
-                    if output.status.code() == Some(1)
-                        && args.contains(&"--get-regexp")
-                    {
-                        Ok(String::new())
-                    } else if stderr.is_empty() {
-                        Err(format!("Git command failed with exit code {:?}", output.status.code()))
-                    } else {
-                        Err(stderr)
-                    }
-                }
+    /// Backups taken before each config file write.
+    pub fn backups(&self) -> &BackupService {
+        &self.backup_service
+    }
+
+    pub fn backups_mut(&mut self) -> &mut BackupService {
+        &mut self.backup_service
+    }
+
+    pub fn ranking_mut(&mut self) -> &mut RankingService {
+        &mut self.ranking_service
+    }
+
+    /// How every alias's rank score is made up, highest first.
+    pub fn alias_scores(&mut self) -> Result<AliasScores, GamError> {
+        let aliases = self.get_aliases(None)?;
+        let mut scores: Vec<_> = self
+            .ranking_service
+            .get_scores(&aliases)?;
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        Ok(AliasScores {
+            half_life_days: self.ranking_service.half_life_days(),
+            aliases: scores,
+        })
+    }
+
+    /// Aliases worth adding for the git commands typed most often.
+    pub fn alias_suggestions(&mut self, limit: usize) -> Result<Vec<AliasSuggestion>, GamError> {
+        let aliases = self.get_aliases(None)?;
+        Ok(self.ranking_service.suggest_aliases(&aliases, limit))
+    }
+
+    /// Git usage in each known repository, busiest first.
+    pub fn repo_stats(&mut self, limit: usize) -> Result<Vec<RepoUsage>, GamError> {
+        let aliases = self.get_aliases(None)?;
+        let repos = self.known_repos_service.get_all();
+        Ok(self.ranking_service.repo_stats(&repos, &aliases, limit))
+    }
+
+    /// Resolves the config file `git config --<scope>` would write to.
+    fn config_path_for(&self, scope: Scope, local_path: Option<&str>) -> Result<PathBuf, GamError> {
+        let invalid = |message: String| GamError::Invalid(message);
+        let repo = || {
+            local_path
+                .map(|s| s.to_string())
+                .or_else(|| self.local_path.clone())
+                .ok_or_else(|| invalid("No local repository selected".to_string()))
+        };
+        match scope {
+            Scope::System => git_config::system_config_path().ok_or_else(|| {
+                invalid("System config is disabled (GIT_CONFIG_NOSYSTEM)".to_string())
+            }),
+            Scope::Global => Ok(git_config::global_write_path()),
+            Scope::Local => {
+                let repo = repo()?;
+                git_config::local_config_path(Path::new(&repo))
+                    .ok_or_else(|| invalid(format!("Not a git repository: {}", repo)))
             }
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    Err("Git is not installed or not found in PATH".to_string())
-                } else {
-                    Err(e.to_string())
-                }
+            Scope::Worktree => {
+                GitEnvironment::current().require(Feature::WorktreeConfig)?;
+                let repo = repo()?;
+                git_config::worktree_config_path(Path::new(&repo)).ok_or_else(|| {
+                    invalid(format!("extensions.worktreeConfig is not enabled in {}", repo))
+                })
             }
         }
     }
 
-    pub fn get_aliases(&mut self, scope: &str) -> Result<Vec<GitAlias>, String> {
+    /// Lists aliases of one scope, or of every scope when `scope` is `None`.
+    pub fn get_aliases(&mut self, scope: Option<Scope>) -> Result<Vec<GitAlias>, GamError> {
         let mut aliases: Vec<GitAlias> = Vec::new();
 
-        if scope == "global" || scope == "all" {
-            match self.exec_git(
-                &["config", "--global", "--get-regexp", r"^alias\."],
-                None,
-            ) {
-                Ok(output) => {
-                    aliases.extend(Self::parse_alias_output(&output, "global", None));
-                }
-                Err(_) => {
-                    // No global aliases or no global config
-                }
+        for current in Scope::ALL {
+            if scope.is_some_and(|s| s != current) {
+                continue;
+            }
+
+            if !current.is_repo_scoped() {
+                aliases.extend(self.read_scope(current, self.local_path.as_deref()));
+                continue;
             }
-        }
 
-        if scope == "local" || scope == "all" {
-            let paths_to_scan: Vec<String> = if scope == "all" || self.local_path.is_none() {
-                self.known_repos_service.get_all()
+            let paths_to_scan: Vec<String> = if scope.is_none() || self.local_path.is_none() {
+                let mut paths = self.known_repos_service.get_all();
+                // A repository selected without remembering it is listed too
+                if let Some(local) = &self.local_path
+                    && !paths.contains(local)
+                {
+                    paths.push(local.clone());
+                }
+                paths
             } else {
                 self.local_path.iter().cloned().collect()
             };
 
             for scan_path in &paths_to_scan {
-                match self.exec_git(
-                    &["config", "--local", "--get-regexp", r"^alias\."],
-                    Some(scan_path),
-                ) {
-                    Ok(output) => {
-                        aliases.extend(Self::parse_alias_output(
-                            &output,
-                            "local",
-                            Some(scan_path.clone()),
-                        ));
-                    }
-                    Err(_) => {
-                        // Ignore errors for individual paths
-                    }
-                }
+                aliases.extend(self.read_scope(current, Some(scan_path)));
             }
         }
 
         // Sort alphabetically
-        aliases.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        aliases.sort_by_key(|a| a.name.to_lowercase());
 
         // Rank aliases based on telemetry history
         if let Ok(scores) = self.ranking_service.get_scores(&aliases) {
-            for alias in &mut aliases {
-                alias.score = Some(*scores.get(&alias.name).unwrap_or(&0.0));
+            for (alias, breakdown) in aliases.iter_mut().zip(scores) {
+                alias.score = Some(breakdown.score);
             }
         }
 
         Ok(aliases)
     }
 
-    fn parse_alias_output(
-        output: &str,
-        scope: &str,
+    /// Lists the aliases of one scope as seen from `repo`, following includes.
+    /// `repo` selects the local config and decides which `[includeIf]` apply.
+    /// Like git, skips what the installed git is too old to read.
+    pub fn read_scope(&self, scope: Scope, repo: Option<&str>) -> Vec<GitAlias> {
+        let env = GitEnvironment::current();
+        let mut ctx = IncludeContext::for_repo(repo.map(Path::new));
+        ctx.ignored = env.ignored_include_conditions();
+        let files: Vec<PathBuf> = match scope {
+            Scope::System => git_config::system_config_path().into_iter().collect(),
+            Scope::Global => git_config::global_config_paths(),
+            // Ignore paths that are no longer git repositories
+            Scope::Local => repo
+                .and_then(|r| git_config::local_config_path(Path::new(r)))
+                .into_iter()
+                .collect(),
+            Scope::Worktree if env.lacks(Feature::WorktreeConfig) => Vec::new(),
+            Scope::Worktree => repo
+                .and_then(|r| git_config::worktree_config_path(Path::new(r)))
+                .into_iter()
+                .collect(),
+        };
+
+        let local_path = repo.filter(|_| scope.is_repo_scoped()).map(|r| r.to_string());
+        files
+            .iter()
+            .flat_map(|path| Self::read_aliases(path, scope, local_path.clone(), &ctx))
+            .collect()
+    }
+
+    /// Reads the `[alias]` entries of a config file and the files it includes.
+    /// Unreadable or malformed files yield no aliases, like an empty config.
+    fn read_aliases(
+        path: &Path,
+        scope: Scope,
         local_path: Option<String>,
+        ctx: &IncludeContext,
     ) -> Vec<GitAlias> {
-        if output.is_empty() {
-            return Vec::new();
+        match git_config::read_with_includes(path, ctx) {
+            Ok(entries) => Self::aliases_from_entries(&entries, scope, local_path),
+            Err(_) => Vec::new(),
         }
+    }
 
-        output
-            .lines()
-            .filter(|line| !line.is_empty())
-            .filter_map(|line| {
-                let first_space = line.find(' ')?;
-                let key = &line[..first_space];
-                let command = &line[first_space + 1..];
-                let name = key.strip_prefix("alias.").unwrap_or(key);
-
+    pub fn aliases_from_entries(
+        entries: &[ResolvedEntry],
+        scope: Scope,
+        local_path: Option<String>,
+    ) -> Vec<GitAlias> {
+        entries
+            .iter()
+            .filter(|r| r.entry.section == "alias" && r.entry.subsection.is_none())
+            .filter_map(|r| {
+                // A bare `name` without `=` is not a usable alias
+                let command = r.entry.value.as_ref()?;
                 Some(GitAlias {
-                    name: name.to_string(),
-                    command: command.to_string(),
-                    scope: scope.to_string(),
+                    name: r.entry.name.clone(),
+                    command: command.clone(),
+                    scope,
                     local_path: local_path.clone(),
                     score: None,
+                    origin: Some(r.origin.display().to_string()),
+                    include_chain: r
+                        .include_chain
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect(),
                 })
             })
             .collect()
     }
 
+    /// Finds the file that defines `name` in a scope. With `origin` set, checks
+    /// that this file really defines it; otherwise picks the definition git uses.
+    fn defining_file(
+        &self,
+        name: &str,
+        scope: Scope,
+        local_path: Option<&str>,
+        origin: Option<&str>,
+    ) -> Result<Option<PathBuf>, GamError> {
+        let repo = local_path.map(|s| s.to_string()).or_else(|| self.local_path.clone());
+        let definitions: Vec<GitAlias> = self
+            .read_scope(scope, repo.as_deref())
+            .into_iter()
+            .filter(|a| a.name.eq_ignore_ascii_case(name))
+            .collect();
+
+        let found = match origin {
+            Some(origin) => Some(
+                definitions
+                    .iter()
+                    .find(|a| a.origin.as_deref() == Some(origin))
+                    .ok_or_else(|| GamError::AliasNotFound {
+                        name: name.to_string(),
+                        scope,
+                        path: Some(PathBuf::from(origin)),
+                    })?,
+            ),
+            None => definitions.last(),
+        };
+        Ok(found.and_then(|a| a.origin.as_ref()).map(PathBuf::from))
+    }
+
     pub fn add_alias(
         &mut self,
         name: &str,
         command: &str,
-        scope: &str,
+        scope: Scope,
         local_path: Option<&str>,
-    ) -> Result<(), String> {
-        // Defense-in-depth: validate alias name even though frontend also checks
-        Self::validate_alias_name(name)?;
-
-        // Check if alias already exists
-        let existing = self.get_aliases(scope)?;
-        if existing.iter().any(|a| a.name == name) {
-            return Err(format!(
-                "Alias \"{}\" already exists in {} config",
-                name, scope
-            ));
-        }
-
-        let target_path = if scope == "local" {
-            local_path
-                .map(|s| s.to_string())
-                .or_else(|| self.local_path.clone())
-        } else {
-            None
-        };
-
-        self.exec_git(
-            &["config", &format!("--{}", scope), &format!("alias.{}", name), command],
-            target_path.as_deref(),
-        )?;
-
-        Ok(())
+    ) -> Result<Vec<FileChange>, GamError> {
+        self.apply_one(AliasOperation::Add {
+            name: name.to_string(),
+            command: command.to_string(),
+            scope,
+            local_path: local_path.map(|s| s.to_string()),
+        })
     }
 
     pub fn update_alias(
@@ -226,68 +524,354 @@ impl GitService {
         old_name: &str,
         name: &str,
         command: &str,
-        scope: &str,
+        scope: Scope,
         local_path: Option<&str>,
-    ) -> Result<(), String> {
-        Self::validate_alias_name(name)?;
+        origin: Option<&str>,
+    ) -> Result<Vec<FileChange>, GamError> {
+        self.apply_one(AliasOperation::Update {
+            old_name: old_name.to_string(),
+            name: name.to_string(),
+            command: command.to_string(),
+            scope,
+            local_path: local_path.map(|s| s.to_string()),
+            origin: origin.map(|s| s.to_string()),
+        })
+    }
 
-        let target_path = if scope == "local" {
-            local_path
-                .map(|s| s.to_string())
-                .or_else(|| self.local_path.clone())
-        } else {
-            None
-        };
+    pub fn delete_alias(
+        &mut self,
+        name: &str,
+        scope: Scope,
+        local_path: Option<&str>,
+        origin: Option<&str>,
+    ) -> Result<Vec<FileChange>, GamError> {
+        self.apply_one(AliasOperation::Delete {
+            name: name.to_string(),
+            scope,
+            local_path: local_path.map(|s| s.to_string()),
+            origin: origin.map(|s| s.to_string()),
+        })
+    }
 
-        // If name changed, delete old one first
-        if old_name != name {
-            let _ = self.exec_git(
-                &[
-                    "config",
-                    &format!("--{}", scope),
-                    "--unset",
-                    &format!("alias.{}", old_name),
-                ],
-                target_path.as_deref(),
-            );
-        }
+    /// Applies a single operation, reporting its error as is.
+    fn apply_one(&mut self, op: AliasOperation) -> Result<Vec<FileChange>, GamError> {
+        let mut batch = Batch::default();
+        self.stage(&mut batch, &op)?;
+        batch.commit(&self.backup_service)
+    }
 
-        self.exec_git(
-            &["config", &format!("--{}", scope), &format!("alias.{}", name), command],
-            target_path.as_deref(),
-        )?;
+    /// Applies `operations` as one transaction. Each operation is validated and
+    /// applied to an in-memory copy of its config file first, and every error
+    /// is reported before anything is written. The files are then replaced
+    /// atomically and restored if one of them can't be written. Returns the
+    /// files that changed.
+    pub fn apply_batch(
+        &mut self,
+        operations: &[AliasOperation],
+    ) -> Result<Vec<FileChange>, GamError> {
+        let mut batch = Batch::default();
+        let errors: Vec<BatchError> = operations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, op)| {
+                self.stage(&mut batch, op).err().map(|error| BatchError {
+                    index: i + 1,
+                    operation: op.to_string(),
+                    error,
+                })
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(GamError::Batch(errors));
+        }
+        batch.commit(&self.backup_service)
+    }
 
+    fn stage(&self, batch: &mut Batch, op: &AliasOperation) -> Result<(), GamError> {
+        match op {
+            AliasOperation::Add {
+                name,
+                command,
+                scope,
+                local_path,
+            } => {
+                Self::validate_alias_name(name)?;
+                let local_path = local_path.as_deref();
+                if let Some(origin) =
+                    self.staged_definition(batch, name, *scope, local_path, None)?
+                {
+                    return Err(GamError::AliasExists {
+                        name: name.clone(),
+                        scope: *scope,
+                        path: Some(origin),
+                    });
+                }
+                let path = self.config_path_for(*scope, local_path)?;
+                batch.file(&path)?.set("alias", None, name, command)?;
+                batch.defined.insert(self.batch_key(name, *scope, local_path), Some(path));
+            }
+            AliasOperation::Update {
+                old_name,
+                name,
+                command,
+                scope,
+                local_path,
+                origin,
+            } => {
+                Self::validate_alias_name(name)?;
+                let local_path = local_path.as_deref();
+                // Renaming onto another alias would overwrite or shadow it
+                if !old_name.eq_ignore_ascii_case(name)
+                    && let Some(origin) =
+                        self.staged_definition(batch, name, *scope, local_path, None)?
+                {
+                    return Err(GamError::AliasExists {
+                        name: name.clone(),
+                        scope: *scope,
+                        path: Some(origin),
+                    });
+                }
+                let defined = self.staged_definition(
+                    batch,
+                    old_name,
+                    *scope,
+                    local_path,
+                    origin.as_deref(),
+                )?;
+                let path = match defined {
+                    Some(path) => path,
+                    // Renaming needs the old alias; updating one that's gone re-creates it.
+                    None if old_name != name => {
+                        return Err(GamError::AliasNotFound {
+                            name: old_name.clone(),
+                            scope: *scope,
+                            path: None,
+                        });
+                    }
+                    None => self.config_path_for(*scope, local_path)?,
+                };
+                if old_name != name {
+                    if !batch.file(&path)?.unset("alias", None, old_name)? {
+                        return Err(GamError::AliasNotFound {
+                            name: old_name.clone(),
+                            scope: *scope,
+                            path: Some(path),
+                        });
+                    }
+                    batch.defined.insert(self.batch_key(old_name, *scope, local_path), None);
+                }
+                batch.file(&path)?.set("alias", None, name, command)?;
+                batch.defined.insert(self.batch_key(name, *scope, local_path), Some(path));
+            }
+            AliasOperation::Delete {
+                name,
+                scope,
+                local_path,
+                origin,
+            } => {
+                let local_path = local_path.as_deref();
+                let not_found = || GamError::AliasNotFound {
+                    name: name.clone(),
+                    scope: *scope,
+                    path: None,
+                };
+                let path = self
+                    .staged_definition(batch, name, *scope, local_path, origin.as_deref())?
+                    .ok_or_else(not_found)?;
+                if !batch.file(&path)?.unset("alias", None, name)? {
+                    return Err(not_found());
+                }
+                batch.defined.insert(self.batch_key(name, *scope, local_path), None);
+            }
+        }
         Ok(())
     }
 
-    pub fn delete_alias(
-        &mut self,
+    fn batch_key(
+        &self,
         name: &str,
-        scope: &str,
+        scope: Scope,
         local_path: Option<&str>,
-    ) -> Result<(), String> {
-        let target_path = if scope == "local" {
-            local_path
-                .map(|s| s.to_string())
-                .or_else(|| self.local_path.clone())
+    ) -> (Scope, Option<String>, String) {
+        let repo = scope
+            .is_repo_scoped()
+            .then(|| local_path.map(|s| s.to_string()).or_else(|| self.local_path.clone()))
+            .flatten();
+        (scope, repo, name.to_lowercase())
+    }
+
+    /// Like `defining_file`, but takes the operations staged in `batch` into account.
+    fn staged_definition(
+        &self,
+        batch: &Batch,
+        name: &str,
+        scope: Scope,
+        local_path: Option<&str>,
+        origin: Option<&str>,
+    ) -> Result<Option<PathBuf>, GamError> {
+        match batch.defined.get(&self.batch_key(name, scope, local_path)) {
+            Some(Some(path)) if origin.is_some_and(|o| Path::new(o) != path) => {
+                Err(GamError::AliasNotFound {
+                    name: name.to_string(),
+                    scope,
+                    path: origin.map(PathBuf::from),
+                })
+            }
+            Some(path) => Ok(path.clone()),
+            None => self.defining_file(name, scope, local_path, origin),
+        }
+    }
+
+    /// Computes the effective definition of each alias as git sees it inside
+    /// `repo` (or the selected repository), marking shadowed definitions.
+    pub fn resolve_aliases(&self, repo: Option<&str>, name: Option<&str>) -> Vec<AliasResolution> {
+        let repo = repo.map(|r| r.to_string()).or_else(|| self.local_path.clone());
+        let definitions: Vec<GitAlias> = Scope::ALL
+            .into_iter()
+            .flat_map(|scope| self.read_scope(scope, repo.as_deref()))
+            .filter(|a| name.is_none_or(|n| a.name.eq_ignore_ascii_case(n)))
+            .collect();
+        Self::build_resolutions(definitions)
+    }
+
+    /// Groups definitions (given in git's read order) by name; the last wins.
+    fn build_resolutions(definitions: Vec<GitAlias>) -> Vec<AliasResolution> {
+        let mut by_name: Vec<(String, Vec<GitAlias>)> = Vec::new();
+        for alias in definitions {
+            let key = alias.name.to_lowercase();
+            match by_name.iter_mut().find(|(n, _)| *n == key) {
+                Some((_, defs)) => defs.push(alias),
+                None => by_name.push((key, vec![alias])),
+            }
+        }
+        by_name.sort_by(|a, b| a.0.cmp(&b.0));
+
+        by_name
+            .into_iter()
+            .filter_map(|(name, defs)| {
+                let effective = defs.last()?.clone();
+                let last = defs.len() - 1;
+                let explanation = Self::explain_resolution(&name, &defs);
+                Some(AliasResolution {
+                    name,
+                    effective,
+                    definitions: defs
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, alias)| AliasDefinition {
+                            alias,
+                            shadowed: i != last,
+                        })
+                        .collect(),
+                    explanation,
+                })
+            })
+            .collect()
+    }
+
+    fn explain_resolution(name: &str, defs: &[GitAlias]) -> String {
+        let describe = |a: &GitAlias| match &a.origin {
+            Some(origin) => format!("{} config ({})", a.scope, origin),
+            None => format!("{} config", a.scope),
+        };
+        let Some((effective, shadowed)) = defs.split_last() else {
+            return String::new();
+        };
+
+        if shadowed.is_empty() {
+            return format!("\"{}\" is only defined in {}.", name, describe(effective));
+        }
+
+        let overridden: Vec<String> = shadowed
+            .iter()
+            .rev()
+            .map(|a| format!("\"{}\" from {}", a.command, describe(a)))
+            .collect();
+        format!(
+            "\"{}\" runs \"{}\" from {}. It overrides {}. Git reads system, global, local and \
+             worktree config in that order, following includes where they appear, and the last \
+             definition wins.",
+            name,
+            effective.command,
+            describe(effective),
+            overridden.join(", ")
+        )
+    }
+
+    /// Expands an invocation such as `git lg -5 main` against the aliases in
+    /// effect in `repo` (or the selected repository).
+    pub fn expand_alias(&self, invocation: &str, repo: Option<&str>) -> Result<AliasExpansion, GamError> {
+        self.alias_graph(repo).expand(invocation)
+    }
+
+    /// Prepares a sandbox dry run of `name`, or of `command` under that name
+    /// when trying out an alias that isn't saved yet.
+    pub fn dry_run_plan(
+        &self,
+        name: &str,
+        command: Option<&str>,
+        repo: Option<&str>,
+        clone_repo: bool,
+    ) -> Result<DryRunPlan, GamError> {
+        let repo = repo.map(|r| r.to_string()).or_else(|| self.local_path.clone());
+        let mut aliases: Vec<(String, String)> = self.effective_commands(repo.as_deref()).collect();
+        if let Some(command) = command {
+            aliases.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+            aliases.push((name.to_string(), command.to_string()));
+        }
+        let command = aliases
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, c)| c.clone())
+            .ok_or_else(|| GamError::NotFound(format!("alias \"{}\"", name)))?;
+
+        let validation = self.validate_alias(name, &command, repo.as_deref(), None);
+        if !validation.valid {
+            return Err(GamError::Invalid(validation.errors.join("; ")));
+        }
+        let source = if clone_repo {
+            let repo = repo
+                .ok_or_else(|| GamError::Invalid("No local repository selected".to_string()))?;
+            Some(PathBuf::from(repo))
         } else {
             None
         };
 
-        self.exec_git(
-            &[
-                "config",
-                &format!("--{}", scope),
-                "--unset",
-                &format!("alias.{}", name),
-            ],
-            target_path.as_deref(),
-        )?;
+        Ok(DryRunPlan {
+            name: name.to_string(),
+            aliases,
+            source,
+            warnings: validation.warnings,
+        })
+    }
 
-        Ok(())
+    fn effective_commands(&self, repo: Option<&str>) -> impl Iterator<Item = (String, String)> {
+        self.resolve_aliases(repo, None)
+            .into_iter()
+            .map(|r| (r.name, r.effective.command))
     }
 
+    fn alias_graph(&self, repo: Option<&str>) -> AliasGraph {
+        AliasGraph::new(self.effective_commands(repo))
+    }
+
+    /// Validates an alias value against the selected repository's aliases:
+    /// dangerous patterns, and a value that expands to something that's
+    /// neither a git command nor an alias, or loops.
     pub fn validate_command(&self, command: &str) -> ValidationResult {
+        let mut result = Self::check_command(command);
+        if result.valid && !command.starts_with('!') {
+            match self.alias_graph(None).expand(command) {
+                Ok(expansion) => result.expansion = Some(expansion.command_line),
+                Err(e) => result.errors.push(e.to_string()),
+            }
+            result.valid = result.errors.is_empty();
+        }
+        result
+    }
+
+    /// The checks on an alias value that don't depend on other aliases.
+    fn check_command(command: &str) -> ValidationResult {
         let mut warnings = Vec::new();
         let mut errors = Vec::new();
 
@@ -297,6 +881,7 @@ impl GitService {
                 valid: false,
                 warnings,
                 errors,
+                expansion: None,
             };
         }
 
@@ -330,79 +915,493 @@ impl GitService {
             valid: errors.is_empty(),
             warnings,
             errors,
+            expansion: None,
         }
     }
 
-    /// Validates that an alias name matches `^[a-zA-Z][\w-]*$`.
-    /// Defense-in-depth: the frontend enforces the same rule.
-    fn validate_alias_name(name: &str) -> Result<(), String> {
+    /// Validates an alias as it would be saved into `repo`'s effective config:
+    /// the command itself, its name against built-ins and `git-*` executables,
+    /// and the alias-to-alias expansion graph. `old_name` is the alias being
+    /// renamed, if any, and is left out of the graph.
+    pub fn validate_alias(
+        &self,
+        name: &str,
+        command: &str,
+        repo: Option<&str>,
+        old_name: Option<&str>,
+    ) -> ValidationResult {
+        let mut result = Self::check_command(command);
+        if let Err(e) = Self::validate_alias_name(name) {
+            result.errors.push(e.to_string());
+            result.valid = false;
+            return result;
+        }
+
+        if alias_graph::is_builtin(name) {
+            result.warnings.push(format!(
+                "\"{}\" is a built-in git command; git ignores aliases with this name",
+                name
+            ));
+        } else if let Some(path) = alias_graph::find_external_command(name) {
+            result.warnings.push(format!(
+                "\"git {}\" runs {} instead of this alias",
+                name,
+                path.display()
+            ));
+        }
+
+        if !command.trim().is_empty() {
+            let graph = AliasGraph::new(
+                self.effective_commands(repo)
+                    .filter(|(n, _)| old_name.is_none_or(|o| !n.eq_ignore_ascii_case(o)))
+                    .filter(|(n, _)| !n.eq_ignore_ascii_case(name))
+                    .chain(std::iter::once((name.to_string(), command.to_string()))),
+            );
+            if let Some(cycle) = graph.find_cycle(name) {
+                result
+                    .errors
+                    .push(format!("Alias loop detected: {}", cycle.join(" -> ")));
+            } else if let Some(target) = graph.dead_reference(name) {
+                result.errors.push(format!(
+                    "\"{}\" expands to \"{}\", which is neither a git command nor an alias",
+                    name, target
+                ));
+            }
+            if result.errors.is_empty() {
+                result.expansion = graph.expand(name).ok().map(|e| e.command_line);
+            }
+        }
+
+        result.valid = result.errors.is_empty();
+        result
+    }
+
+    /// Validates that an alias name matches `^[a-zA-Z][a-zA-Z0-9-]*$`, the
+    /// characters git allows in a config key. Defense-in-depth: the frontend
+    /// enforces the same rule.
+    pub fn validate_alias_name(name: &str) -> Result<(), GamError> {
         fn alias_name_re() -> &'static regex_lite::Regex {
             static RE: OnceLock<regex_lite::Regex> = OnceLock::new();
-            RE.get_or_init(|| regex_lite::Regex::new(r"^[a-zA-Z][\w-]*$").unwrap())
+            RE.get_or_init(|| regex_lite::Regex::new(r"^[a-zA-Z][a-zA-Z0-9-]*$").unwrap())
         }
 
         if name.is_empty() {
-            return Err("Alias name cannot be empty".to_string());
+            return Err(GamError::Invalid("Alias name cannot be empty".to_string()));
         }
         if !alias_name_re().is_match(name) {
-            return Err(format!(
-                "Invalid alias name \"{}\": must start with a letter and contain only letters, numbers, hyphens, underscores",
+            return Err(GamError::Invalid(format!(
+                "Invalid alias name \"{}\": must start with a letter and contain only letters, numbers and hyphens",
                 name
-            ));
+            )));
         }
         Ok(())
     }
 }
 
+#[cfg(test)]
+impl GitService {
+    /// A service that keeps known repositories and backups in a scratch
+    /// directory and usage in memory, away from the user's app data.
+    pub(crate) fn scratch() -> Self {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir()
+            .join(format!("gam_test_service_{}_{}", std::process::id(), id));
+        Self::with_services(
+            KnownReposService::open(dir.join("known-repos.json")),
+            RankingService::in_memory(),
+            BackupService::with_dir(dir.join("backups"), Default::default()),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!("gam_test_{}_{}_{}", label, std::process::id(), id));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn aliases_from(text: &str, scope: Scope, local_path: Option<String>) -> Vec<GitAlias> {
+        let dir = temp_dir("aliases");
+        let path = dir.join("config");
+        fs::write(&path, text).unwrap();
+        let aliases = GitService::read_aliases(&path, scope, local_path, &IncludeContext::default());
+        let _ = fs::remove_dir_all(&dir);
+        aliases
+    }
 
     #[test]
-    fn parse_alias_output_parses_global_aliases() {
-        let output = "alias.co checkout\nalias.st status -sb\n";
-        let aliases = GitService::parse_alias_output(output, "global", None);
+    fn aliases_from_config_parses_global_aliases() {
+        let aliases = aliases_from("[alias]\n\tco = checkout\n\tst = status -sb\n", Scope::Global, None);
 
         assert_eq!(aliases.len(), 2);
         assert_eq!(aliases[0].name, "co");
         assert_eq!(aliases[0].command, "checkout");
-        assert_eq!(aliases[0].scope, "global");
+        assert_eq!(aliases[0].scope, Scope::Global);
         assert!(aliases[0].local_path.is_none());
     }
 
     #[test]
-    fn parse_alias_output_handles_local_path() {
-        let output = "alias.lg log --oneline --graph\n";
-        let aliases = GitService::parse_alias_output(output, "local", Some("/tmp/repo".to_string()));
+    fn aliases_from_config_handles_local_path() {
+        let aliases = aliases_from(
+            "[alias]\n\tlg = log --oneline --graph\n",
+            Scope::Local,
+            Some("/tmp/repo".to_string()),
+        );
 
         assert_eq!(aliases.len(), 1);
-        assert_eq!(aliases[0].scope, "local");
+        assert_eq!(aliases[0].scope, Scope::Local);
         assert_eq!(aliases[0].local_path, Some("/tmp/repo".to_string()));
     }
 
     #[test]
-    fn parse_alias_output_handles_empty_input() {
-        let aliases = GitService::parse_alias_output("", "global", None);
+    fn aliases_from_config_handles_empty_input() {
+        let aliases = aliases_from("", Scope::Global, None);
         assert!(aliases.is_empty());
     }
 
     #[test]
-    fn parse_alias_output_skips_empty_lines() {
-        let output = "alias.co checkout\n\n\nalias.st status\n";
-        let aliases = GitService::parse_alias_output(output, "global", None);
+    fn aliases_from_config_skips_other_sections() {
+        let text = "[core]\n\teditor = vim\n[alias]\n\tco = checkout\n[alias \"sub\"]\n\tx = y\n[alias]\n\tst = status\n";
+        let aliases = aliases_from(text, Scope::Global, None);
         assert_eq!(aliases.len(), 2);
     }
 
     #[test]
-    fn parse_alias_output_handles_multiword_commands() {
-        let output = "alias.lg log --oneline --graph --decorate --all\n";
-        let aliases = GitService::parse_alias_output(output, "global", None);
+    fn aliases_from_config_handles_multiword_commands() {
+        let aliases = aliases_from("[alias]\n\tlg = log --oneline --graph --decorate --all\n", Scope::Global, None);
         assert_eq!(aliases[0].command, "log --oneline --graph --decorate --all");
     }
 
+    #[test]
+    fn aliases_from_config_keeps_multiline_shell_aliases() {
+        let text = "[alias]\n\tsync = \"!f() { \\\n\t\tgit fetch && \\\n\t\tgit rebase; }; f\"\n";
+        let aliases = aliases_from(text, Scope::Global, None);
+        assert_eq!(aliases[0].command, "!f() { \t\tgit fetch && \t\tgit rebase; }; f");
+    }
+
+    #[test]
+    fn aliases_carry_origin_and_include_chain() {
+        let dir = temp_dir("origin");
+        let main = dir.join("config");
+        fs::write(&main, "[alias]\n\tco = checkout\n[include]\n\tpath = team.inc\n").unwrap();
+        fs::write(dir.join("team.inc"), "[alias]\n\tst = status\n").unwrap();
+
+        let aliases = GitService::read_aliases(&main, Scope::Global, None, &IncludeContext::default());
+        assert_eq!(aliases[0].origin, Some(main.display().to_string()));
+        assert!(aliases[0].include_chain.is_empty());
+        assert_eq!(aliases[1].origin, Some(dir.join("team.inc").display().to_string()));
+        assert_eq!(aliases[1].include_chain, vec![main.display().to_string()]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn writes_go_to_the_defining_file() {
+        let repo = temp_dir("write_origin");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        let local = repo.join(".git").join("config");
+        let team = repo.join("team.inc");
+        fs::write(&local, "[include]\n\tpath = ../team.inc\n").unwrap();
+        fs::write(&team, "# shared\n[alias]\n\tco = checkout\n").unwrap();
+        let repo_str = repo.to_str().unwrap();
+
+        let mut svc = GitService::scratch();
+        assert!(svc.add_alias("co", "switch", Scope::Local, Some(repo_str)).is_err());
+
+        // Renaming an alias that doesn't exist fails instead of just adding the new name
+        let err = svc.update_alias("nope", "np", "log", Scope::Local, Some(repo_str), None);
+        assert!(matches!(
+            err.unwrap_err(),
+            GamError::AliasNotFound { name, .. } if name == "nope"
+        ));
+
+        let changes =
+            svc.update_alias("co", "sw", "switch", Scope::Local, Some(repo_str), None).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].path.ends_with("team.inc"));
+        assert_eq!(changes[0].before.as_deref(), Some("# shared\n[alias]\n\tco = checkout\n"));
+        assert_eq!(fs::read_to_string(&team).unwrap(), "# shared\n[alias]\n\tsw = switch\n");
+        assert_eq!(fs::read_to_string(&local).unwrap(), "[include]\n\tpath = ../team.inc\n");
+
+        let wrong_origin = local.display().to_string();
+        assert!(svc.delete_alias("sw", Scope::Local, Some(repo_str), Some(&wrong_origin)).is_err());
+        svc.delete_alias("sw", Scope::Local, Some(repo_str), None).unwrap();
+        assert_eq!(fs::read_to_string(&team).unwrap(), "# shared\n[alias]\n");
+
+        svc.add_alias("lg", "log", Scope::Local, Some(repo_str)).unwrap();
+        assert!(fs::read_to_string(&local).unwrap().contains("[alias]\n\tlg = log\n"));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn rename_onto_an_existing_alias_fails() {
+        let repo = temp_dir("rename_exists");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        let local = repo.join(".git").join("config");
+        let team = repo.join("team.inc");
+        let config = "[include]\n\tpath = ../team.inc\n[alias]\n\tlg = log\n";
+        fs::write(&local, config).unwrap();
+        fs::write(&team, "[alias]\n\tco = checkout\n").unwrap();
+        let repo_str = repo.to_str().unwrap();
+
+        let mut svc = GitService::scratch();
+        let err = svc.update_alias("lg", "CO", "switch", Scope::Local, Some(repo_str), None);
+        assert!(matches!(
+            err.unwrap_err(),
+            GamError::AliasExists { name, path: Some(path), .. }
+                if name == "CO" && path.ends_with("team.inc")
+        ));
+        assert_eq!(fs::read_to_string(&local).unwrap(), config);
+
+        // Changing only the case of a name is still a rename of the same alias
+        svc.update_alias("lg", "LG", "log", Scope::Local, Some(repo_str), None).unwrap();
+        assert!(fs::read_to_string(&local).unwrap().contains("\tLG = log\n"));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn apply_batch_writes_all_files_or_none() {
+        let dir = temp_dir("batch");
+        let (one, two) = (dir.join("one"), dir.join("two"));
+        for repo in [&one, &two] {
+            fs::create_dir_all(repo.join(".git")).unwrap();
+            fs::write(repo.join(".git").join("config"), "[alias]\n\tco = checkout\n").unwrap();
+        }
+        let (one_str, two_str) = (one.to_str().unwrap(), two.to_str().unwrap());
+        let config = |repo: &Path| fs::read_to_string(repo.join(".git").join("config")).unwrap();
+        let add = |name: &str, repo: &str| AliasOperation::Add {
+            name: name.to_string(),
+            command: "log".to_string(),
+            scope: Scope::Local,
+            local_path: Some(repo.to_string()),
+        };
+        let delete = |name: &str, repo: &str| AliasOperation::Delete {
+            name: name.to_string(),
+            scope: Scope::Local,
+            local_path: Some(repo.to_string()),
+            origin: None,
+        };
+        let mut svc = GitService::scratch();
+
+        // Every invalid operation is reported and nothing is written
+        let err = svc
+            .apply_batch(&[
+                add("lg", one_str),
+                add("co", two_str),
+                delete("nope", one_str),
+                add("1x", two_str),
+            ])
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("#2 add \"co\" (local)"));
+        assert!(message.contains("#3 delete \"nope\""));
+        assert!(message.contains("#4 add \"1x\""));
+        let GamError::Batch(errors) = err else {
+            panic!("expected a batch error");
+        };
+        let codes: Vec<(usize, &str)> = errors.iter().map(|e| (e.index, e.error.code())).collect();
+        assert_eq!(
+            codes,
+            [(2, "ALIAS_EXISTS"), (3, "ALIAS_NOT_FOUND"), (4, "INVALID_INPUT")]
+        );
+        assert_eq!(config(&one), "[alias]\n\tco = checkout\n");
+
+        // Later operations see earlier ones: co can be re-added once deleted
+        svc.apply_batch(&[
+            add("lg", one_str),
+            delete("co", two_str),
+            AliasOperation::Update {
+                old_name: "lg".to_string(),
+                name: "l".to_string(),
+                command: "log --oneline".to_string(),
+                scope: Scope::Local,
+                local_path: Some(one_str.to_string()),
+                origin: None,
+            },
+            add("co", two_str),
+        ])
+        .unwrap();
+        assert_eq!(config(&one), "[alias]\n\tco = checkout\n\tl = log --oneline\n");
+        assert_eq!(config(&two), "[alias]\n\tco = log\n");
+
+        // A file locked by another git process aborts the whole batch
+        let lock = two.join(".git").join("config.lock");
+        fs::write(&lock, "").unwrap();
+        assert!(svc.apply_batch(&[delete("l", one_str), delete("co", two_str)]).is_err());
+        assert!(config(&one).contains("\tl = log --oneline\n"));
+        assert!(!one.join(".git").join("config.lock").exists());
+        fs::remove_file(&lock).unwrap();
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn scope_parses_and_displays() {
+        for scope in Scope::ALL {
+            assert_eq!(scope.as_str().parse::<Scope>().unwrap(), scope);
+        }
+        assert!("all".parse::<Scope>().is_err());
+        assert!(Scope::System < Scope::Global && Scope::Local < Scope::Worktree);
+        assert_eq!(serde_json::to_string(&Scope::Worktree).unwrap(), "\"worktree\"");
+    }
+
+    #[test]
+    fn worktree_scope_reads_config_worktree() {
+        let repo = temp_dir("worktree_scope");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("config"), "[extensions]\n\tworktreeConfig\n[alias]\n\tco = checkout\n").unwrap();
+        fs::write(repo.join(".git").join("config.worktree"), "[alias]\n\tco = switch\n").unwrap();
+        let repo_str = repo.to_str().unwrap();
+
+        let svc = GitService::scratch();
+        let local = svc.read_scope(Scope::Local, Some(repo_str));
+        let worktree = svc.read_scope(Scope::Worktree, Some(repo_str));
+        assert_eq!(local[0].command, "checkout");
+        assert_eq!(worktree[0].command, "switch");
+        assert_eq!(worktree[0].scope, Scope::Worktree);
+        assert_eq!(worktree[0].local_path.as_deref(), Some(repo_str));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    fn alias(name: &str, command: &str, scope: Scope, origin: &str) -> GitAlias {
+        GitAlias {
+            name: name.to_string(),
+            command: command.to_string(),
+            scope,
+            local_path: None,
+            score: None,
+            origin: Some(origin.to_string()),
+            include_chain: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn build_resolutions_marks_shadowed_definitions() {
+        let resolutions = GitService::build_resolutions(vec![
+            alias("co", "checkout", Scope::System, "/etc/gitconfig"),
+            alias("st", "status", Scope::Global, "/home/me/.gitconfig"),
+            alias("co", "checkout -b", Scope::Global, "/home/me/.gitconfig"),
+            alias("co", "switch", Scope::Local, "/repo/.git/config"),
+        ]);
+
+        assert_eq!(resolutions.len(), 2);
+        let co = &resolutions[0];
+        assert_eq!(co.name, "co");
+        assert_eq!(co.effective.command, "switch");
+        assert_eq!(co.effective.scope, Scope::Local);
+        let shadowed: Vec<bool> = co.definitions.iter().map(|d| d.shadowed).collect();
+        assert_eq!(shadowed, vec![true, true, false]);
+        assert!(co.explanation.contains("local config (/repo/.git/config)"));
+        assert!(co.explanation.contains("\"checkout -b\" from global config"));
+
+        let st = &resolutions[1];
+        assert!(!st.definitions[0].shadowed);
+        assert!(st.explanation.contains("only defined in global config"));
+    }
+
+    #[test]
+    fn resolve_aliases_reads_repo_scopes() {
+        let repo = temp_dir("resolve");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("config"), "[alias]\n\tgamtestco = switch\n\tgamtestco = checkout\n").unwrap();
+
+        let svc = GitService::scratch();
+        let resolved = svc.resolve_aliases(repo.to_str(), Some("gamtestco"));
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].effective.command, "checkout");
+        assert_eq!(resolved[0].definitions.len(), 2);
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn validate_alias_warns_on_builtin_name() {
+        let svc = GitService::scratch();
+        let result = svc.validate_alias("status", "status -sb", None, None);
+        assert!(result.valid);
+        assert!(result.warnings.iter().any(|w| w.contains("built-in")));
+    }
+
+    #[test]
+    fn validate_alias_reports_cycles_and_dead_references() {
+        let repo = temp_dir("graph");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(
+            repo.join(".git").join("config"),
+            "[alias]\n\tgamtestone = gamtesttwo --all\n\tgamtesttwo = log --oneline\n",
+        )
+        .unwrap();
+        let svc = GitService::scratch();
+
+        let result = svc.validate_alias("gamtesttwo", "gamtestone -5", repo.to_str(), None);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.contains("gamtesttwo -> gamtestone -> gamtesttwo")));
+
+        let result = svc.validate_alias("gamtestthree", "gamtestnope", repo.to_str(), None);
+        assert!(result.errors.iter().any(|e| e.contains("\"gamtestnope\"")));
+
+        let result = svc.validate_alias("gamtestthree", "gamtestone -5", repo.to_str(), None);
+        assert_eq!(result.expansion.as_deref(), Some("git log --oneline --all -5"));
+
+        // Commands from git's exec-path aren't dead references
+        for (name, command) in [("gamtestsm", "submodule update --init"), ("gamtestmt", "mergetool")] {
+            let result = svc.validate_alias(name, command, repo.to_str(), None);
+            assert!(result.valid, "{}: {:?}", command, result.errors);
+        }
+
+        // Renaming gamtesttwo away leaves gamtestone dangling but doesn't loop.
+        let result = svc.validate_alias("gamtestlg", "gamtestone", repo.to_str(), Some("gamtesttwo"));
+        assert!(result.valid);
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn expand_alias_uses_repo_aliases() {
+        let repo = temp_dir("expand");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("config"), "[alias]\n\tgamtestlg = log --oneline\n").unwrap();
+
+        let svc = GitService::scratch();
+        let expansion = svc.expand_alias("git gamtestlg -5 main", repo.to_str()).unwrap();
+        assert_eq!(expansion.command_line, "git log --oneline -5 main");
+        assert_eq!(expansion.chain, vec!["gamtestlg"]);
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn dry_run_plan_overrides_saved_definition() {
+        let svc = GitService::scratch();
+        let plan = svc
+            .dry_run_plan("gamtestdry", Some("reset --hard"), None, false)
+            .unwrap();
+        assert!(plan.aliases.contains(&("gamtestdry".to_string(), "reset --hard".to_string())));
+        assert!(plan.warnings.iter().any(|w| w.contains("hard reset")));
+        assert!(plan.source.is_none());
+
+        assert!(svc.dry_run_plan("gamtestmissing", None, None, false).is_err());
+        assert!(svc.dry_run_plan("gamtestloop", Some("gamtestloop"), None, false).is_err());
+    }
+
     #[test]
     fn validate_empty_command() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("");
         assert!(!result.valid);
         assert!(!result.errors.is_empty());
@@ -410,22 +1409,30 @@ mod tests {
 
     #[test]
     fn validate_whitespace_only_command() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("   ");
         assert!(!result.valid);
     }
 
     #[test]
     fn validate_safe_command() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("checkout -b");
         assert!(result.valid);
         assert!(result.warnings.is_empty());
     }
 
+    #[test]
+    fn validate_dead_reference() {
+        let svc = GitService::scratch();
+        let result = svc.validate_command("gamtestnope -5");
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.contains("neither a git command nor an alias")));
+    }
+
     #[test]
     fn validate_force_push_warning() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("push origin --force");
         assert!(result.valid);
         assert!(!result.warnings.is_empty());
@@ -434,7 +1441,7 @@ mod tests {
 
     #[test]
     fn validate_rm_rf_warning() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("!rm -rf /tmp/test");
         assert!(result.valid);
         assert!(result.warnings.iter().any(|w| w.contains("recursive delete")));
@@ -442,7 +1449,7 @@ mod tests {
 
     #[test]
     fn validate_shell_command_warning() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("!echo hello");
         assert!(result.valid);
         assert!(result.warnings.iter().any(|w| w.contains("shell command")));
@@ -450,7 +1457,7 @@ mod tests {
 
     #[test]
     fn validate_reset_hard_warning() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         let result = svc.validate_command("reset --hard HEAD~1");
         assert!(result.valid);
         assert!(result.warnings.iter().any(|w| w.contains("hard reset")));
@@ -458,22 +1465,38 @@ mod tests {
 
     #[test]
     fn new_service_has_no_local_path() {
-        let svc = GitService::new();
+        let svc = GitService::scratch();
         assert!(svc.get_local_path().is_none());
     }
 
     #[test]
     fn set_and_get_local_path() {
-        let mut svc = GitService::new();
-        svc.set_local_path(Some("/tmp/test-repo".to_string()));
+        let mut svc = GitService::scratch();
+        svc.set_local_path(Some("/tmp/test-repo".to_string())).unwrap();
         assert_eq!(svc.get_local_path(), Some("/tmp/test-repo".to_string()));
     }
 
+    #[test]
+    fn selecting_a_repo_does_not_remember_it() {
+        let repo = temp_dir("select");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("config"), "[alias]\n\tgamtestsel = status\n").unwrap();
+        let repo_str = repo.to_string_lossy().to_string();
+
+        let mut svc = GitService::scratch();
+        svc.select_local_path(Some(repo_str.clone()));
+        assert_eq!(svc.get_local_path(), Some(repo_str));
+        assert!(svc.known_repos_service.get_all().is_empty());
+        let aliases = svc.get_aliases(None).unwrap();
+        assert!(aliases.iter().any(|a| a.name == "gamtestsel" && a.scope == Scope::Local));
+        let _ = fs::remove_dir_all(&repo);
+    }
+
     #[test]
     fn clear_local_path() {
-        let mut svc = GitService::new();
-        svc.set_local_path(Some("/tmp/test-repo".to_string()));
-        svc.set_local_path(None);
+        let mut svc = GitService::scratch();
+        svc.set_local_path(Some("/tmp/test-repo".to_string())).unwrap();
+        svc.set_local_path(None).unwrap();
         assert!(svc.get_local_path().is_none());
     }
 
@@ -482,7 +1505,7 @@ mod tests {
         assert!(GitService::validate_alias_name("co").is_ok());
         assert!(GitService::validate_alias_name("checkout-main").is_ok());
         assert!(GitService::validate_alias_name("st2").is_ok());
-        assert!(GitService::validate_alias_name("my_alias").is_ok());
+        assert!(GitService::validate_alias_name("my-alias").is_ok());
     }
 
     #[test]
@@ -495,6 +1518,8 @@ mod tests {
         assert!(GitService::validate_alias_name("co;rm").is_err());
         assert!(GitService::validate_alias_name("co\ngit push").is_err());
         assert!(GitService::validate_alias_name("co && echo").is_err());
+        // git rejects `_` in config keys
+        assert!(GitService::validate_alias_name("my_alias").is_err());
     }
 
     #[test]
@@ -2,10 +2,14 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::error::GamError;
+use crate::json_store;
+
 /// A named group for organizing aliases.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AliasGroup {
     pub id: String,
     pub name: String,
@@ -44,7 +48,10 @@ impl GroupService {
             let _ = fs::create_dir_all(&config_dir);
         }
 
-        let config_path = config_dir.join("groups.json");
+        Self::open(config_dir.join("groups.json"))
+    }
+
+    pub fn open(config_path: PathBuf) -> Self {
         let mut service = Self {
             config_path,
             data: GroupData::default(),
@@ -54,16 +61,28 @@ impl GroupService {
     }
 
     fn load(&mut self) {
-        if let Ok(content) = fs::read_to_string(&self.config_path)
-            && let Ok(data) = serde_json::from_str::<GroupData>(&content) {
-                self.data = data;
-            }
+        match json_store::load::<GroupData>(&self.config_path) {
+            Ok(data) => self.data = data.unwrap_or_default(),
+            Err(e) => eprintln!("[GAM] Starting without groups: {}", e),
+        }
+    }
+
+    fn save(&self) -> Result<(), GamError> {
+        json_store::save(&self.config_path, &self.data)
     }
 
-    fn save(&self) {
-        if let Ok(json) = serde_json::to_string_pretty(&self.data) {
-            let _ = fs::write(&self.config_path, json);
+    /// Applies `change` and saves, keeping the previous data in memory if
+    /// either fails so it never drifts from what's on disk.
+    fn update<T>(
+        &mut self,
+        change: impl FnOnce(&mut GroupData) -> Result<T, GamError>,
+    ) -> Result<T, GamError> {
+        let previous = self.data.clone();
+        let result = change(&mut self.data).and_then(|value| self.save().map(|()| value));
+        if result.is_err() {
+            self.data = previous;
         }
+        result
     }
 
     fn generate_id() -> String {
@@ -99,86 +118,97 @@ impl GroupService {
 
     // ── Mutations ──
 
-    pub fn create_group(&mut self, name: &str, color: &str) -> AliasGroup {
+    pub fn create_group(&mut self, name: &str, color: &str) -> Result<AliasGroup, GamError> {
         let group = AliasGroup {
             id: Self::generate_id(),
             name: name.to_string(),
             color: color.to_string(),
         };
-        self.data.groups.push(group.clone());
-        self.save();
-        group
-    }
-
-    pub fn rename_group(&mut self, group_id: &str, new_name: &str) -> Result<(), String> {
-        let group = self
-            .data
-            .groups
-            .iter_mut()
-            .find(|g| g.id == group_id)
-            .ok_or_else(|| format!("Group not found: {}", group_id))?;
-        group.name = new_name.to_string();
-        self.save();
-        Ok(())
-    }
-
-    pub fn set_group_color(&mut self, group_id: &str, color: &str) -> Result<(), String> {
-        let group = self
-            .data
-            .groups
-            .iter_mut()
-            .find(|g| g.id == group_id)
-            .ok_or_else(|| format!("Group not found: {}", group_id))?;
-        group.color = color.to_string();
-        self.save();
-        Ok(())
-    }
-
-    pub fn delete_group(&mut self, group_id: &str) -> Result<(), String> {
-        let before = self.data.groups.len();
-        self.data.groups.retain(|g| g.id != group_id);
-        if self.data.groups.len() == before {
-            return Err(format!("Group not found: {}", group_id));
-        }
+        self.update(|data| {
+            data.groups.push(group.clone());
+            Ok(group)
+        })
+    }
 
-        // Remove this group from all assignments
-        for ids in self.data.assignments.values_mut() {
-            ids.retain(|id| id != group_id);
-        }
-        // Clean up empty assignments
-        self.data.assignments.retain(|_, ids| !ids.is_empty());
+    pub fn rename_group(&mut self, group_id: &str, new_name: &str) -> Result<(), GamError> {
+        self.update(|data| {
+            let group = data
+                .groups
+                .iter_mut()
+                .find(|g| g.id == group_id)
+                .ok_or_else(|| GamError::NotFound(format!("group {}", group_id)))?;
+            group.name = new_name.to_string();
+            Ok(())
+        })
+    }
+
+    pub fn set_group_color(&mut self, group_id: &str, color: &str) -> Result<(), GamError> {
+        self.update(|data| {
+            let group = data
+                .groups
+                .iter_mut()
+                .find(|g| g.id == group_id)
+                .ok_or_else(|| GamError::NotFound(format!("group {}", group_id)))?;
+            group.color = color.to_string();
+            Ok(())
+        })
+    }
 
-        self.save();
-        Ok(())
+    pub fn delete_group(&mut self, group_id: &str) -> Result<(), GamError> {
+        self.update(|data| {
+            let before = data.groups.len();
+            data.groups.retain(|g| g.id != group_id);
+            if data.groups.len() == before {
+                return Err(GamError::NotFound(format!("group {}", group_id)));
+            }
+
+            // Remove this group from all assignments
+            for ids in data.assignments.values_mut() {
+                ids.retain(|id| id != group_id);
+            }
+            // Clean up empty assignments
+            data.assignments.retain(|_, ids| !ids.is_empty());
+            Ok(())
+        })
     }
 
     pub fn set_alias_groups(
         &mut self,
         alias_name: &str,
         group_ids: Vec<String>,
-    ) {
-        if group_ids.is_empty() {
-            self.data.assignments.remove(alias_name);
-        } else {
-            self.data
-                .assignments
-                .insert(alias_name.to_string(), group_ids);
-        }
-        self.save();
+    ) -> Result<(), GamError> {
+        self.update(|data| {
+            if group_ids.is_empty() {
+                data.assignments.remove(alias_name);
+            } else {
+                data.assignments.insert(alias_name.to_string(), group_ids);
+            }
+            Ok(())
+        })
     }
 
-    pub fn import_data(&mut self, incoming: GroupData) {
-        // Merge groups (skip duplicates by ID)
-        for group in incoming.groups {
-            if !self.data.groups.iter().any(|g| g.id == group.id) {
-                self.data.groups.push(group);
+    /// Replaces all groups and assignments, as undo and redo do.
+    pub fn replace_data(&mut self, data: GroupData) -> Result<(), GamError> {
+        self.update(|current| {
+            *current = data;
+            Ok(())
+        })
+    }
+
+    pub fn import_data(&mut self, incoming: GroupData) -> Result<(), GamError> {
+        self.update(|data| {
+            // Merge groups (skip duplicates by ID)
+            for group in incoming.groups {
+                if !data.groups.iter().any(|g| g.id == group.id) {
+                    data.groups.push(group);
+                }
             }
-        }
-        // Merge assignments (incoming overrides)
-        for (alias, ids) in incoming.assignments {
-            self.data.assignments.insert(alias, ids);
-        }
-        self.save();
+            // Merge assignments (incoming overrides)
+            for (alias, ids) in incoming.assignments {
+                data.assignments.insert(alias, ids);
+            }
+            Ok(())
+        })
     }
 }
 
@@ -217,7 +247,7 @@ mod tests {
     #[test]
     fn create_group_returns_group() {
         let (mut svc, dir) = temp_group_service();
-        let group = svc.create_group("Deployment", "#ff6b35");
+        let group = svc.create_group("Deployment", "#ff6b35").unwrap();
         assert_eq!(group.name, "Deployment");
         assert_eq!(group.color, "#ff6b35");
         assert!(group.id.starts_with("g-"));
@@ -227,7 +257,7 @@ mod tests {
     #[test]
     fn create_group_persists() {
         let (mut svc, dir) = temp_group_service();
-        svc.create_group("Review", "#22d3ee");
+        svc.create_group("Review", "#22d3ee").unwrap();
         assert_eq!(svc.get_groups().len(), 1);
         cleanup(&dir);
     }
@@ -235,7 +265,7 @@ mod tests {
     #[test]
     fn rename_group_updates_name() {
         let (mut svc, dir) = temp_group_service();
-        let group = svc.create_group("Old", "#fff");
+        let group = svc.create_group("Old", "#fff").unwrap();
         svc.rename_group(&group.id, "New").unwrap();
         assert_eq!(svc.get_groups()[0].name, "New");
         cleanup(&dir);
@@ -252,8 +282,8 @@ mod tests {
     #[test]
     fn delete_group_removes_it() {
         let (mut svc, dir) = temp_group_service();
-        let g1 = svc.create_group("A", "#aaa");
-        let g2 = svc.create_group("B", "#bbb");
+        let g1 = svc.create_group("A", "#aaa").unwrap();
+        let g2 = svc.create_group("B", "#bbb").unwrap();
         svc.delete_group(&g1.id).unwrap();
         let groups = svc.get_groups();
         assert_eq!(groups.len(), 1);
@@ -264,8 +294,8 @@ mod tests {
     #[test]
     fn delete_group_removes_assignments() {
         let (mut svc, dir) = temp_group_service();
-        let g = svc.create_group("Deploy", "#f00");
-        svc.set_alias_groups("co", vec![g.id.clone()]);
+        let g = svc.create_group("Deploy", "#f00").unwrap();
+        svc.set_alias_groups("co", vec![g.id.clone()]).unwrap();
         svc.delete_group(&g.id).unwrap();
         assert!(svc.get_alias_groups("co").is_empty());
         cleanup(&dir);
@@ -274,9 +304,9 @@ mod tests {
     #[test]
     fn set_and_get_alias_groups() {
         let (mut svc, dir) = temp_group_service();
-        let g1 = svc.create_group("A", "#aaa");
-        let g2 = svc.create_group("B", "#bbb");
-        svc.set_alias_groups("co", vec![g1.id.clone(), g2.id.clone()]);
+        let g1 = svc.create_group("A", "#aaa").unwrap();
+        let g2 = svc.create_group("B", "#bbb").unwrap();
+        svc.set_alias_groups("co", vec![g1.id.clone(), g2.id.clone()]).unwrap();
         let groups = svc.get_alias_groups("co");
         assert_eq!(groups.len(), 2);
         assert!(groups.contains(&g1.id));
@@ -287,9 +317,9 @@ mod tests {
     #[test]
     fn set_empty_groups_removes_assignment() {
         let (mut svc, dir) = temp_group_service();
-        let g = svc.create_group("A", "#aaa");
-        svc.set_alias_groups("co", vec![g.id.clone()]);
-        svc.set_alias_groups("co", vec![]);
+        let g = svc.create_group("A", "#aaa").unwrap();
+        svc.set_alias_groups("co", vec![g.id.clone()]).unwrap();
+        svc.set_alias_groups("co", vec![]).unwrap();
         assert!(svc.get_alias_groups("co").is_empty());
         cleanup(&dir);
     }
@@ -297,9 +327,9 @@ mod tests {
     #[test]
     fn get_all_assignments_returns_map() {
         let (mut svc, dir) = temp_group_service();
-        let g = svc.create_group("A", "#aaa");
-        svc.set_alias_groups("co", vec![g.id.clone()]);
-        svc.set_alias_groups("st", vec![g.id.clone()]);
+        let g = svc.create_group("A", "#aaa").unwrap();
+        svc.set_alias_groups("co", vec![g.id.clone()]).unwrap();
+        svc.set_alias_groups("st", vec![g.id.clone()]).unwrap();
         let all = svc.get_all_assignments();
         assert_eq!(all.len(), 2);
         cleanup(&dir);
@@ -308,7 +338,7 @@ mod tests {
     #[test]
     fn import_data_merges_groups() {
         let (mut svc, dir) = temp_group_service();
-        svc.create_group("Existing", "#111");
+        svc.create_group("Existing", "#111").unwrap();
 
         let incoming = GroupData {
             groups: vec![AliasGroup {
@@ -322,7 +352,7 @@ mod tests {
                 m
             },
         };
-        svc.import_data(incoming);
+        svc.import_data(incoming).unwrap();
         assert_eq!(svc.get_groups().len(), 2);
         assert_eq!(svc.get_alias_groups("co"), vec!["g-incoming"]);
         cleanup(&dir);
@@ -331,7 +361,7 @@ mod tests {
     #[test]
     fn import_data_skips_duplicate_ids() {
         let (mut svc, dir) = temp_group_service();
-        let existing = svc.create_group("Original", "#111");
+        let existing = svc.create_group("Original", "#111").unwrap();
 
         let incoming = GroupData {
             groups: vec![AliasGroup {
@@ -341,7 +371,7 @@ mod tests {
             }],
             assignments: HashMap::new(),
         };
-        svc.import_data(incoming);
+        svc.import_data(incoming).unwrap();
         // Should not duplicate; still 1 group with original name
         assert_eq!(svc.get_groups().len(), 1);
         assert_eq!(svc.get_groups()[0].name, "Original");
@@ -351,7 +381,7 @@ mod tests {
     #[test]
     fn set_group_color_updates() {
         let (mut svc, dir) = temp_group_service();
-        let g = svc.create_group("CI", "#aaa");
+        let g = svc.create_group("CI", "#aaa").unwrap();
         svc.set_group_color(&g.id, "#00ff00").unwrap();
         assert_eq!(svc.get_groups()[0].color, "#00ff00");
         cleanup(&dir);
@@ -366,9 +396,9 @@ mod tests {
             let (mut svc, d) = temp_group_service();
             dir = d;
             config_path = svc.config_path.clone();
-            let g = svc.create_group("Persisted", "#abc");
+            let g = svc.create_group("Persisted", "#abc").unwrap();
             id = g.id;
-            svc.set_alias_groups("st", vec![id.clone()]);
+            svc.set_alias_groups("st", vec![id.clone()]).unwrap();
         }
         // Create a new service instance pointing to the same file
         let mut svc2 = GroupService {
@@ -381,4 +411,36 @@ mod tests {
         assert_eq!(svc2.get_alias_groups("st"), vec![id]);
         cleanup(&dir);
     }
+
+    #[test]
+    fn failed_save_keeps_previous_data() {
+        let (mut svc, dir) = temp_group_service();
+        let group = svc.create_group("Kept", "#abc").unwrap();
+        svc.set_alias_groups("st", vec![group.id.clone()]).unwrap();
+
+        // A directory where the file should be makes every save fail
+        let _ = fs::remove_file(&svc.config_path);
+        fs::create_dir_all(&svc.config_path).unwrap();
+
+        assert!(svc.create_group("Lost", "#def").is_err());
+        assert!(svc.rename_group(&group.id, "Renamed").is_err());
+        assert!(svc.delete_group(&group.id).is_err());
+        assert!(svc.set_alias_groups("st", Vec::new()).is_err());
+        let incoming = GroupData {
+            groups: vec![AliasGroup {
+                id: "g-new".to_string(),
+                name: "New".to_string(),
+                color: "#123".to_string(),
+            }],
+            assignments: HashMap::from([("co".to_string(), vec!["g-new".to_string()])]),
+        };
+        assert!(svc.import_data(incoming).is_err());
+
+        let groups = svc.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "Kept");
+        assert_eq!(svc.get_all_assignments().len(), 1);
+        assert_eq!(svc.get_alias_groups("st"), vec![group.id]);
+        cleanup(&dir);
+    }
 }
@@ -0,0 +1,369 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backup_service::BackupService;
+use crate::error::GamError;
+use crate::git_config::{self, FileChange};
+use crate::group_service::GroupData;
+use crate::json_store;
+
+/// Oldest entries are dropped once the journal holds this many.
+const MAX_ENTRIES: usize = 200;
+
+/// Group data before and after a change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupChange {
+    pub before: GroupData,
+    pub after: GroupData,
+}
+
+/// One undoable change: the config files and group data it touched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub description: String,
+    #[serde(default)]
+    pub files: Vec<FileChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<GroupChange>,
+}
+
+impl JournalEntry {
+    pub fn summary(&self) -> HistoryItem {
+        HistoryItem {
+            id: self.id,
+            timestamp: self.timestamp,
+            description: self.description.clone(),
+        }
+    }
+}
+
+/// An entry as listed in the history, without the file contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub id: u64,
+    pub timestamp: u64,
+    pub description: String,
+}
+
+/// What `undo` and `redo` can replay, most recent first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    pub undo: Vec<HistoryItem>,
+    pub redo: Vec<HistoryItem>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalData {
+    next_id: u64,
+    undo: Vec<JournalEntry>,
+    redo: Vec<JournalEntry>,
+}
+
+/// Journal of alias and group changes, stored in a JSON file in the app data
+/// directory so undo and redo survive restarts.
+pub struct JournalService {
+    journal_path: PathBuf,
+    data: JournalData,
+}
+
+impl Default for JournalService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JournalService {
+    pub fn new() -> Self {
+        let config_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("com.github.zintaen.gam");
+
+        if !config_dir.exists() {
+            let _ = fs::create_dir_all(&config_dir);
+        }
+
+        Self::open(config_dir.join("journal.json"))
+    }
+
+    pub fn open(journal_path: PathBuf) -> Self {
+        let mut service = Self {
+            journal_path,
+            data: JournalData::default(),
+        };
+        service.load();
+        service
+    }
+
+    fn load(&mut self) {
+        match json_store::load::<JournalData>(&self.journal_path) {
+            Ok(data) => self.data = data.unwrap_or_default(),
+            Err(e) => eprintln!("[GAM] Starting with an empty undo history: {}", e),
+        }
+    }
+
+    fn save(&self) -> Result<(), GamError> {
+        json_store::save(&self.journal_path, &self.data)
+    }
+
+    /// Records a change and clears the redo stack. Changes that touched
+    /// nothing are not recorded. The change itself has already been made, so
+    /// a failed save says it can't be undone after a restart.
+    pub fn record(
+        &mut self,
+        description: &str,
+        files: Vec<FileChange>,
+        groups: Option<GroupChange>,
+    ) -> Result<(), GamError> {
+        let groups = groups.filter(|g| !same_groups(&g.before, &g.after));
+        if files.is_empty() && groups.is_none() {
+            return Ok(());
+        }
+
+        self.data.next_id += 1;
+        self.data.undo.push(JournalEntry {
+            id: self.data.next_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            description: description.to_string(),
+            files,
+            groups,
+        });
+        if self.data.undo.len() > MAX_ENTRIES {
+            let excess = self.data.undo.len() - MAX_ENTRIES;
+            self.data.undo.drain(..excess);
+        }
+        self.data.redo.clear();
+        self.save().map_err(|e| {
+            e.context("The change was made but won't survive a restart in the undo history: ")
+        })
+    }
+
+    pub fn history(&self) -> History {
+        let items =
+            |entries: &[JournalEntry]| entries.iter().rev().map(JournalEntry::summary).collect();
+        History {
+            undo: items(&self.data.undo),
+            redo: items(&self.data.redo),
+        }
+    }
+
+    /// Reverts the latest change. The config files are restored here; the
+    /// returned entry's `groups.before` is the group data to restore.
+    ///
+    /// Fails without changing anything if the files or `groups` no longer
+    /// match what the change left behind, or the journal can't be saved.
+    pub fn undo(
+        &mut self,
+        groups: &GroupData,
+        backups: &BackupService,
+    ) -> Result<JournalEntry, GamError> {
+        let entry = self.data.undo.pop().ok_or_else(|| GamError::Invalid("Nothing to undo".to_string()))?;
+        self.data.redo.push(entry.clone());
+        let files: Vec<FileChange> = entry.files.iter().map(FileChange::reversed).collect();
+        let expected = entry.groups.as_ref().map(|g| &g.after);
+
+        // The journal is saved first, so the files never get ahead of it
+        let result = self
+            .save()
+            .and_then(|_| Self::replay(&entry.description, &files, expected, groups, backups));
+        if let Err(e) = result {
+            self.data.redo.pop();
+            self.data.undo.push(entry);
+            return Err(self.save_after_failure(e));
+        }
+        Ok(entry)
+    }
+
+    /// Re-applies the latest undone change. The config files are written
+    /// here; the returned entry's `groups.after` is the group data to restore.
+    pub fn redo(
+        &mut self,
+        groups: &GroupData,
+        backups: &BackupService,
+    ) -> Result<JournalEntry, GamError> {
+        let entry = self.data.redo.pop().ok_or_else(|| GamError::Invalid("Nothing to redo".to_string()))?;
+        self.data.undo.push(entry.clone());
+        let expected = entry.groups.as_ref().map(|g| &g.before);
+
+        let result = self.save().and_then(|_| {
+            Self::replay(&entry.description, &entry.files, expected, groups, backups)
+        });
+        if let Err(e) = result {
+            self.data.undo.pop();
+            self.data.redo.push(entry);
+            return Err(self.save_after_failure(e));
+        }
+        Ok(entry)
+    }
+
+    /// Saves the journal again once a failed undo or redo was rolled back.
+    fn save_after_failure(&self, error: GamError) -> GamError {
+        if let Err(e) = self.save() {
+            eprintln!("[GAM] Could not save the undo history: {}", e);
+        }
+        error
+    }
+
+    /// Backs up and writes `files` after checking that the group data is
+    /// still `expected`.
+    fn replay(
+        description: &str,
+        files: &[FileChange],
+        expected: Option<&GroupData>,
+        groups: &GroupData,
+        backups: &BackupService,
+    ) -> Result<(), GamError> {
+        if expected.is_some_and(|expected| !same_groups(expected, groups)) {
+            return Err(GamError::Invalid(format!(
+                "Groups were changed since \"{}\"; nothing was changed",
+                description
+            )));
+        }
+        git_config::write_files(files, |files| backups.snapshot(files))
+    }
+}
+
+/// Compares group data, ignoring the order of group lists in assignments.
+fn same_groups(a: &GroupData, b: &GroupData) -> bool {
+    let sorted = |data: &GroupData| {
+        let mut assignments: Vec<(String, Vec<String>)> = data
+            .assignments
+            .iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(name, ids)| {
+                let mut ids = ids.clone();
+                ids.sort();
+                (name.clone(), ids)
+            })
+            .collect();
+        assignments.sort();
+        assignments
+    };
+    let groups = |data: &GroupData| {
+        data.groups
+            .iter()
+            .map(|g| (g.id.clone(), g.name.clone(), g.color.clone()))
+            .collect::<Vec<_>>()
+    };
+    groups(a) == groups(b) && sorted(a) == sorted(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_service::AliasGroup;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("gam_test_journal_{}_{}", std::process::id(), id));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn journal(dir: &std::path::Path) -> JournalService {
+        let mut service = JournalService {
+            journal_path: dir.join("journal.json"),
+            data: JournalData::default(),
+        };
+        service.load();
+        service
+    }
+
+    #[test]
+    fn undo_and_redo_survive_a_restart() {
+        let dir = temp_dir();
+        let config = dir.join("config");
+        fs::write(&config, "[alias]\n\tco = checkout\n").unwrap();
+        let mut service = journal(&dir);
+        let backups = BackupService::with_dir(dir.join("backups"), Default::default());
+
+        fs::write(&config, "[alias]\n\tco = checkout\n\tst = status\n").unwrap();
+        service
+            .record(
+                "Add alias \"st\"",
+                vec![FileChange {
+                    path: config.clone(),
+                    before: Some("[alias]\n\tco = checkout\n".to_string()),
+                    after: Some("[alias]\n\tco = checkout\n\tst = status\n".to_string()),
+                }],
+                None,
+            )
+            .unwrap();
+        service.record("Nothing", Vec::new(), None).unwrap();
+        assert_eq!(service.history().undo.len(), 1);
+
+        let mut service = journal(&dir);
+        service.undo(&GroupData::default(), &backups).unwrap();
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "[alias]\n\tco = checkout\n"
+        );
+        assert!(service.undo(&GroupData::default(), &backups).is_err());
+
+        let mut service = journal(&dir);
+        assert_eq!(service.history().redo[0].description, "Add alias \"st\"");
+        service.redo(&GroupData::default(), &backups).unwrap();
+        assert!(fs::read_to_string(&config).unwrap().contains("st = status"));
+
+        // A file edited behind GAM's back is left alone
+        fs::write(&config, "[alias]\n\tlg = log\n").unwrap();
+        assert!(
+            service
+                .undo(&GroupData::default(), &backups)
+                .unwrap_err()
+                .to_string()
+                .contains("modified")
+        );
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "[alias]\n\tlg = log\n"
+        );
+        assert_eq!(service.history().undo.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn group_changes_are_checked_before_undo() {
+        let dir = temp_dir();
+        let mut service = journal(&dir);
+        let backups = BackupService::with_dir(dir.join("backups"), Default::default());
+        let before = GroupData::default();
+        let mut after = GroupData::default();
+        after.groups.push(AliasGroup {
+            id: "g-1".to_string(),
+            name: "Work".to_string(),
+            color: "#fff".to_string(),
+        });
+        service
+            .record(
+                "Create group \"Work\"",
+                Vec::new(),
+                Some(GroupChange {
+                    before: before.clone(),
+                    after: after.clone(),
+                }),
+            )
+            .unwrap();
+
+        assert!(service.undo(&before, &backups).is_err());
+        let entry = service.undo(&after, &backups).unwrap();
+        assert!(entry.groups.unwrap().before.groups.is_empty());
+        assert!(service.history().undo.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
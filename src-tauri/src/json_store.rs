@@ -0,0 +1,132 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::error::GamError;
+
+/// `<path>` with `suffix` appended to the file name (`groups.json.bak`).
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Reads a JSON store written by `save`. A missing file yields `None`. When
+/// the file is unreadable or corrupt, the `.bak` copy of the previous save is
+/// used instead; only if that fails too is an error returned.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, GamError> {
+    let parse = |path: &Path| -> Result<T, GamError> {
+        let content = fs::read_to_string(path).map_err(|e| GamError::file("read", path, e))?;
+        serde_json::from_str(&content).map_err(|e| GamError::File {
+            path: path.to_path_buf(),
+            message: format!("{} is corrupt: {}", path.display(), e),
+        })
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+    match parse(path) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            let backup = sibling(path, ".bak");
+            parse(&backup).map(Some).map_err(|_| GamError::File {
+                path: path.to_path_buf(),
+                message: format!("{}, and there is no usable backup", e),
+            })
+        }
+    }
+}
+
+/// Writes `value` as pretty JSON without ever leaving a half-written file:
+/// the JSON goes to `<path>.tmp`, is fsynced and renamed over `path`. The
+/// previous contents are kept as `<path>.bak` for `load` to fall back on.
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), GamError> {
+    let failed = |e: std::io::Error| GamError::file("save", path, e);
+    let json = serde_json::to_string_pretty(value)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(failed)?;
+    }
+
+    let tmp = sibling(path, ".tmp");
+    let written = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(json.as_bytes())?;
+            file.sync_all()
+        })
+        .map_err(failed);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    // Only a file that still parses is worth keeping as the backup
+    let previous_is_valid = fs::read_to_string(path)
+        .is_ok_and(|content| serde_json::from_str::<serde_json::Value>(&content).is_ok());
+    if previous_is_valid {
+        fs::copy(path, sibling(path, ".bak")).map_err(failed)?;
+    }
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        failed(e)
+    })?;
+
+    // Persist the rename itself; directories can't be opened on Windows
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let _ = fs::File::open(dir).and_then(|d| d.sync_all());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("gam_test_store_{}_{}", std::process::id(), id));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn save_keeps_a_backup_that_load_falls_back_to() {
+        let dir = temp_dir();
+        let path = dir.join("store.json");
+        assert_eq!(load::<Vec<String>>(&path).unwrap(), None);
+
+        save(&path, &vec!["one"]).unwrap();
+        save(&path, &vec!["one", "two"]).unwrap();
+        assert!(!sibling(&path, ".tmp").exists());
+        assert_eq!(load::<Vec<String>>(&path).unwrap().unwrap().len(), 2);
+
+        // A torn write falls back to the previous save
+        fs::write(&path, "[\"one\", \"tw").unwrap();
+        assert_eq!(
+            load::<Vec<String>>(&path).unwrap(),
+            Some(vec!["one".to_string()])
+        );
+
+        // Saving over the corrupt file doesn't replace the good backup
+        save(&path, &vec!["three"]).unwrap();
+        fs::write(&path, "{").unwrap();
+        assert_eq!(
+            load::<Vec<String>>(&path).unwrap(),
+            Some(vec!["one".to_string()])
+        );
+
+        fs::write(sibling(&path, ".bak"), "").unwrap();
+        let err = load::<HashMap<String, String>>(&path).unwrap_err().to_string();
+        assert!(err.contains("corrupt") && err.contains("no usable backup"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
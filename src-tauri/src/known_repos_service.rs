@@ -2,6 +2,9 @@ use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::error::GamError;
+use crate::json_store;
+
 /// Persists known repo paths to a JSON file in the app data directory.
 pub struct KnownReposService {
     config_path: PathBuf,
@@ -24,7 +27,10 @@ impl KnownReposService {
             let _ = fs::create_dir_all(&config_dir);
         }
 
-        let config_path = config_dir.join("known-repos.json");
+        Self::open(config_dir.join("known-repos.json"))
+    }
+
+    pub fn open(config_path: PathBuf) -> Self {
         let mut service = Self {
             config_path,
             known_paths: HashSet::new(),
@@ -34,27 +40,29 @@ impl KnownReposService {
     }
 
     fn load(&mut self) {
-        if let Ok(data) = fs::read_to_string(&self.config_path)
-            && let Ok(paths) = serde_json::from_str::<Vec<String>>(&data) {
-                // Filter out paths that no longer exist
+        match json_store::load::<Vec<String>>(&self.config_path) {
+            // Filter out paths that no longer exist
+            Ok(paths) => {
                 self.known_paths = paths
+                    .unwrap_or_default()
                     .into_iter()
                     .filter(|p| Path::new(p).exists())
                     .collect();
             }
+            Err(e) => eprintln!("[GAM] Starting without known repositories: {}", e),
+        }
     }
 
-    fn save(&self) {
+    fn save(&self) -> Result<(), GamError> {
         let paths: Vec<&String> = self.known_paths.iter().collect();
-        if let Ok(json) = serde_json::to_string_pretty(&paths) {
-            let _ = fs::write(&self.config_path, json);
-        }
+        json_store::save(&self.config_path, &paths)
     }
 
-    pub fn add(&mut self, repo_path: &str) {
+    pub fn add(&mut self, repo_path: &str) -> Result<(), GamError> {
         if self.known_paths.insert(repo_path.to_string()) {
-            self.save();
+            self.save()?;
         }
+        Ok(())
     }
 
     pub fn get_all(&self) -> Vec<String> {
@@ -62,10 +70,11 @@ impl KnownReposService {
     }
 
     #[allow(dead_code)]
-    pub fn remove(&mut self, repo_path: &str) {
+    pub fn remove(&mut self, repo_path: &str) -> Result<(), GamError> {
         if self.known_paths.remove(repo_path) {
-            self.save();
+            self.save()?;
         }
+        Ok(())
     }
 }
 
@@ -105,7 +114,7 @@ mod tests {
     #[test]
     fn add_stores_path() {
         let (mut svc, dir) = temp_service();
-        svc.add("/tmp/repo1");
+        svc.add("/tmp/repo1").unwrap();
         assert!(svc.get_all().contains(&"/tmp/repo1".to_string()));
         cleanup(&dir);
     }
@@ -113,8 +122,8 @@ mod tests {
     #[test]
     fn add_deduplicates() {
         let (mut svc, dir) = temp_service();
-        svc.add("/tmp/repo1");
-        svc.add("/tmp/repo1");
+        svc.add("/tmp/repo1").unwrap();
+        svc.add("/tmp/repo1").unwrap();
         let all = svc.get_all();
         assert_eq!(all.iter().filter(|p| *p == "/tmp/repo1").count(), 1);
         cleanup(&dir);
@@ -123,9 +132,9 @@ mod tests {
     #[test]
     fn remove_deletes_path() {
         let (mut svc, dir) = temp_service();
-        svc.add("/tmp/repo1");
-        svc.add("/tmp/repo2");
-        svc.remove("/tmp/repo1");
+        svc.add("/tmp/repo1").unwrap();
+        svc.add("/tmp/repo2").unwrap();
+        svc.remove("/tmp/repo1").unwrap();
         let all = svc.get_all();
         assert!(!all.contains(&"/tmp/repo1".to_string()));
         assert!(all.contains(&"/tmp/repo2".to_string()));
@@ -135,7 +144,7 @@ mod tests {
     #[test]
     fn remove_nonexistent_is_noop() {
         let (mut svc, dir) = temp_service();
-        svc.remove("/tmp/nonexistent");
+        svc.remove("/tmp/nonexistent").unwrap();
         assert!(svc.get_all().is_empty());
         cleanup(&dir);
     }
@@ -143,7 +152,7 @@ mod tests {
     #[test]
     fn save_creates_json_file() {
         let (mut svc, dir) = temp_service();
-        svc.add("/tmp/repo1");
+        svc.add("/tmp/repo1").unwrap();
         assert!(svc.config_path.exists(), "Config file was not created at {:?}", svc.config_path);
         cleanup(&dir);
     }
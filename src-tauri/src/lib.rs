@@ -1,31 +1,53 @@
 use std::sync::RwLock;
 
+mod alias_diff;
+mod alias_graph;
+mod backup_service;
+mod cli;
 mod commands;
 mod error;
+mod export_schema;
 mod file_service;
+mod git_config;
+mod git_environment;
 mod git_service;
 mod group_service;
+mod journal_service;
+mod json_store;
 mod known_repos_service;
 mod ranking_service;
+mod sandbox_service;
 mod settings_service;
+mod shell_command;
+mod usage_store;
 
 pub use commands::*;
 pub use git_service::GitService;
 pub use group_service::GroupService;
+pub use journal_service::JournalService;
 pub use known_repos_service::KnownReposService;
 pub use ranking_service::RankingService;
 pub use settings_service::SettingsService;
 
+use git_environment::GitEnvironment;
+
 /// Shared application state managed by Tauri.
 pub struct AppState {
     pub git_service: RwLock<GitService>,
     pub settings_service: RwLock<SettingsService>,
     pub group_service: RwLock<GroupService>,
+    pub journal_service: RwLock<JournalService>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let start = std::time::Instant::now();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `gam list`, `gam add`, ... run headless and never open a window.
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
 
     // Log panics to crash.log in the app data directory
     let crash_log_path = dirs::data_dir()
@@ -49,7 +71,6 @@ pub fn run() {
 
     // Evaluate CLI argument for folder
     let local_path: Option<String> = {
-        let args: Vec<String> = std::env::args().skip(1).collect();
         args.iter()
             .find(|a| !a.starts_with('-') && !a.ends_with(".js") && !a.ends_with(".ts") && *a != ".")
             .map(|a| {
@@ -65,9 +86,13 @@ pub fn run() {
             })
     };
 
-    let mut git_service = GitService::new();
-    if let Some(ref path) = local_path {
-        git_service.set_local_path(Some(path.clone()));
+    let settings_service = SettingsService::new();
+    GitEnvironment::configure(&settings_service);
+    let mut git_service = GitService::new(&settings_service);
+    if let Some(ref path) = local_path
+        && let Err(e) = git_service.set_local_path(Some(path.clone()))
+    {
+        eprintln!("[GAM] {}", e);
     }
 
     tauri::Builder::default()
@@ -86,17 +111,42 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(AppState {
             git_service: RwLock::new(git_service),
-            settings_service: RwLock::new(SettingsService::new()),
+            settings_service: RwLock::new(settings_service),
             group_service: RwLock::new(GroupService::new()),
+            journal_service: RwLock::new(JournalService::new()),
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_aliases,
             commands::add_alias,
             commands::update_alias,
             commands::delete_alias,
+            commands::resolve_aliases,
+            commands::expand_alias,
+            commands::dry_run_alias,
             commands::validate_command,
+            commands::validate_alias,
             commands::export_aliases,
             commands::import_aliases,
+            commands::preview_import,
+            commands::import_shell_aliases,
+            commands::apply_import,
+            commands::apply_batch,
+            commands::diff_aliases,
+            commands::merge_aliases,
+            commands::undo,
+            commands::redo,
+            commands::get_history,
+            commands::list_backups,
+            commands::diff_backup,
+            commands::restore_backup,
+            commands::get_backup_retention,
+            commands::set_backup_retention,
+            commands::get_alias_scores,
+            commands::set_ranking_half_life,
+            commands::suggest_aliases,
+            commands::get_repo_stats,
+            commands::get_git_environment,
+            commands::set_git_path,
             commands::select_folder,
             commands::get_local_path,
             commands::set_local_path,
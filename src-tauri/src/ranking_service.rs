@@ -1,170 +1,483 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::git_service::GitAlias;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
 
-/// Time-based recency multipliers matching the README algorithm.
-const MULTIPLIER_1H: f64 = 4.0;
-const MULTIPLIER_1D: f64 = 2.0;
-const MULTIPLIER_1W: f64 = 0.5;
-const MULTIPLIER_OLD: f64 = 0.25;
-/// If the total score across all aliases exceeds this threshold,
-/// all frequencies are halved to prevent score inflation.
-const SCORE_CEILING: f64 = 70_000.0;
+use crate::alias_graph::{self, shell_quote, split_cmdline};
+use crate::error::GamError;
+use crate::git_service::{GitAlias, GitService};
+use crate::settings_service::SettingsService;
+use crate::shell_command::{self, GitInvocation};
+use crate::usage_store::{HistoryEntry, LineCount, Usage, UsageStore};
 
-/// Precompiled regex patterns for shell history parsing.
-fn re_zsh_timestamped() -> &'static regex_lite::Regex {
+/// Settings key holding the ranking half-life in days.
+pub const HALF_LIFE_SETTING: &str = "ranking_half_life_days";
+const DEFAULT_HALF_LIFE_DAYS: f64 = 7.0;
+/// Weight of a run whose time the history doesn't record: two half-lives old.
+const UNDATED_WEIGHT: f64 = 0.25;
+
+/// Bumped whenever history is counted differently, so the usage store reads
+/// the history files again from the start.
+const PARSER_VERSION: u32 = 4;
+
+/// Times a command line must have been typed before it's suggested as an alias.
+const MIN_SUGGESTION_RUNS: u64 = 3;
+/// Shorter command lines (after `git `) aren't worth an alias.
+const MIN_SUGGESTED_COMMAND: usize = 12;
+/// Longer command lines aren't counted: they're one-offs, not habits.
+const MAX_SUGGESTED_COMMAND: usize = 200;
+
+/// zsh `EXTENDED_HISTORY` lines: ": 1234567890:0;git checkout main"
+fn re_zsh_extended() -> &'static regex_lite::Regex {
     static RE: OnceLock<regex_lite::Regex> = OnceLock::new();
-    RE.get_or_init(|| regex_lite::Regex::new(r":\s*(\d+):\d+;.*?git\s+([\w-]+)").unwrap())
+    RE.get_or_init(|| regex_lite::Regex::new(r"^:\s*(\d+):\d+;(.*)$").unwrap())
 }
-fn re_zsh_plain() -> &'static regex_lite::Regex {
-    static RE: OnceLock<regex_lite::Regex> = OnceLock::new();
-    RE.get_or_init(|| regex_lite::Regex::new(r"(?m)^git\s+([\w-]+)").unwrap())
+
+/// A shell alias (`alias gco='git checkout'`) or fish abbreviation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellAlias {
+    pub name: String,
+    pub expansion: String,
+    /// File the definition was read from.
+    pub source: PathBuf,
 }
-fn re_git_cmd() -> &'static regex_lite::Regex {
-    static RE: OnceLock<regex_lite::Regex> = OnceLock::new();
-    RE.get_or_init(|| regex_lite::Regex::new(r"git\s+([\w-]+)").unwrap())
+
+/// How an alias's score is made up: `frequency × recency × length_factor`,
+/// times `repo_share` for a local alias.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    pub name: String,
+    /// The repository of a local alias.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    pub score: f64,
+    /// Times the alias was run.
+    pub frequency: f64,
+    /// Average weight of those runs: 1 for one just now, halving every half-life.
+    pub recency: f64,
+    /// Command length ^ (3/5), favouring aliases that save more typing.
+    pub length_factor: f64,
+    /// Unix seconds of the latest run; 0 when unknown.
+    pub last_seen: u64,
+    /// For a local alias, the share of its runs in a known directory that
+    /// were in its repository. `None` when no run's directory is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_share: Option<f64>,
+}
+
+/// Every alias's score breakdown, highest score first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasScores {
+    pub half_life_days: f64,
+    pub aliases: Vec<ScoreBreakdown>,
+}
+
+/// A git command line typed often enough to deserve an alias.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AliasSuggestion {
+    /// Free among existing aliases, built-ins and the other suggestions.
+    pub name: String,
+    /// The alias value: the command line after `git`.
+    pub command: String,
+    /// Times the command line was typed.
+    pub runs: u64,
+    /// Unix seconds of the latest run; 0 when unknown.
+    pub last_seen: u64,
+    /// Characters the alias would have saved over those runs.
+    pub keystrokes_saved: u64,
+}
+
+/// How often an alias ran in one repository.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AliasUsage {
+    pub name: String,
+    pub runs: u64,
+    /// Unix seconds of the latest run; 0 when unknown.
+    pub last_seen: u64,
+}
+
+/// Git usage in one known repository.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepoUsage {
+    pub repo: String,
+    /// Git commands run there, through an alias or not.
+    pub runs: u64,
+    /// The aliases available there, most used first.
+    pub top_aliases: Vec<AliasUsage>,
 }
 
-/// Entry tracking both total frequency and most-recent timestamp.
-struct HistoryEntry {
-    frequency: f64,
-    last_seen: u64, // Unix seconds
+/// The shell history formats GAM can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HistoryFormat {
+    Zsh,
+    Bash,
+    Fish,
+    Plain,
 }
 
 /// Ranks aliases by parsing shell history files.
 ///
 /// Scoring formula:
-///   Score = TimeMultiplier × Length^(3/5) × Frequency
+///   Score = Σ 0.5^(age / half-life) × Length^(3/5)
 ///
 /// Where:
-/// - TimeMultiplier is based on the recency of last usage
+/// - the sum runs over every time the alias appears in history, so each run
+///   counts 1 when it's new and half as much every half-life after
+/// - runs without a timestamp count `UNDATED_WEIGHT`
 /// - Length is the character length of the aliased command
-/// - Frequency is how often the command appears in history
+///
+/// Runs and their times persist in the usage store, which only reads lines
+/// added to the history since the last refresh.
+///
+/// Where each command ran is tracked from `cd` and `git -C` in the history,
+/// fish's `paths` and atuin's database, so a local alias can be ranked by its
+/// runs in its own repository.
 pub struct RankingService {
-    history_cache: HashMap<String, HistoryEntry>,
+    usage: UsageStore,
+    half_life_days: f64,
     last_fetch_time: Option<Instant>,
     cache_ttl: Duration,
 }
 
-impl Default for RankingService {
-    fn default() -> Self {
-        Self::new()
+impl RankingService {
+    /// Keeps usage in the app data directory and decays it by the half-life
+    /// saved in `settings`.
+    pub fn new(settings: &SettingsService) -> Self {
+        let half_life_days = settings
+            .get(HALF_LIFE_SETTING)
+            .and_then(|v| v.parse().ok())
+            .filter(|days: &f64| days.is_finite() && *days > 0.0)
+            .unwrap_or(DEFAULT_HALF_LIFE_DAYS);
+        Self::with_store(UsageStore::new(PARSER_VERSION), half_life_days)
     }
-}
 
-impl RankingService {
-    pub fn new() -> Self {
+    /// Counts usage in memory only, with the default half-life.
+    pub fn in_memory() -> Self {
+        Self::with_store(UsageStore::in_memory(PARSER_VERSION), DEFAULT_HALF_LIFE_DAYS)
+    }
+
+    pub fn with_store(usage: UsageStore, half_life_days: f64) -> Self {
         Self {
-            history_cache: HashMap::new(),
+            usage,
+            half_life_days,
             last_fetch_time: None,
             cache_ttl: Duration::from_secs(5),
         }
     }
 
-    pub fn get_scores(
-        &mut self,
-        aliases: &[GitAlias],
-    ) -> Result<HashMap<String, f64>, String> {
-        let should_refresh = match self.last_fetch_time {
-            Some(t) => t.elapsed() > self.cache_ttl,
-            None => true,
-        };
+    pub fn half_life_days(&self) -> f64 {
+        self.half_life_days
+    }
 
-        if should_refresh || self.history_cache.is_empty() {
-            self.refresh_history_cache();
-            self.last_fetch_time = Some(Instant::now());
-        }
+    pub fn set_half_life_days(&mut self, days: f64) {
+        self.half_life_days = days;
+    }
+
+    /// The score of each of `aliases`, in the same order.
+    pub fn get_scores(&mut self, aliases: &[GitAlias]) -> Result<Vec<ScoreBreakdown>, GamError> {
+        self.refresh_if_stale();
 
         let now_secs = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
-        let mut scores: HashMap<String, f64> = HashMap::new();
-        let mut total_score: f64 = 0.0;
+        let mut repos: Vec<String> = aliases
+            .iter()
+            .filter(|a| a.scope.is_repo_scoped())
+            .filter_map(|a| a.local_path.clone())
+            .collect();
+        repos.sort();
+        repos.dedup();
+        let by_repo = self.runs_by_repo(&repos);
+
+        let mut scores: Vec<ScoreBreakdown> = Vec::with_capacity(aliases.len());
 
         for alias in aliases {
-            let entry = self.history_cache.get(&format!("git {}", alias.name));
+            // Only runs of the alias itself count, not of the command it wraps
+            let entry = self.usage.get(&format!("git {}", alias.name));
+            let length_factor = (alias.command.len() as f64).powf(0.6);
+            let (frequency, recency, last_seen) = match entry {
+                Some(e) if e.frequency > 0.0 => (
+                    e.frequency,
+                    self.decayed_runs(e, now_secs) / e.frequency,
+                    e.last_seen,
+                ),
+                _ => (0.0, 0.0, 0),
+            };
+            // A local alias ranks by how much of its use was in its own repo
+            let repo = alias.local_path.clone().filter(|_| alias.scope.is_repo_scoped());
+            let repo_share = repo.as_deref().and_then(|repo| {
+                let located = self.located_runs(&alias.name);
+                let here = by_repo
+                    .get(repo)
+                    .and_then(|counts| counts.get(&alias.name))
+                    .map_or(0, |count| count.runs);
+                (located > 0).then(|| here as f64 / located as f64)
+            });
 
-            let (frequency, last_seen) = match entry {
-                Some(e) => (e.frequency, e.last_seen),
-                None => {
-                    // Fall back to base command matching
-                    let base_cmd = alias.command.split_whitespace().next().unwrap_or("");
-                    let base_entry = self.history_cache.get(&format!("git {}", base_cmd));
-                    match base_entry {
-                        Some(e) => (e.frequency * 0.2, e.last_seen),
-                        None => {
-                            scores.insert(alias.name.clone(), 0.0);
-                            continue;
-                        }
-                    }
+            scores.push(ScoreBreakdown {
+                name: alias.name.clone(),
+                repo,
+                score: frequency * recency * length_factor * repo_share.unwrap_or(1.0),
+                frequency,
+                recency,
+                length_factor,
+                last_seen,
+                repo_share,
+            });
+        }
+
+        Ok(scores)
+    }
+
+    /// How often each of `repos` ran git and its available aliases, busiest
+    /// repository first, with at most `limit` aliases each.
+    pub fn repo_stats(
+        &mut self,
+        repos: &[String],
+        aliases: &[GitAlias],
+        limit: usize,
+    ) -> Vec<RepoUsage> {
+        self.refresh_if_stale();
+        let by_repo = self.runs_by_repo(repos);
+
+        let mut stats: Vec<RepoUsage> = repos
+            .iter()
+            .map(|repo| {
+                let counts = by_repo.get(repo.as_str());
+                let mut top_aliases: Vec<AliasUsage> = aliases
+                    .iter()
+                    .filter(|a| !a.scope.is_repo_scoped() || a.local_path.as_ref() == Some(repo))
+                    .filter_map(|a| {
+                        let count = counts?.get(&a.name)?;
+                        Some(AliasUsage {
+                            name: a.name.clone(),
+                            runs: count.runs,
+                            last_seen: count.last_seen,
+                        })
+                    })
+                    .collect();
+                top_aliases.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.name.cmp(&b.name)));
+                // The same name may be defined in more than one scope
+                top_aliases.dedup_by(|a, b| a.name == b.name);
+                top_aliases.truncate(limit);
+                RepoUsage {
+                    repo: repo.clone(),
+                    runs: counts.map_or(0, |c| c.values().map(|count| count.runs).sum()),
+                    top_aliases,
                 }
+            })
+            .collect();
+        stats.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.repo.cmp(&b.repo)));
+        stats
+    }
+
+    /// Runs per name in each of `repos`. A directory counts for the innermost
+    /// repository containing it.
+    fn runs_by_repo<'a>(&self, repos: &'a [String]) -> HashMap<&'a str, HashMap<String, LineCount>> {
+        let mut by_repo: HashMap<&str, HashMap<String, LineCount>> = HashMap::new();
+        for (dir, names) in &self.usage.usage().directories {
+            let dir = Path::new(dir);
+            let Some(repo) = repos
+                .iter()
+                .filter(|repo| dir.starts_with(repo))
+                .max_by_key(|repo| repo.len())
+            else {
+                continue;
             };
+            let counts = by_repo.entry(repo.as_str()).or_default();
+            for (name, count) in names {
+                let total = counts.entry(name.clone()).or_default();
+                total.runs += count.runs;
+                total.last_seen = total.last_seen.max(count.last_seen);
+            }
+        }
+        by_repo
+    }
 
-            // Time multiplier based on recency
-            let elapsed = if last_seen > 0 { now_secs.saturating_sub(last_seen) } else { u64::MAX };
-            let time_multiplier = if elapsed <= 3600 {
-                MULTIPLIER_1H
-            } else if elapsed <= 86400 {
-                MULTIPLIER_1D
-            } else if elapsed <= 604800 {
-                MULTIPLIER_1W
-            } else {
-                MULTIPLIER_OLD
+    /// Runs of `name` whose directory is known, wherever that was.
+    fn located_runs(&self, name: &str) -> u64 {
+        self.usage
+            .usage()
+            .directories
+            .values()
+            .filter_map(|names| names.get(name))
+            .map(|count| count.runs)
+            .sum()
+    }
+
+    /// Proposes aliases for the git command lines typed most often that no
+    /// alias in `aliases` covers yet, by keystrokes saved, at most `limit`.
+    pub fn suggest_aliases(&mut self, aliases: &[GitAlias], limit: usize) -> Vec<AliasSuggestion> {
+        self.refresh_if_stale();
+        Self::suggestions(&self.usage.usage().command_lines, aliases, limit)
+    }
+
+    fn suggestions(
+        lines: &HashMap<String, LineCount>,
+        aliases: &[GitAlias],
+        limit: usize,
+    ) -> Vec<AliasSuggestion> {
+        let aliased: Vec<String> = aliases
+            .iter()
+            .filter_map(|a| split_cmdline(&a.command).ok())
+            .map(|words| alias_value(&words))
+            .collect();
+        let is_alias = |name: &str| aliases.iter().any(|a| a.name.eq_ignore_ascii_case(name));
+
+        let mut candidates: Vec<(&String, &LineCount, Vec<String>)> = lines
+            .iter()
+            .filter(|(command, count)| {
+                count.runs >= MIN_SUGGESTION_RUNS
+                    && command.len() >= MIN_SUGGESTED_COMMAND
+                    && !aliased.contains(command)
+            })
+            .filter_map(|(command, count)| Some((command, count, split_cmdline(command).ok()?)))
+            // Runs through an alias or of a mistyped command don't need one
+            .filter(|(_, _, words)| {
+                let first = words[0].as_str();
+                !is_alias(first)
+                    && (alias_graph::is_builtin(first)
+                        || alias_graph::find_external_command(first).is_some())
+            })
+            .collect();
+        // The most used commands get the first pick of names
+        candidates.sort_by(|a, b| {
+            let weight = |(command, count, _): &(&String, &LineCount, _)| {
+                count.runs * command.len() as u64
             };
+            weight(b).cmp(&weight(a)).then_with(|| a.0.cmp(b.0))
+        });
 
-            // Length factor: command length ^ (3/5)
-            let length_factor = (alias.command.len() as f64).powf(0.6);
+        let mut out: Vec<AliasSuggestion> = Vec::new();
+        for (command, count, words) in candidates {
+            let taken = |name: &str| {
+                alias_graph::is_builtin(name)
+                    || GitService::validate_alias_name(name).is_err()
+                    || is_alias(name)
+                    || out.iter().any(|s| s.name.eq_ignore_ascii_case(name))
+            };
+            let Some(name) = suggestion_names(&words)
+                .into_iter()
+                .find(|name| name.len() < command.len() && !taken(name))
+            else {
+                continue;
+            };
+            out.push(AliasSuggestion {
+                keystrokes_saved: count.runs * (command.len() - name.len()) as u64,
+                name,
+                command: command.clone(),
+                runs: count.runs,
+                last_seen: count.last_seen,
+            });
+        }
+        out.sort_by(|a, b| {
+            b.keystrokes_saved
+                .cmp(&a.keystrokes_saved)
+                .then_with(|| a.command.cmp(&b.command))
+        });
+        out.truncate(limit);
+        out
+    }
+
+    /// The runs of `entry`, each weighted by how many half-lives ago it was.
+    fn decayed_runs(&self, entry: &HistoryEntry, now_secs: u64) -> f64 {
+        let half_life_secs = self.half_life_days * 86_400.0;
+        let weight = |at: u64| 0.5f64.powf(now_secs.saturating_sub(at) as f64 / half_life_secs);
+        let timed: f64 = entry.timestamps.iter().map(|t| weight(*t)).sum();
+        timed + entry.folded * weight(entry.folded_at) + entry.undated() * UNDATED_WEIGHT
+    }
+
+    fn refresh_if_stale(&mut self) {
+        let should_refresh = match self.last_fetch_time {
+            Some(t) => t.elapsed() > self.cache_ttl,
+            None => true,
+        };
 
-            let score = (time_multiplier * length_factor * frequency).round();
-            total_score += score;
-            scores.insert(alias.name.clone(), score);
+        if should_refresh {
+            self.refresh_usage();
+            self.last_fetch_time = Some(Instant::now());
+        }
+    }
+
+    /// Counts the history lines added since the last refresh.
+    fn refresh_usage(&mut self) {
+        for (path, format) in Self::history_files() {
+            self.usage.ingest(&path, |content, cache| match format {
+                HistoryFormat::Zsh => Self::parse_zsh_history(content, cache),
+                HistoryFormat::Bash => Self::parse_bash_history(content, cache),
+                HistoryFormat::Fish => Self::parse_fish_history(content, cache),
+                HistoryFormat::Plain => Self::parse_plain_history(content, cache),
+            });
+        }
+        if let Some(db) = atuin_database().filter(|db| db.is_file()) {
+            self.usage.ingest_since(&db, |after, usage| {
+                Self::read_atuin(&db, after, usage)
+                    .map_err(|e| eprintln!("[GAM] Failed to read atuin history: {}", e))
+                    .ok()
+            });
+        }
+        if let Err(e) = self.usage.save() {
+            eprintln!("[GAM] Failed to save alias usage: {}", e);
         }
+    }
+
+    /// Places the git commands atuin recorded after `after` (nanoseconds) in
+    /// the directories they were typed in, and returns the newest timestamp.
+    /// Only runs the command line doesn't place itself are taken: the runs are
+    /// counted from the shell's history file, which atuin doesn't replace.
+    fn read_atuin(db: &Path, after: i64, usage: &mut Usage) -> rusqlite::Result<i64> {
+        let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = conn.prepare(
+            "SELECT timestamp, cwd, command FROM history WHERE timestamp > ?1 ORDER BY timestamp",
+        )?;
+        let mut rows = statement.query([after])?;
+        let mut newest = after;
+        while let Some(row) = rows.next()? {
+            let timestamp: i64 = row.get(0)?;
+            let cwd: String = row.get(1)?;
+            let command: String = row.get(2)?;
+            newest = newest.max(timestamp);
 
-        // Auto-reset: halve all frequencies when total exceeds ceiling
-        if total_score > SCORE_CEILING {
-            for entry in self.history_cache.values_mut() {
-                entry.frequency /= 2.0;
+            let start = Path::new(&cwd);
+            if !start.is_absolute() {
+                continue;
             }
-            // Recalculate scores with halved frequencies
-            for (_, score) in scores.iter_mut() {
-                *score /= 2.0;
+            let seconds = (timestamp / 1_000_000_000).max(0) as u64;
+            let own = Self::git_runs(&command, None, &[]);
+            let placed = Self::git_runs(&command, Some(start), &[]);
+            for ((_, own_dir), (git, dir)) in own.iter().zip(placed) {
+                if let (None, Some(dir)) = (own_dir, dir) {
+                    let names = usage.directories.entry(dir.to_string_lossy().to_string());
+                    names.or_default().entry(git.args[0].clone()).or_default().record(seconds);
+                }
             }
         }
-
-        Ok(scores)
+        Ok(newest)
     }
 
-    fn refresh_history_cache(&mut self) {
-        let mut new_cache: HashMap<String, HistoryEntry> = HashMap::new();
+    /// The shell history files of this platform.
+    fn history_files() -> Vec<(PathBuf, HistoryFormat)> {
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
 
         if cfg!(not(target_os = "windows")) {
-            // Try zsh history (format: ": timestamp:0;command")
-            let zsh_path = home.join(".zsh_history");
-            if let Ok(content) = fs::read_to_string(&zsh_path) {
-                Self::parse_zsh_history(&content, &mut new_cache);
-            }
-
-            // Try bash history (plain lines or with timestamps)
-            let bash_path = home.join(".bash_history");
-            if let Ok(content) = fs::read_to_string(&bash_path) {
-                Self::parse_bash_history(&content, &mut new_cache);
-            }
-
-            // Try Fish history (~/.local/share/fish/fish_history)
+            // Fish keeps its history in ~/.local/share/fish/fish_history
             let fish_path = dirs::data_dir()
                 .unwrap_or_else(|| home.join(".local").join("share"))
                 .join("fish")
                 .join("fish_history");
-            if let Ok(content) = fs::read_to_string(&fish_path) {
-                Self::parse_fish_history(&content, &mut new_cache);
-            }
+            vec![
+                // ": timestamp:0;command"
+                (home.join(".zsh_history"), HistoryFormat::Zsh),
+                // Plain lines or with "#timestamp" lines
+                (home.join(".bash_history"), HistoryFormat::Bash),
+                (fish_path, HistoryFormat::Fish),
+            ]
         } else {
             // PowerShell history on Windows
             let ps_path = home
@@ -175,170 +488,398 @@ impl RankingService {
                 .join("PowerShell")
                 .join("PSReadLine")
                 .join("ConsoleHost_history.txt");
-            if let Ok(content) = fs::read_to_string(&ps_path) {
-                Self::parse_plain_history(&content, &mut new_cache);
+            vec![(ps_path, HistoryFormat::Plain)]
+        }
+    }
+
+    /// The git commands in a shell command line that run an alias or
+    /// subcommand, with the directory each ran in when the line itself tells:
+    /// after an absolute `cd` (and relative ones from there) or with an
+    /// absolute `git -C`. History interleaves every terminal, so a `cd` on an
+    /// earlier line says nothing about this one; only `start`, where atuin
+    /// says the line was typed, does. `hints` are absolute paths the line
+    /// mentioned, used when the directory is otherwise unknown.
+    fn git_runs(
+        line: &str,
+        start: Option<&Path>,
+        hints: &[String],
+    ) -> Vec<(GitInvocation, Option<PathBuf>)> {
+        let mut runs = Vec::new();
+        let mut cwd = start.map(Path::to_path_buf);
+        for words in shell_command::commands(line) {
+            if let Some(target) = shell_command::cd_target(&words, cwd.as_deref()) {
+                cwd = target;
+                continue;
+            }
+            let Some(git) = shell_command::git_invocation(&words) else {
+                continue;
+            };
+            let name = &git.args[0];
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                continue;
+            }
+            let mut dir = cwd.clone();
+            for chdir in &git.dirs {
+                dir = shell_command::resolve_path(dir.as_deref(), chdir);
             }
+            let dir = dir.or_else(|| {
+                hints.iter().find_map(|hint| shell_command::resolve_path(cwd.as_deref(), hint))
+            });
+            runs.push((git, dir));
         }
+        runs
+    }
 
-        self.history_cache = new_cache;
-    }
-
-    /// Parse zsh history with timestamps: ": 1234567890:0;git checkout main"
-    fn parse_zsh_history(content: &str, cache: &mut HashMap<String, HistoryEntry>) {
-        let re = re_zsh_timestamped();
-        for cap in re.captures_iter(content) {
-            if let (Some(ts), Some(cmd)) = (cap.get(1), cap.get(2)) {
-                let timestamp: u64 = ts.as_str().parse().unwrap_or(0);
-                let key = format!("git {}", cmd.as_str());
-                let entry = cache.entry(key).or_insert(HistoryEntry {
-                    frequency: 0.0,
-                    last_seen: 0,
-                });
-                entry.frequency += 1.0;
-                if timestamp > entry.last_seen {
-                    entry.last_seen = timestamp;
+    /// Counts every git command in a shell command line under the alias or
+    /// subcommand name that was typed, e.g. `git -C repo co main` as `git co`,
+    /// and under the directory it ran in when known. Command lines with
+    /// arguments are counted as well, for suggestions.
+    fn record_git(line: &str, timestamp: u64, hints: &[String], cache: &mut Usage) {
+        for (git, dir) in Self::git_runs(line, None, hints) {
+            let args = &git.args;
+            let name = &args[0];
+            cache.commands.entry(format!("git {}", name)).or_default().record(timestamp);
+            if args.len() > 1 {
+                let command = alias_value(args);
+                if command.len() <= MAX_SUGGESTED_COMMAND {
+                    cache.command_lines.entry(command).or_default().record(timestamp);
                 }
             }
+            if let Some(dir) = dir {
+                let names = cache.directories.entry(dir.to_string_lossy().to_string());
+                names.or_default().entry(name.clone()).or_default().record(timestamp);
+            }
         }
+    }
+
+    /// Parse zsh history, with or without `EXTENDED_HISTORY` timestamps:
+    /// ": 1234567890:0;git checkout main". Lines ending in `\` continue the
+    /// command on the next line.
+    fn parse_zsh_history(content: &str, cache: &mut Usage) {
+        let re = re_zsh_extended();
+        let mut command = String::new();
+        let mut timestamp = 0;
 
-        // Also match plain "git xxx" lines without the timestamp prefix
-        let re_plain = re_zsh_plain();
-        for cap in re_plain.captures_iter(content) {
-            if let Some(cmd) = cap.get(1) {
-                let key = format!("git {}", cmd.as_str());
-                let entry = cache.entry(key).or_insert(HistoryEntry {
-                    frequency: 0.0,
-                    last_seen: 0,
-                });
-                entry.frequency += 1.0;
+        for line in content.lines() {
+            if command.is_empty() {
+                match re.captures(line) {
+                    Some(cap) => {
+                        timestamp = cap[1].parse().unwrap_or(0);
+                        command.push_str(&cap[2]);
+                    }
+                    None => {
+                        timestamp = 0;
+                        command.push_str(line);
+                    }
+                }
+            } else {
+                command.push_str(line);
             }
+            if command.ends_with('\\') {
+                command.pop();
+                command.push('\n');
+                continue;
+            }
+            Self::record_git(&command, timestamp, &[], cache);
+            command.clear();
+        }
+        if !command.is_empty() {
+            Self::record_git(&command, timestamp, &[], cache);
         }
     }
 
     /// Parse plain history (PowerShell) — no timestamps available.
-    fn parse_plain_history(content: &str, cache: &mut HashMap<String, HistoryEntry>) {
-        let re = re_git_cmd();
-        for cap in re.captures_iter(content) {
-            if let Some(m) = cap.get(1) {
-                let key = format!("git {}", m.as_str());
-                let entry = cache.entry(key).or_insert(HistoryEntry {
-                    frequency: 0.0,
-                    last_seen: 0,
-                });
-                entry.frequency += 1.0;
-            }
+    fn parse_plain_history(content: &str, cache: &mut Usage) {
+        for line in content.lines() {
+            Self::record_git(line, 0, &[], cache);
         }
     }
 
     /// Parse bash history — handles both plain and timestamped format.
     /// Timestamped format: lines starting with `#1234567890` followed by the command on the next line.
-    fn parse_bash_history(content: &str, cache: &mut HashMap<String, HistoryEntry>) {
-        let lines: Vec<&str> = content.lines().collect();
-        let git_re = re_git_cmd();
-
-        let mut i = 0;
-        while i < lines.len() {
-            let line = lines[i];
-
+    fn parse_bash_history(content: &str, cache: &mut Usage) {
+        let mut timestamp = 0;
+        for line in content.lines() {
             // Check for timestamp prefix: #1234567890
-            if line.starts_with('#')
-                && let Ok(ts) = line[1..].trim().parse::<u64>() {
-                    // Next line is the command
-                    if i + 1 < lines.len() {
-                        if let Some(cap) = git_re.captures(lines[i + 1])
-                            && let Some(cmd) = cap.get(1) {
-                                let key = format!("git {}", cmd.as_str());
-                                let entry = cache.entry(key).or_insert(HistoryEntry {
-                                    frequency: 0.0,
-                                    last_seen: 0,
-                                });
-                                entry.frequency += 1.0;
-                                if ts > entry.last_seen {
-                                    entry.last_seen = ts;
-                                }
-                            }
-                        i += 2;
-                        continue;
-                    }
-                }
-
-            // Plain line (no timestamp)
-            if let Some(cap) = git_re.captures(line)
-                && let Some(cmd) = cap.get(1) {
-                    let key = format!("git {}", cmd.as_str());
-                    let entry = cache.entry(key).or_insert(HistoryEntry {
-                        frequency: 0.0,
-                        last_seen: 0,
-                    });
-                    entry.frequency += 1.0;
-                }
-
-            i += 1;
+            if let Some(ts) = line.strip_prefix('#').and_then(|ts| ts.trim().parse().ok()) {
+                timestamp = ts;
+                continue;
+            }
+            Self::record_git(line, timestamp, &[], cache);
+            timestamp = 0;
         }
     }
 
-    /// Parse Fish shell history (YAML-like format):
+    /// Parse Fish shell history (YAML-like format), where `paths` lists the
+    /// arguments that named existing files:
     /// ```text
     /// - cmd: git checkout main
     ///   when: 1700000000
+    ///   paths:
+    ///     - src/main.rs
     /// ```
-    fn parse_fish_history(content: &str, cache: &mut HashMap<String, HistoryEntry>) {
-        let git_re = re_git_cmd();
-        let mut current_cmd: Option<String> = None;
+    fn parse_fish_history(content: &str, cache: &mut Usage) {
+        // The command being read, its time and paths
+        let mut entry: Option<(String, u64, Vec<String>)> = None;
+        let mut in_paths = false;
 
         for line in content.lines() {
             let trimmed = line.trim();
 
             if let Some(cmd_part) = trimmed.strip_prefix("- cmd:") {
-                current_cmd = Some(cmd_part.trim().to_string());
-            } else if let Some(when_part) = trimmed.strip_prefix("when:") {
-                if let Some(ref cmd) = current_cmd {
-                    let timestamp: u64 = when_part.trim().parse().unwrap_or(0);
-                    if let Some(cap) = git_re.captures(cmd)
-                        && let Some(m) = cap.get(1) {
-                            let key = format!("git {}", m.as_str());
-                            let entry = cache.entry(key).or_insert(HistoryEntry {
-                                frequency: 0.0,
-                                last_seen: 0,
-                            });
-                            entry.frequency += 1.0;
-                            if timestamp > entry.last_seen {
-                                entry.last_seen = timestamp;
-                            }
-                        }
+                if let Some((cmd, when, paths)) = entry.take() {
+                    Self::record_git(&cmd, when, &paths, cache);
+                }
+                entry = Some((Self::unescape_fish(cmd_part.trim()), 0, Vec::new()));
+                in_paths = false;
+            } else if let Some((_, when, paths)) = entry.as_mut() {
+                if let Some(when_part) = trimmed.strip_prefix("when:") {
+                    *when = when_part.trim().parse().unwrap_or(0);
+                } else if trimmed == "paths:" {
+                    in_paths = true;
+                } else if let Some(path) = trimmed.strip_prefix("- ").filter(|_| in_paths) {
+                    paths.push(Self::unescape_fish(path));
+                }
+            }
+        }
+
+        // A command without `when:` still counts
+        if let Some((cmd, when, paths)) = entry {
+            Self::record_git(&cmd, when, &paths, cache);
+        }
+    }
+
+    /// Fish stores newlines in commands as `\n` and backslashes as `\\`.
+    fn unescape_fish(cmd: &str) -> String {
+        let mut out = String::with_capacity(cmd.len());
+        let mut chars = cmd.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some('n')) => {
+                    chars.next();
+                    out.push('\n');
+                }
+                ('\\', Some('\\')) => {
+                    chars.next();
+                    out.push('\\');
+                }
+                (c, _) => out.push(c),
+            }
+        }
+        out
+    }
+
+    /// Shell startup files that commonly define aliases, plus the oh-my-zsh
+    /// git plugin when `.zshrc` enables it. Missing files are left out.
+    pub fn shell_alias_files() -> Vec<PathBuf> {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        let mut files: Vec<PathBuf> = [
+            ".bashrc",
+            ".bash_aliases",
+            ".bash_profile",
+            ".zshrc",
+            ".zsh_aliases",
+        ]
+        .iter()
+        .map(|f| home.join(f))
+        .collect();
+
+        let omz_git = std::env::var_os("ZSH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".oh-my-zsh"))
+            .join("plugins")
+            .join("git")
+            .join("git.plugin.zsh");
+        let zshrc = fs::read_to_string(home.join(".zshrc")).unwrap_or_default();
+        let plugin_enabled = zshrc.lines().any(|line| {
+            line.trim_start().starts_with("plugins=")
+                && line
+                    .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+                    .any(|w| w == "git")
+        });
+        if plugin_enabled {
+            files.push(omz_git);
+        }
+
+        let fish = dirs::config_dir()
+            .unwrap_or_else(|| home.join(".config"))
+            .join("fish");
+        files.push(fish.join("config.fish"));
+        if let Ok(entries) = fs::read_dir(fish.join("conf.d")) {
+            let mut conf_d: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "fish"))
+                .collect();
+            conf_d.sort();
+            files.extend(conf_d);
+        }
+
+        files.into_iter().filter(|f| f.is_file()).collect()
+    }
+
+    /// Reads the aliases defined in `path`, as fish or POSIX shell syntax
+    /// depending on the file extension.
+    pub fn read_shell_aliases(path: &Path) -> Result<Vec<ShellAlias>, GamError> {
+        let content = fs::read_to_string(path).map_err(|e| GamError::file("read", path, e))?;
+        let fish = path.extension().is_some_and(|ext| ext == "fish");
+        let parsed = if fish {
+            Self::parse_fish_aliases(&content)
+        } else {
+            Self::parse_posix_aliases(&content)
+        };
+        Ok(parsed
+            .into_iter()
+            .map(|(name, expansion)| ShellAlias {
+                name,
+                expansion,
+                source: path.to_path_buf(),
+            })
+            .collect())
+    }
+
+    /// Parse bash/zsh `alias` builtins:
+    /// `alias gco='git checkout'`, `alias -g gp="git push" gl=git\ pull`
+    fn parse_posix_aliases(content: &str) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        for line in content.lines() {
+            let Some(rest) = line.trim_start().strip_prefix("alias ") else {
+                continue;
+            };
+            let Ok(words) = split_cmdline(rest) else {
+                continue;
+            };
+            for word in words {
+                if word.starts_with('#') {
+                    break;
+                }
+                if word.starts_with('-') {
+                    continue;
+                }
+                if let Some((name, expansion)) = word.split_once('=') {
+                    out.push((name.to_string(), expansion.trim().to_string()));
                 }
-                current_cmd = None;
             }
         }
+        out
+    }
 
-        // Handle trailing cmd without when
-        if let Some(ref cmd) = current_cmd
-            && let Some(cap) = git_re.captures(cmd)
-                && let Some(m) = cap.get(1) {
-                    let key = format!("git {}", m.as_str());
-                    let entry = cache.entry(key).or_insert(HistoryEntry {
-                        frequency: 0.0,
-                        last_seen: 0,
-                    });
-                    entry.frequency += 1.0;
+    /// Parse fish `abbr` and `alias` definitions:
+    /// `abbr -a gco git checkout`, `abbr --add gp -- git push`, `alias gl 'git pull'`
+    fn parse_fish_aliases(content: &str) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        for line in content.lines() {
+            let Ok(words) = split_cmdline(line.trim()) else {
+                continue;
+            };
+            let Some((keyword, rest)) = words.split_first() else {
+                continue;
+            };
+            if keyword != "abbr" && keyword != "alias" {
+                continue;
+            }
+            let rest: Vec<&String> = rest.iter().take_while(|w| !w.starts_with('#')).collect();
+
+            let mut i = 0;
+            while let Some(word) = rest.get(i) {
+                match word.as_str() {
+                    // Options that take a value
+                    "--position" | "-p" | "--regex" | "-r" | "--function" | "-f" => i += 2,
+                    "--" => {
+                        i += 1;
+                        break;
+                    }
+                    w if w.starts_with('-') => {
+                        // `abbr --erase`, `--list`, `--show` etc. don't define anything
+                        if matches!(
+                            w,
+                            "-e" | "--erase" | "-l" | "--list" | "-s" | "--show" | "-q" | "--query"
+                        ) {
+                            i = rest.len();
+                        }
+                        i += 1;
+                    }
+                    _ => break,
                 }
+            }
+            let Some(name) = rest.get(i) else {
+                continue;
+            };
+            // `alias name=value` is also valid fish
+            let (name, expansion) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => {
+                    let expansion: Vec<&str> = rest[i + 1..]
+                        .iter()
+                        .skip_while(|w| w.as_str() == "--")
+                        .map(|w| w.as_str())
+                        .collect();
+                    (name.to_string(), expansion.join(" "))
+                }
+            };
+            if !expansion.trim().is_empty() {
+                out.push((name, expansion.trim().to_string()));
+            }
+        }
+        out
+    }
+}
+
+/// Words after `git` as an alias value, quoted so git splits them back alike.
+fn alias_value(words: &[String]) -> String {
+    words.iter().map(|w| shell_quote(w)).collect::<Vec<_>>().join(" ")
+}
+
+/// Alias names for a command line, best first: the initials of its words
+/// (`status --short --branch` → `ssb`), then with the subcommand's first two
+/// letters (`stsb`), then the initials numbered (`ssb2` ... `ssb9`).
+fn suggestion_names(words: &[String]) -> Vec<String> {
+    let initial = |word: &String| {
+        word.chars()
+            .find(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+    };
+    let rest: String = words.iter().skip(1).filter_map(initial).take(5).collect();
+    let subcommand: String = words[0]
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let Some(first) = subcommand.chars().next() else {
+        return Vec::new();
+    };
+
+    let initials = format!("{}{}", first, rest);
+    let mut names = vec![initials.clone()];
+    if subcommand.len() > 1 {
+        names.push(format!("{}{}", &subcommand[..2], rest));
     }
+    names.extend((2..=9).map(|n| format!("{}{}", initials, n)));
+    names
+}
+
+/// atuin's history database, in its data directory on every platform.
+fn atuin_database() -> Option<PathBuf> {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("share")))?;
+    Some(data_dir.join("atuin").join("history.db"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git_service::Scope;
 
     #[test]
     fn new_service_has_empty_cache() {
-        let svc = RankingService::new();
-        assert!(svc.history_cache.is_empty());
+        let svc = RankingService::in_memory();
+        assert!(svc.usage.get("git status").is_none());
         assert!(svc.last_fetch_time.is_none());
     }
 
     #[test]
     fn scores_empty_aliases() {
-        let mut svc = RankingService::new();
+        let mut svc = RankingService::in_memory();
         let aliases: Vec<GitAlias> = vec![];
         let scores = svc.get_scores(&aliases).unwrap();
         assert!(scores.is_empty());
@@ -346,22 +887,50 @@ mod tests {
 
     #[test]
     fn scores_alias_with_no_history() {
-        let mut svc = RankingService::new();
+        let mut svc = RankingService::in_memory();
         let aliases = vec![GitAlias {
             name: "co".to_string(),
             command: "checkout".to_string(),
-            scope: "global".to_string(),
+            scope: Scope::Global,
             local_path: None,
             score: None,
+            origin: None,
+            include_chain: Vec::new(),
         }];
         let scores = svc.get_scores(&aliases).unwrap();
-        assert_eq!(*scores.get("co").unwrap(), 0.0);
+        assert_eq!(scores[0].score, 0.0);
+    }
+
+    #[test]
+    fn recent_runs_outweigh_old_ones() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let day = 86_400;
+        let svc = RankingService::in_memory();
+        let runs = |timestamps: &[u64]| {
+            let mut entry = HistoryEntry::default();
+            for t in timestamps {
+                entry.record(*t);
+            }
+            svc.decayed_runs(&entry, now)
+        };
+        let half_life = (svc.half_life_days * day as f64) as u64;
+
+        assert!((runs(&[now]) - 1.0).abs() < 1e-9);
+        assert!((runs(&[now - half_life]) - 0.5).abs() < 1e-9);
+        assert!((runs(&[0, 0]) - 2.0 * UNDATED_WEIGHT).abs() < 1e-9);
+        // Two runs two half-lives ago weigh as much as one a half-life ago
+        let old = now - 2 * half_life;
+        assert!((runs(&[old, old]) - runs(&[now - half_life])).abs() < 1e-9);
+        assert!(runs(&[now - day]) > runs(&[now - 2 * day]));
     }
 
     #[test]
     fn parse_zsh_history_extracts_timestamps() {
         let content = ": 1700000000:0;git checkout main\n: 1700000100:0;git status\n";
-        let mut cache = HashMap::new();
+        let mut cache = Usage::default();
         RankingService::parse_zsh_history(content, &mut cache);
 
         let co = cache.get("git checkout").unwrap();
@@ -373,10 +942,179 @@ mod tests {
         assert_eq!(st.last_seen, 1700000100);
     }
 
+    #[test]
+    fn counts_the_alias_typed_anywhere_in_a_command_line() {
+        let content = "cd repo && git -C sub co main | cat\nsudo git --no-pager lg\ngit checkout main\n";
+        let mut cache = Usage::default();
+        RankingService::parse_plain_history(content, &mut cache);
+        assert_eq!(cache.get("git co").unwrap().frequency, 1.0);
+        assert_eq!(cache.get("git lg").unwrap().frequency, 1.0);
+        assert_eq!(cache.get("git checkout").unwrap().frequency, 1.0);
+        assert_eq!(cache.commands.len(), 3);
+
+        // zsh continues a command on the next line after a trailing `\`
+        let content = ": 1700000000:0;git add . \\\n  && git ci -m wip\n: 1700000100:0;ls\n";
+        let mut cache = Usage::default();
+        RankingService::parse_zsh_history(content, &mut cache);
+        assert_eq!(cache.get("git ci").unwrap().last_seen, 1700000000);
+        assert_eq!(cache.get("git add").unwrap().frequency, 1.0);
+
+        let content = "- cmd: git ci -m 'a\\nb' && git st\n  when: 1700000000\n";
+        let mut cache = Usage::default();
+        RankingService::parse_fish_history(content, &mut cache);
+        assert_eq!(cache.get("git st").unwrap().last_seen, 1700000000);
+    }
+
+    #[test]
+    fn places_runs_where_atuin_saw_them() {
+        let db = std::env::temp_dir().join(format!("gam_test_atuin_{}.db", std::process::id()));
+        let _ = fs::remove_file(&db);
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE history (id TEXT PRIMARY KEY, timestamp INTEGER NOT NULL, \
+             duration INTEGER NOT NULL, exit INTEGER NOT NULL, command TEXT NOT NULL, \
+             cwd TEXT NOT NULL, session TEXT NOT NULL, hostname TEXT NOT NULL, deleted_at INTEGER)",
+        )
+        .unwrap();
+        let second = 1_000_000_000i64;
+        let add = |id: i64, cwd: &str, command: &str| {
+            conn.execute(
+                "INSERT INTO history VALUES (?1, ?2, 0, 0, ?3, ?4, 's', 'h', NULL)",
+                rusqlite::params![id.to_string(), 1_700_000_000 * second + id, command, cwd],
+            )
+            .unwrap();
+        };
+        add(1, "/src/gam", "git co main");
+        add(2, "/src/gam", "cd vendor && git st");
+        // The line places itself, so the history file already counted it there
+        add(3, "/src/gam", "cd /src/other && git lg");
+        add(4, "", "git co main");
+
+        let mut svc = RankingService::in_memory();
+        let read = |svc: &mut RankingService| {
+            svc.usage.ingest_since(&db, |after, usage| {
+                RankingService::read_atuin(&db, after, usage).ok()
+            })
+        };
+        assert!(read(&mut svc));
+        let dirs = &svc.usage.usage().directories;
+        assert_eq!(dirs["/src/gam"]["co"].runs, 1);
+        assert_eq!(dirs["/src/gam"]["co"].last_seen, 1_700_000_000);
+        assert_eq!(dirs["/src/gam/vendor"]["st"].runs, 1);
+        assert!(!dirs.contains_key("/src/other"));
+        assert_eq!(dirs.len(), 2);
+
+        // Only entries added since are read
+        assert!(!read(&mut svc));
+        add(5, "/src/gam", "git co -");
+        assert!(read(&mut svc));
+        assert_eq!(svc.usage.usage().directories["/src/gam"]["co"].runs, 2);
+
+        drop(conn);
+        let _ = fs::remove_file(&db);
+    }
+
+    /// Counts `history` into `svc`'s store the way a refresh does.
+    fn ingest(svc: &mut RankingService, history: &str, parse: fn(&str, &mut Usage)) {
+        static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir()
+            .join(format!("gam_test_history_{}_{}", std::process::id(), id));
+        fs::write(&path, history).unwrap();
+        assert!(svc.usage.ingest(&path, parse));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn attributes_runs_to_the_innermost_repository() {
+        let mut svc = RankingService::in_memory();
+        svc.last_fetch_time = Some(Instant::now());
+        let content = ": 1:0;cd /src/gam && git co main\n\
+                       : 2:0;cd /src/gam; cd vendor/lib && git st\n\
+                       : 3:0;git -C /src/gam/vendor/lib co dev\n: 4:0;cd vendor && git co x\n\
+                       : 5:0;cd /src/gam\n: 6:0;git co z\n";
+        ingest(&mut svc, content, RankingService::parse_zsh_history);
+        let content = "- cmd: git co y\n  when: 7\n  paths:\n    - /src/app/README.md\n";
+        ingest(&mut svc, content, RankingService::parse_fish_history);
+
+        let alias = |name: &str, repo: Option<&str>| GitAlias {
+            name: name.to_string(),
+            command: "checkout".to_string(),
+            scope: repo.map_or(Scope::Global, |_| Scope::Local),
+            local_path: repo.map(str::to_string),
+            score: None,
+            origin: None,
+            include_chain: Vec::new(),
+        };
+        let aliases = vec![alias("co", None), alias("st", Some("/src/gam/vendor/lib"))];
+        let repos: Vec<String> = ["/src/app", "/src/gam", "/src/gam/vendor/lib"]
+            .map(String::from)
+            .to_vec();
+        let stats = svc.repo_stats(&repos, &aliases, 10);
+        let runs: Vec<_> = stats.iter().map(|s| (s.repo.as_str(), s.runs)).collect();
+        assert_eq!(runs, [("/src/gam/vendor/lib", 2), ("/src/app", 1), ("/src/gam", 1)]);
+        let names: Vec<_> = stats[0].top_aliases.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["co", "st"]);
+        assert_eq!(stats[2].top_aliases[0].last_seen, 1);
+
+        // A relative `cd`, or one on an earlier line, doesn't place a run
+        assert_eq!(svc.usage.get("git co").unwrap().frequency, 5.0);
+        let scores = svc.get_scores(&aliases).unwrap();
+        assert_eq!(scores[0].repo_share, None);
+        assert_eq!(scores[1].repo_share, Some(1.0));
+    }
+
+    #[test]
+    fn suggests_free_names_for_repeated_long_commands() {
+        let mut history = String::new();
+        for _ in 0..4 {
+            history.push_str("git status --short --branch\n");
+            history.push_str("git log --format='%h %s' -20 | head\n");
+            history.push_str("git lg --stat --no-merges\n");
+        }
+        // Too rare, too short, or aliased already
+        history.push_str("git rebase -i --autosquash origin/main\n");
+        history.push_str("git diff --stat\ngit diff --stat\ngit diff --stat\n");
+        for _ in 0..3 {
+            history.push_str("git fetch --all --prune\n");
+        }
+        let mut usage = Usage::default();
+        RankingService::parse_plain_history(&history, &mut usage);
+
+        let alias = |name: &str, command: &str| GitAlias {
+            name: name.to_string(),
+            command: command.to_string(),
+            scope: Scope::Global,
+            local_path: None,
+            score: None,
+            origin: None,
+            include_chain: Vec::new(),
+        };
+        let aliases = vec![
+            alias("ssb", "stash show -p"),
+            alias("lg", "log --graph"),
+            alias("fa", "fetch  --all \"--prune\""),
+        ];
+        let suggestions = RankingService::suggestions(&usage.command_lines, &aliases, 10);
+        let summary: Vec<(&str, &str, u64)> = suggestions
+            .iter()
+            .map(|s| (s.name.as_str(), s.command.as_str(), s.keystrokes_saved))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("lf2", "log '--format=%h %s' -20", 4 * 21),
+                ("stsb", "status --short --branch", 4 * 19),
+            ]
+        );
+        assert_eq!(suggestions[0].runs, 4);
+        assert_eq!(RankingService::suggestions(&usage.command_lines, &aliases, 1).len(), 1);
+    }
+
     #[test]
     fn parse_plain_history_counts_frequency() {
         let content = "git commit -m 'test'\ngit commit --amend\nls -la\ngit status\n";
-        let mut cache = HashMap::new();
+        let mut cache = Usage::default();
         RankingService::parse_plain_history(content, &mut cache);
 
         assert_eq!(cache.get("git commit").unwrap().frequency, 2.0);
@@ -384,19 +1122,10 @@ mod tests {
         assert!(cache.get("git ls").is_none());
     }
 
-    #[test]
-    fn time_multipliers_are_correct() {
-        // Verify the constants match the README
-        assert_eq!(MULTIPLIER_1H, 4.0);
-        assert_eq!(MULTIPLIER_1D, 2.0);
-        assert_eq!(MULTIPLIER_1W, 0.5);
-        assert_eq!(MULTIPLIER_OLD, 0.25);
-    }
-
     #[test]
     fn parse_fish_history_extracts_commands_with_timestamps() {
         let content = "- cmd: git checkout main\n  when: 1700000000\n- cmd: git status\n  when: 1700000100\n- cmd: ls -la\n  when: 1700000200\n";
-        let mut cache = HashMap::new();
+        let mut cache = Usage::default();
         RankingService::parse_fish_history(content, &mut cache);
 
         let co = cache.get("git checkout").unwrap();
@@ -413,7 +1142,7 @@ mod tests {
     #[test]
     fn parse_fish_history_handles_no_when() {
         let content = "- cmd: git push origin main\n";
-        let mut cache = HashMap::new();
+        let mut cache = Usage::default();
         RankingService::parse_fish_history(content, &mut cache);
 
         assert_eq!(cache.get("git push").unwrap().frequency, 1.0);
@@ -422,7 +1151,7 @@ mod tests {
     #[test]
     fn parse_bash_history_with_timestamps() {
         let content = "#1700000000\ngit checkout main\n#1700000100\ngit status\n#1700000200\nls -la\n";
-        let mut cache = HashMap::new();
+        let mut cache = Usage::default();
         RankingService::parse_bash_history(content, &mut cache);
 
         let co = cache.get("git checkout").unwrap();
@@ -437,11 +1166,43 @@ mod tests {
     #[test]
     fn parse_bash_history_plain_lines() {
         let content = "git commit -m 'test'\ngit push\nls\n";
-        let mut cache = HashMap::new();
+        let mut cache = Usage::default();
         RankingService::parse_bash_history(content, &mut cache);
 
         assert_eq!(cache.get("git commit").unwrap().frequency, 1.0);
         assert_eq!(cache.get("git push").unwrap().frequency, 1.0);
         assert!(cache.get("git ls").is_none());
     }
+
+    #[test]
+    fn parse_posix_aliases_handles_quoting_and_flags() {
+        let content = "# aliases\nalias gco='git checkout'\n  alias -g gp=\"git push\" gl=git\\ pull\nalias ll='ls -la' # listing\nexport FOO=bar\nalias gsta='git stash push'\\''s'\n";
+        let aliases = RankingService::parse_posix_aliases(content);
+        assert_eq!(
+            aliases,
+            vec![
+                ("gco".to_string(), "git checkout".to_string()),
+                ("gp".to_string(), "git push".to_string()),
+                ("gl".to_string(), "git pull".to_string()),
+                ("ll".to_string(), "ls -la".to_string()),
+                ("gsta".to_string(), "git stash push's".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fish_aliases_handles_abbr_and_alias() {
+        let content = "abbr -a gco git checkout\nabbr --add --position command gp -- git push\nalias gl 'git pull --rebase'\nalias gst=\"git status\"\nabbr --erase gco\nabbr gd git diff\n";
+        let aliases = RankingService::parse_fish_aliases(content);
+        assert_eq!(
+            aliases,
+            vec![
+                ("gco".to_string(), "git checkout".to_string()),
+                ("gp".to_string(), "git push".to_string()),
+                ("gl".to_string(), "git pull --rebase".to_string()),
+                ("gst".to_string(), "git status".to_string()),
+                ("gd".to_string(), "git diff".to_string()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,537 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alias_graph::AliasGraph;
+use crate::error::GamError;
+use crate::git_config::ConfigFile;
+use crate::git_environment::GitEnvironment;
+
+/// Output beyond this many bytes per stream is dropped from the report.
+const MAX_OUTPUT: usize = 64 * 1024;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What to run in the sandbox, prepared by `GitService::dry_run_plan`.
+#[derive(Debug, Clone)]
+pub struct DryRunPlan {
+    pub name: String,
+    /// Every alias the sandbox config defines, including the one under test.
+    pub aliases: Vec<(String, String)>,
+    /// Repository to clone into the sandbox instead of starting empty.
+    pub source: Option<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefChange {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// A path whose `git status` code changed; `None` means clean.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorktreeChange {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunReport {
+    pub command_line: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloned_from: Option<String>,
+    /// `None` when the process was killed or terminated by a signal.
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    pub ref_changes: Vec<RefChange>,
+    pub worktree_changes: Vec<WorktreeChange>,
+    pub warnings: Vec<String>,
+}
+
+/// Runs aliases inside a throwaway repository with an isolated git config.
+pub struct SandboxService;
+
+impl SandboxService {
+    /// Runs `plan` in a new sandbox. Shell aliases run real commands with the
+    /// user's privileges, so one anywhere in the expansion is refused unless
+    /// `allow_shell` is set.
+    pub fn dry_run(
+        plan: &DryRunPlan,
+        args: &[String],
+        timeout: Duration,
+        allow_shell: bool,
+    ) -> Result<DryRunReport, GamError> {
+        let mut warnings = plan.warnings.clone();
+        if let Some(shell) = AliasGraph::new(plan.aliases.iter().cloned()).shell_alias(&plan.name) {
+            if !allow_shell {
+                return Err(GamError::Invalid(format!(
+                    "\"{}\" is a shell alias: it would run real commands outside the sandbox",
+                    shell
+                )));
+            }
+            warnings.push(
+                "Shell aliases run real commands; only git state inside the sandbox is isolated"
+                    .to_string(),
+            );
+        }
+
+        let sandbox = Sandbox::create(&plan.aliases, plan.source.as_deref())?;
+        let before = sandbox.snapshot()?;
+
+        let mut argv = vec![plan.name.clone()];
+        argv.extend(args.iter().cloned());
+        let started = Instant::now();
+        let run = sandbox.run(&argv, timeout.min(MAX_TIMEOUT))?;
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        let after = sandbox.snapshot()?;
+        if run.timed_out {
+            warnings.push(format!("Killed after {:?}", timeout.min(MAX_TIMEOUT)));
+        }
+
+        Ok(DryRunReport {
+            command_line: std::iter::once("git".to_string())
+                .chain(argv)
+                .collect::<Vec<_>>()
+                .join(" "),
+            cloned_from: plan
+                .source
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
+            exit_code: run.exit_code,
+            timed_out: run.timed_out,
+            duration_ms,
+            stdout: run.stdout,
+            stderr: run.stderr,
+            ref_changes: diff_maps(&before.refs, &after.refs)
+                .into_iter()
+                .map(|(name, before, after)| RefChange {
+                    name,
+                    before,
+                    after,
+                })
+                .collect(),
+            worktree_changes: diff_maps(&before.status, &after.status)
+                .into_iter()
+                .map(|(path, before, after)| WorktreeChange {
+                    path,
+                    before,
+                    after,
+                })
+                .collect(),
+            warnings,
+        })
+    }
+}
+
+struct RunOutput {
+    exit_code: Option<i32>,
+    timed_out: bool,
+    stdout: String,
+    stderr: String,
+}
+
+struct Snapshot {
+    refs: BTreeMap<String, String>,
+    status: BTreeMap<String, String>,
+}
+
+/// A temporary directory holding a fake `$HOME` and a scratch repository.
+/// Removed on drop.
+struct Sandbox {
+    root: PathBuf,
+    home: PathBuf,
+    repo: PathBuf,
+}
+
+impl Sandbox {
+    fn create(aliases: &[(String, String)], source: Option<&Path>) -> Result<Self, GamError> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "gam-sandbox-{}-{}-{}",
+            std::process::id(),
+            nanos,
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let sandbox = Sandbox {
+            home: root.join("home"),
+            repo: root.join("repo"),
+            root,
+        };
+        fs::create_dir_all(&sandbox.home)
+            .map_err(|e| GamError::file("create sandbox", &sandbox.home, e))?;
+
+        let mut config = ConfigFile::parse("")?;
+        config.set("user", None, "name", "GAM Sandbox")?;
+        config.set("user", None, "email", "sandbox@gam.invalid")?;
+        config.set("init", None, "defaultBranch", "main")?;
+        for (name, command) in aliases {
+            config.set("alias", None, name, command)?;
+        }
+        config.save(&sandbox.home.join(".gitconfig"))?;
+
+        let repo = sandbox.repo.to_string_lossy().to_string();
+        match source {
+            Some(source) => {
+                let source = source.to_string_lossy();
+                sandbox.git_in(
+                    &sandbox.root,
+                    &["clone", "-q", "--no-hardlinks", &source, &repo],
+                )?;
+                // Keep fetches working but make sure nothing is pushed back.
+                let nowhere = sandbox.root.join("no-push").to_string_lossy().to_string();
+                sandbox.git(&["config", "remote.origin.pushurl", &nowhere])?;
+            }
+            None => {
+                sandbox.git_in(&sandbox.root, &["init", "-q", &repo])?;
+                let readme = sandbox.repo.join("README.md");
+                fs::write(&readme, "# Sandbox\n")
+                    .map_err(|e| GamError::file("create sandbox", &readme, e))?;
+                sandbox.git(&["add", "README.md"])?;
+                sandbox.git(&["commit", "-q", "-m", "Initial commit"])?;
+            }
+        }
+        Ok(sandbox)
+    }
+
+    /// A `git` command isolated from the user's config and terminal.
+    fn command(&self, cwd: &Path, args: &[&str]) -> Result<Command, GamError> {
+        let mut cmd = GitEnvironment::current().command()?;
+        cmd.args(args)
+            .current_dir(cwd)
+            .stdin(Stdio::null())
+            .env("HOME", &self.home)
+            .env("XDG_CONFIG_HOME", self.home.join(".config"))
+            .env("GIT_CONFIG_GLOBAL", self.home.join(".gitconfig"))
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_PAGER", "cat")
+            .env("PAGER", "cat")
+            .env("GIT_EDITOR", "true")
+            .env("GIT_SEQUENCE_EDITOR", "true");
+        for var in [
+            "GIT_DIR",
+            "GIT_WORK_TREE",
+            "GIT_INDEX_FILE",
+            "GIT_CONFIG",
+            "GIT_CONFIG_PARAMETERS",
+            "GIT_CONFIG_COUNT",
+        ] {
+            cmd.env_remove(var);
+        }
+        Ok(cmd)
+    }
+
+    fn git_in(&self, cwd: &Path, args: &[&str]) -> Result<String, GamError> {
+        let output = self.command(cwd, args)?.output()?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            let status = output.status.code();
+            Err(GamError::GitFailed {
+                message: if stderr.is_empty() {
+                    format!("git command failed with exit code {:?}", status)
+                } else {
+                    stderr
+                },
+                status,
+            })
+        }
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, GamError> {
+        self.git_in(&self.repo, args)
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, GamError> {
+        let mut refs: BTreeMap<String, String> = self
+            .git(&["for-each-ref", "--format=%(refname) %(objectname)"])?
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(name, oid)| (name.to_string(), oid.to_string()))
+            .collect();
+        let head = match self.git(&["symbolic-ref", "-q", "HEAD"]) {
+            Ok(target) => format!("ref: {}", target.trim()),
+            Err(_) => self.git(&["rev-parse", "HEAD"])?.trim().to_string(),
+        };
+        refs.insert("HEAD".to_string(), head);
+
+        let output = self.git(&["status", "--porcelain=v1", "-z", "--untracked-files=all"])?;
+        let mut status = BTreeMap::new();
+        let mut entries = output.split('\0');
+        while let Some(entry) = entries.next() {
+            // `XY path`, where X and Y are ASCII status letters
+            let (Some(code), Some(path)) = (entry.get(..2), entry.get(3..)) else {
+                continue;
+            };
+            // A rename or copy is followed by the path it came from
+            if code.contains(['R', 'C']) {
+                entries.next();
+            }
+            status.insert(path.to_string(), code.to_string());
+        }
+        Ok(Snapshot { refs, status })
+    }
+
+    fn run(&self, argv: &[String], timeout: Duration) -> Result<RunOutput, GamError> {
+        let args: Vec<&str> = argv.iter().map(|a| a.as_str()).collect();
+        let mut cmd = self.command(&self.repo, &args)?;
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        // Give the run its own process group, so whatever a shell alias starts
+        // can be killed with it.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let mut child = cmd.spawn()?;
+
+        // Read both pipes on their own threads so a chatty child can't block
+        // on a full pipe while we wait for it.
+        let (tx, rx) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            let tx = tx.clone();
+            std::thread::spawn(move || tx.send((true, read_capped(stdout))));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            std::thread::spawn(move || tx.send((false, read_capped(stderr))));
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut timed_out = false;
+        let status = loop {
+            match child.try_wait().map_err(GamError::Io)? {
+                Some(status) => break Some(status),
+                None if Instant::now() >= deadline => {
+                    timed_out = true;
+                    kill_tree(&mut child);
+                    break child.wait().ok();
+                }
+                None => std::thread::sleep(Duration::from_millis(20)),
+            }
+        };
+        // Nothing started by the run may outlive the sandbox directory
+        kill_tree(&mut child);
+
+        // Processes that escaped the group may keep the pipes open after git
+        // exits, so don't wait on them forever.
+        let (mut stdout, mut stderr) = (String::new(), String::new());
+        for _ in 0..2 {
+            match rx.recv_timeout(Duration::from_secs(1)) {
+                Ok((true, out)) => stdout = out,
+                Ok((false, err)) => stderr = err,
+                Err(_) => break,
+            }
+        }
+
+        Ok(RunOutput {
+            exit_code: if timed_out {
+                None
+            } else {
+                status.and_then(|s| s.code())
+            },
+            timed_out,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Kills `child` and, where the platform allows, every process it started.
+fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        unsafe extern "C" {
+            fn kill(pid: i32, sig: i32) -> i32;
+        }
+        const SIGKILL: i32 = 9;
+        // The child leads its own process group (see `run`)
+        unsafe {
+            kill(-(child.id() as i32), SIGKILL);
+        }
+    }
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &child.id().to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    let _ = child.kill();
+}
+
+/// Reads at most `MAX_OUTPUT` bytes, then drains the rest without keeping it
+/// so the child doesn't block on a full pipe.
+fn read_capped(mut reader: impl Read) -> String {
+    let mut buf = Vec::new();
+    let _ = reader.by_ref().take(MAX_OUTPUT as u64).read_to_end(&mut buf);
+    let mut text = String::from_utf8_lossy(&buf).to_string();
+    if std::io::copy(&mut reader, &mut std::io::sink()).is_ok_and(|rest| rest > 0) {
+        text.push_str("\n… output truncated");
+    }
+    text
+}
+
+/// Keys whose values differ between two snapshots, with both values.
+fn diff_maps(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<(String, Option<String>, Option<String>)> {
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|k| before.get(*k) != after.get(*k))
+        .map(|k| (k.clone(), before.get(k).cloned(), after.get(k).cloned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(name: &str, command: &str) -> DryRunPlan {
+        DryRunPlan {
+            name: name.to_string(),
+            aliases: vec![(name.to_string(), command.to_string())],
+            source: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn dry_run_reports_output_and_ref_changes() {
+        let report = SandboxService::dry_run(
+            &plan("nb", "checkout -q -b"),
+            &["feature".to_string()],
+            DEFAULT_TIMEOUT,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(report.exit_code, Some(0));
+        assert_eq!(report.command_line, "git nb feature");
+        assert!(report.ref_changes.contains(&RefChange {
+            name: "HEAD".to_string(),
+            before: Some("ref: refs/heads/main".to_string()),
+            after: Some("ref: refs/heads/feature".to_string()),
+        }));
+        assert!(report
+            .ref_changes
+            .iter()
+            .any(|c| c.name == "refs/heads/feature" && c.before.is_none() && c.after.is_some()));
+    }
+
+    #[test]
+    fn dry_run_reports_worktree_changes() {
+        let report =
+            SandboxService::dry_run(&plan("wipe", "rm -q README.md"), &[], DEFAULT_TIMEOUT, false)
+                .unwrap();
+        assert_eq!(
+            report.worktree_changes,
+            vec![WorktreeChange {
+                path: "README.md".to_string(),
+                before: None,
+                after: Some("D ".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn dry_run_captures_failures() {
+        let report =
+            SandboxService::dry_run(&plan("bad", "log --no-such-flag"), &[], DEFAULT_TIMEOUT, false)
+                .unwrap();
+        assert_ne!(report.exit_code, Some(0));
+        assert!(!report.stderr.is_empty());
+        assert!(report.ref_changes.is_empty());
+    }
+
+    #[test]
+    fn dry_run_clones_source_repository() {
+        let source = Sandbox::create(&[], None).unwrap();
+        fs::write(source.repo.join("notes.txt"), "hi\n").unwrap();
+        source.git(&["add", "notes.txt"]).unwrap();
+        source.git(&["commit", "-q", "-m", "Add notes"]).unwrap();
+
+        let mut plan = plan("count", "rev-list --count HEAD");
+        plan.source = Some(source.repo.clone());
+        let report = SandboxService::dry_run(&plan, &[], DEFAULT_TIMEOUT, false).unwrap();
+        assert_eq!(report.stdout.trim(), "2");
+        assert!(report.cloned_from.is_some());
+    }
+
+    #[test]
+    fn dry_run_reports_renames_once() {
+        let renamed = plan("ren", "mv README.md NOTES.md");
+        let report = SandboxService::dry_run(&renamed, &[], DEFAULT_TIMEOUT, false).unwrap();
+        assert_eq!(
+            report.worktree_changes,
+            vec![WorktreeChange {
+                path: "NOTES.md".to_string(),
+                before: None,
+                after: Some("R ".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn dry_run_refuses_shell_aliases_unless_allowed() {
+        let mut chained = plan("outer", "inner -v");
+        chained.aliases.push(("inner".to_string(), "!echo hi".to_string()));
+        let err = SandboxService::dry_run(&chained, &[], DEFAULT_TIMEOUT, false).unwrap_err();
+        assert!(err.to_string().contains("\"inner\" is a shell alias"));
+
+        let report = SandboxService::dry_run(&chained, &[], DEFAULT_TIMEOUT, true).unwrap();
+        assert_eq!(report.stdout.trim(), "hi -v");
+        assert!(report.warnings.iter().any(|w| w.contains("Shell aliases")));
+    }
+
+    #[test]
+    fn dry_run_kills_on_timeout() {
+        let report = SandboxService::dry_run(
+            &plan("hang", "!sleep 5"),
+            &[],
+            Duration::from_millis(200),
+            true,
+        )
+        .unwrap();
+        assert!(report.timed_out);
+        assert_eq!(report.exit_code, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dry_run_kills_what_a_shell_alias_started() {
+        let marker = std::env::temp_dir().join(format!("gam-late-{}", std::process::id()));
+        let script = format!("!sleep 1; touch '{}'", marker.display());
+        let report =
+            SandboxService::dry_run(&plan("late", &script), &[], Duration::from_millis(200), true)
+                .unwrap();
+        assert!(report.timed_out);
+        std::thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+    }
+}
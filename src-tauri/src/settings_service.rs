@@ -2,6 +2,9 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+use crate::error::GamError;
+use crate::json_store;
+
 /// Persists app settings (e.g. theme) to a JSON file in the app data directory.
 pub struct SettingsService {
     config_path: PathBuf,
@@ -34,25 +37,23 @@ impl SettingsService {
     }
 
     fn load(&mut self) {
-        if let Ok(data) = fs::read_to_string(&self.config_path)
-            && let Ok(map) = serde_json::from_str::<HashMap<String, String>>(&data) {
-                self.settings = map;
-            }
+        match json_store::load::<HashMap<String, String>>(&self.config_path) {
+            Ok(map) => self.settings = map.unwrap_or_default(),
+            Err(e) => eprintln!("[GAM] Starting with default settings: {}", e),
+        }
     }
 
-    fn save(&self) {
-        if let Ok(json) = serde_json::to_string_pretty(&self.settings) {
-            let _ = fs::write(&self.config_path, json);
-        }
+    fn save(&self) -> Result<(), GamError> {
+        json_store::save(&self.config_path, &self.settings)
     }
 
     pub fn get(&self, key: &str) -> Option<String> {
         self.settings.get(key).cloned()
     }
 
-    pub fn set(&mut self, key: &str, value: &str) {
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), GamError> {
         self.settings.insert(key.to_string(), value.to_string());
-        self.save();
+        self.save()
     }
 }
 
@@ -92,7 +93,7 @@ mod tests {
     #[test]
     fn set_and_get_roundtrip() {
         let (mut svc, dir) = temp_settings();
-        svc.set("theme", "cybercore-dark");
+        svc.set("theme", "cybercore-dark").unwrap();
         assert_eq!(svc.get("theme"), Some("cybercore-dark".to_string()));
         cleanup(&dir);
     }
@@ -100,8 +101,8 @@ mod tests {
     #[test]
     fn set_overwrites_existing() {
         let (mut svc, dir) = temp_settings();
-        svc.set("theme", "sketch-dark");
-        svc.set("theme", "gothic-light");
+        svc.set("theme", "sketch-dark").unwrap();
+        svc.set("theme", "gothic-light").unwrap();
         assert_eq!(svc.get("theme"), Some("gothic-light".to_string()));
         cleanup(&dir);
     }
@@ -109,7 +110,7 @@ mod tests {
     #[test]
     fn save_creates_json_file() {
         let (mut svc, dir) = temp_settings();
-        svc.set("key", "value");
+        svc.set("key", "value").unwrap();
         assert!(svc.config_path.exists(), "Config file was not created at {:?}", svc.config_path);
         cleanup(&dir);
     }
@@ -126,7 +127,7 @@ mod tests {
                 config_path: config_path.clone(),
                 settings: HashMap::new(),
             };
-            svc.set("theme", "pixel-dark");
+            svc.set("theme", "pixel-dark").unwrap();
         }
 
         // Load
@@ -0,0 +1,300 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::alias_graph::subcommand_index;
+
+/// A word of a shell command line, or the end of one command in it.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// `;`, `&&`, `||`, `|`, `&`, a newline or a subshell parenthesis.
+    End,
+}
+
+/// One run of git in a shell command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitInvocation {
+    /// The words after `git`, starting at the subcommand or alias name typed.
+    pub args: Vec<String>,
+    /// The `-C` directories, in order; each is relative to the one before.
+    pub dirs: Vec<String>,
+}
+
+/// The simple commands of a shell command line, in order, as words.
+/// Chained, piped and substituted commands are split apart.
+pub fn commands(line: &str) -> Vec<Vec<String>> {
+    tokenize(line)
+        .split(|t| *t == Token::End)
+        .map(|command| {
+            command
+                .iter()
+                .filter_map(|t| match t {
+                    Token::Word(w) => Some(w.clone()),
+                    Token::End => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+/// Splits a command line into words and command ends. Quotes and `\`
+/// escapes work like in POSIX shells; redirections and comments are dropped.
+fn tokenize(line: &str) -> Vec<Token> {
+    let mut lexer = Lexer::default();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                lexer.in_word = true;
+                lexer.word.extend(chars.by_ref().take_while(|c| *c != '\''));
+            }
+            '"' => {
+                lexer.in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.peek(), Some('"' | '\\' | '$' | '`')) => {
+                            lexer.word.extend(chars.next());
+                        }
+                        c => lexer.word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                lexer.in_word = true;
+                lexer.word.extend(chars.next());
+            }
+            '#' if !lexer.in_word => break,
+            '>' | '<' => {
+                // `2>&1`, `>> log`, `< input`: drop the fd and the target
+                if lexer.word.chars().all(|c| c.is_ascii_digit()) {
+                    lexer.word.clear();
+                    lexer.in_word = false;
+                }
+                lexer.finish();
+                while chars.next_if(|c| matches!(c, '>' | '<' | '|')).is_some() {}
+                if chars.next_if_eq(&'&').is_some() {
+                    while chars.next_if(|c| c.is_ascii_digit() || *c == '-').is_some() {}
+                } else {
+                    lexer.skip_word = true;
+                }
+            }
+            ';' | '&' | '|' | '\n' | '(' | ')' | '`' => {
+                // `$(...)` starts a command like `(...)` does
+                if c == '(' && lexer.word.ends_with('$') {
+                    lexer.word.pop();
+                    lexer.in_word = !lexer.word.is_empty();
+                }
+                lexer.finish();
+                lexer.tokens.push(Token::End);
+            }
+            c if c.is_whitespace() => lexer.finish(),
+            c => {
+                lexer.in_word = true;
+                lexer.word.push(c);
+            }
+        }
+    }
+    lexer.finish();
+    lexer.tokens
+}
+
+#[derive(Default)]
+struct Lexer {
+    tokens: Vec<Token>,
+    word: String,
+    /// Set once a word started, even if it's empty like `''`.
+    in_word: bool,
+    /// Drop the next word, the target of a redirection.
+    skip_word: bool,
+}
+
+impl Lexer {
+    fn finish(&mut self) {
+        if self.in_word {
+            let word = std::mem::take(&mut self.word);
+            if !std::mem::take(&mut self.skip_word) {
+                self.tokens.push(Token::Word(word));
+            }
+            self.in_word = false;
+        }
+    }
+}
+
+/// Where a simple command changes the directory to, if it's a `cd` or
+/// `pushd`: `Some(None)` when that can't be known, as after `cd -`.
+pub fn cd_target<S: AsRef<str>>(words: &[S], cwd: Option<&Path>) -> Option<Option<PathBuf>> {
+    if !matches!(words.first()?.as_ref(), "cd" | "pushd") {
+        return None;
+    }
+    let target = words[1..]
+        .iter()
+        .map(|w| w.as_ref())
+        .find(|w| !w.starts_with('-') || *w == "-");
+    Some(match target {
+        None => dirs::home_dir(),
+        Some("-") => None,
+        Some(target) => resolve_path(cwd, target),
+    })
+}
+
+/// `path` made absolute against `base`, expanding `~` and resolving `.` and
+/// `..` without touching the disk. `None` for a relative path without a
+/// base, or one using variables.
+pub fn resolve_path(base: Option<&Path>, path: &str) -> Option<PathBuf> {
+    if path.is_empty() || path.contains('$') {
+        return None;
+    }
+    let joined = if path == "~" {
+        dirs::home_dir()?
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        dirs::home_dir()?.join(rest)
+    } else if Path::new(path).is_absolute() {
+        PathBuf::from(path)
+    } else {
+        base?.join(path)
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            c => resolved.push(c),
+        }
+    }
+    Some(resolved)
+}
+
+/// The git run of one simple command, if it runs git, with git's global
+/// options (`-C repo`, `--no-pager`, ...) left out of the arguments.
+/// `sudo`, `env`, `command` and variable assignments in front of `git` are
+/// skipped.
+pub fn git_invocation<S: AsRef<str>>(words: &[S]) -> Option<GitInvocation> {
+    let words: Vec<&str> = words.iter().map(|w| w.as_ref()).collect();
+    let mut i = 0;
+    loop {
+        let word = *words.get(i)?;
+        i += 1;
+        let with_value: &[&str] = match word {
+            _ if is_assignment(word) => continue,
+            "sudo" | "doas" => &["-u", "-g", "-h", "-p", "-C", "-D", "-R", "-r", "-t", "-T", "-U"],
+            "env" => &["-u", "-C", "-S", "--unset", "--chdir"],
+            "nice" => &["-n"],
+            "command" | "builtin" | "exec" | "nohup" | "noglob" | "time" => &[],
+            _ => {
+                i -= 1;
+                break;
+            }
+        };
+        while let Some(option) = words.get(i).filter(|w| w.starts_with('-')) {
+            // `command -v git` looks git up rather than running it
+            if word == "command" && matches!(*option, "-v" | "-V") {
+                return None;
+            }
+            i += if with_value.contains(option) { 2 } else { 1 };
+        }
+    }
+
+    let program = words[i].rsplit(['/', '\\']).next().unwrap_or_default();
+    if !matches!(program, "git" | "git.exe") {
+        return None;
+    }
+    let args = &words[i + 1..];
+    let start = subcommand_index(args)?;
+    let dirs = args[..start]
+        .windows(2)
+        .filter(|pair| pair[0] == "-C")
+        .map(|pair| pair[1].to_string())
+        .collect();
+    Some(GitInvocation {
+        args: args[start..].iter().map(|w| w.to_string()).collect(),
+        dirs,
+    })
+}
+
+/// `NAME=value`, as in `GIT_PAGER=cat git log`.
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_invocations(line: &str) -> Vec<GitInvocation> {
+        commands(line).iter().filter_map(|words| git_invocation(words)).collect()
+    }
+
+    fn names(line: &str) -> Vec<String> {
+        git_invocations(line).into_iter().map(|git| git.args[0].clone()).collect()
+    }
+
+    fn args(line: &str) -> Vec<Vec<String>> {
+        git_invocations(line).into_iter().map(|git| git.args).collect()
+    }
+
+    #[test]
+    fn skips_global_options_and_wrappers() {
+        assert_eq!(names("git -C ~/repo co main"), ["co"]);
+        assert_eq!(names("git --no-pager -c color.ui=never lg -5"), ["lg"]);
+        assert_eq!(names("sudo -u root git st"), ["st"]);
+        assert_eq!(names("env -u PAGER GIT_PAGER=cat git log"), ["log"]);
+        assert_eq!(names("LANG=C command git --git-dir .git br"), ["br"]);
+        assert_eq!(names("/usr/bin/git push"), ["push"]);
+        assert!(names("command -v git").is_empty());
+        assert!(names("git --version").is_empty());
+        assert!(names("gitk --all").is_empty());
+        assert!(names("echo git status").is_empty());
+    }
+
+    #[test]
+    fn finds_every_command_in_chains_and_pipes() {
+        assert_eq!(
+            names("git add -A && git ci -m 'wip; later' || git st; git lg | less"),
+            ["add", "ci", "st", "lg"]
+        );
+        assert_eq!(names("cd repo&&git pull&"), ["pull"]);
+        assert_eq!(names("echo $(git rev-parse HEAD) `git describe`"), ["rev-parse", "describe"]);
+        assert_eq!(names("(cd sub; git fetch)"), ["fetch"]);
+    }
+
+    #[test]
+    fn keeps_arguments_without_quotes_redirects_or_comments() {
+        assert_eq!(
+            args(r#"git commit -m "fix \"it\"" 2>&1 >> log.txt # done"#),
+            [["commit", "-m", r#"fix "it""#]]
+        );
+        assert_eq!(args("git log>out --oneline <in"), [["log", "--oneline"]]);
+        assert_eq!(args(r"git grep a\ b ''"), [["grep", "a b", ""]]);
+    }
+
+    #[test]
+    fn follows_cd_without_touching_the_disk() {
+        let home = dirs::home_dir().unwrap();
+        let repo = Path::new("/src/gam");
+        assert_eq!(cd_target(&["ls"], None), None);
+        assert_eq!(cd_target(&["cd"], Some(repo)), Some(Some(home.clone())));
+        assert_eq!(cd_target(&["cd", "-"], Some(repo)), Some(None));
+        assert_eq!(cd_target(&["cd", "-P", "../app/./x"], Some(repo)), Some(Some("/src/app/x".into())));
+        assert_eq!(cd_target(&["pushd", "~/w"], None), Some(Some(home.join("w"))));
+        assert_eq!(resolve_path(None, "sub"), None);
+        assert_eq!(resolve_path(Some(repo), "$HOME/x"), None);
+    }
+
+    #[test]
+    fn keeps_directories_and_simple_commands_in_order() {
+        let git = &git_invocations("git -C ~/src -c a=b -C gam --no-pager st")[0];
+        assert_eq!(git.dirs, ["~/src", "gam"]);
+        assert_eq!(
+            commands("cd ~/src/gam && git pull; make"),
+            [vec!["cd", "~/src/gam"], vec!["git", "pull"], vec!["make"]]
+        );
+    }
+}
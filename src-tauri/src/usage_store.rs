@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::GamError;
+use crate::json_store;
+
+/// At most this many bytes of the last line read are remembered to find the
+/// resume point in a rewritten history file.
+const MAX_TAIL: usize = 1024;
+
+/// Run times remembered per command; older ones are folded into a count.
+const MAX_TIMESTAMPS: usize = 200;
+
+/// How often a command was run and when.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Every run, with or without a timestamp.
+    pub frequency: f64,
+    /// Unix seconds of the latest run; 0 when the history has no timestamps.
+    pub last_seen: u64,
+    /// The most recent run times (Unix seconds), oldest first.
+    #[serde(default)]
+    pub timestamps: Vec<u64>,
+    /// Timed runs dropped from `timestamps`, all at or before `folded_at`.
+    #[serde(default)]
+    pub folded: f64,
+    #[serde(default)]
+    pub folded_at: u64,
+}
+
+impl HistoryEntry {
+    /// Counts one run at `timestamp`, or of unknown time when it's 0.
+    pub fn record(&mut self, timestamp: u64) {
+        self.frequency += 1.0;
+        if timestamp == 0 {
+            return;
+        }
+        self.last_seen = self.last_seen.max(timestamp);
+        let at = self.timestamps.partition_point(|t| *t <= timestamp);
+        self.timestamps.insert(at, timestamp);
+        if self.timestamps.len() > MAX_TIMESTAMPS {
+            let oldest = self.timestamps.remove(0);
+            self.folded += 1.0;
+            self.folded_at = self.folded_at.max(oldest);
+        }
+    }
+
+    /// Runs with no timestamp.
+    pub fn undated(&self) -> f64 {
+        (self.frequency - self.folded - self.timestamps.len() as f64).max(0.0)
+    }
+}
+
+/// How often a whole command line was run, without the run times.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineCount {
+    pub runs: u64,
+    /// Unix seconds of the latest run; 0 when unknown.
+    pub last_seen: u64,
+}
+
+impl LineCount {
+    pub fn record(&mut self, timestamp: u64) {
+        self.runs += 1;
+        self.last_seen = self.last_seen.max(timestamp);
+    }
+}
+
+/// What was counted in the shell history.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+    /// Runs per alias or subcommand name typed, keyed `git <name>`.
+    pub commands: HashMap<String, HistoryEntry>,
+    /// Runs per git command line with arguments, keyed by the words after
+    /// `git` as an alias value, e.g. `log --oneline -5`.
+    #[serde(default)]
+    pub command_lines: HashMap<String, LineCount>,
+    /// Runs per directory the history places them in, then per alias or
+    /// subcommand name. Fish only records the paths a command mentioned, so
+    /// its directories may be paths inside the one it ran in.
+    #[serde(default)]
+    pub directories: HashMap<String, HashMap<String, LineCount>>,
+}
+
+impl Usage {
+    pub fn get(&self, command: &str) -> Option<&HistoryEntry> {
+        self.commands.get(command)
+    }
+}
+
+/// How far a history file has been read.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FileCursor {
+    /// Inode number on Unix, to notice a file replaced by a new one.
+    inode: u64,
+    /// Bytes read so far; always the end of a line.
+    offset: u64,
+    /// Length and FNV-1a hash of the last line read, which ends at `offset`.
+    tail_len: u64,
+    tail_hash: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageData {
+    /// The parser version the counts were made with.
+    #[serde(default)]
+    parser: u32,
+    files: HashMap<String, FileCursor>,
+    /// Newest entry timestamp counted from each history database.
+    #[serde(default)]
+    databases: HashMap<String, i64>,
+    #[serde(default)]
+    usage: Usage,
+}
+
+/// Usage counts mined from shell history, kept in `usage.json` in the app
+/// data directory together with how far each history file was read, so only
+/// lines appended since are parsed.
+pub struct UsageStore {
+    /// `None` keeps the store in memory only.
+    path: Option<PathBuf>,
+    data: UsageData,
+    dirty: bool,
+}
+
+impl UsageStore {
+    /// Opens the store in the app data directory. Counts made by another
+    /// `parser` version are dropped and the history is read again.
+    pub fn new(parser: u32) -> Self {
+        let config_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("com.github.zintaen.gam");
+        Self::open(config_dir.join("usage.json"), parser)
+    }
+
+    pub fn open(path: PathBuf, parser: u32) -> Self {
+        let data = match json_store::load::<UsageData>(&path) {
+            Ok(Some(data)) if data.parser == parser => Some(data),
+            Ok(Some(_)) => {
+                eprintln!("[GAM] History parsing changed; re-reading shell history");
+                None
+            }
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("[GAM] Re-reading shell history from the start: {}", e);
+                None
+            }
+        };
+        Self {
+            path: Some(path),
+            dirty: data.is_none(),
+            data: data.unwrap_or_else(|| UsageData {
+                parser,
+                ..UsageData::default()
+            }),
+        }
+    }
+
+    pub fn in_memory(parser: u32) -> Self {
+        Self {
+            path: None,
+            data: UsageData {
+                parser,
+                ..UsageData::default()
+            },
+            dirty: false,
+        }
+    }
+
+    /// Saves the store if anything changed since it was loaded or saved.
+    pub fn save(&mut self) -> Result<(), GamError> {
+        if let (true, Some(path)) = (self.dirty, &self.path) {
+            json_store::save(path, &self.data)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn get(&self, command: &str) -> Option<&HistoryEntry> {
+        self.data.usage.get(command)
+    }
+
+    pub fn usage(&self) -> &Usage {
+        &self.data.usage
+    }
+
+    /// Counts the entries added to the database at `path` since the last
+    /// call. `read` gets the newest timestamp counted before and returns the
+    /// newest it counted, or `None` when it couldn't read the database.
+    pub fn ingest_since(
+        &mut self,
+        path: &Path,
+        read: impl FnOnce(i64, &mut Usage) -> Option<i64>,
+    ) -> bool {
+        let key = path.to_string_lossy().to_string();
+        let after = self.data.databases.get(&key).copied().unwrap_or(0);
+        match read(after, &mut self.data.usage) {
+            Some(newest) if newest > after => {
+                self.data.databases.insert(key, newest);
+                self.dirty = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Counts the complete lines appended to `path` since the last call with
+    /// `parse`. A file that was rewritten, e.g. trimmed by the shell, is
+    /// resumed after the last line read before; if that line is gone, the
+    /// whole file is read again. Returns whether any new lines were found.
+    pub fn ingest(
+        &mut self,
+        path: &Path,
+        parse: impl FnOnce(&str, &mut Usage),
+    ) -> bool {
+        let Ok(meta) = fs::metadata(path) else {
+            return false;
+        };
+        let key = path.to_string_lossy().to_string();
+        let cursor = self.data.files.get(&key).cloned().unwrap_or_default();
+        let inode = inode(&meta);
+        let Ok((start, bytes)) = read_new(path, &cursor, inode, meta.len()) else {
+            return false;
+        };
+
+        let Some(end) = bytes.iter().rposition(|&b| b == b'\n').map(|i| i + 1) else {
+            // Nothing but a partial line yet; just follow a replaced file
+            if start != cursor.offset || inode != cursor.inode {
+                self.data.files.insert(
+                    key,
+                    FileCursor {
+                        inode,
+                        offset: start,
+                        ..cursor
+                    },
+                );
+                self.dirty = true;
+            }
+            return false;
+        };
+        let complete = &bytes[..end];
+        parse(&String::from_utf8_lossy(complete), &mut self.data.usage);
+
+        let line_start = complete[..end - 1]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1)
+            .max(end.saturating_sub(MAX_TAIL));
+        let tail = &complete[line_start..];
+        self.data.files.insert(
+            key,
+            FileCursor {
+                inode,
+                offset: start + end as u64,
+                tail_len: tail.len() as u64,
+                tail_hash: fnv1a(tail),
+            },
+        );
+        self.dirty = true;
+        true
+    }
+}
+
+/// The bytes of `path` from where reading should resume, and that position.
+fn read_new(
+    path: &Path,
+    cursor: &FileCursor,
+    inode: u64,
+    len: u64,
+) -> std::io::Result<(u64, Vec<u8>)> {
+    let mut file = fs::File::open(path)?;
+    if cursor.offset > 0 && cursor.inode == inode && len >= cursor.offset {
+        // Same file, maybe appended to: check the last line read is still there
+        let from = cursor.offset - cursor.tail_len;
+        file.seek(SeekFrom::Start(from))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let tail_len = cursor.tail_len as usize;
+        if bytes.len() >= tail_len && fnv1a(&bytes[..tail_len]) == cursor.tail_hash {
+            return Ok((cursor.offset, bytes.split_off(tail_len)));
+        }
+        file.seek(SeekFrom::Start(0))?;
+    }
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let tail_len = cursor.tail_len as usize;
+    if cursor.offset == 0 || tail_len == 0 {
+        return Ok((0, bytes));
+    }
+    // The last match, so a line typed again later isn't counted twice
+    let resume = (tail_len..=bytes.len())
+        .rev()
+        .filter(|&end| bytes[end - 1] == b'\n')
+        .find(|&end| fnv1a(&bytes[end - tail_len..end]) == cursor.tail_hash)
+        .unwrap_or(0);
+    Ok((resume as u64, bytes.split_off(resume)))
+}
+
+/// FNV-1a, which stays the same across Rust versions unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(unix)]
+fn inode(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("gam_test_usage_{}_{}", std::process::id(), id));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Counts every line as one use of itself.
+    fn count_lines(content: &str, usage: &mut Usage) {
+        for line in content.lines() {
+            usage.commands.entry(line.to_string()).or_default().record(0);
+        }
+    }
+
+    fn frequency(store: &UsageStore, command: &str) -> f64 {
+        store.get(command).map_or(0.0, |e| e.frequency)
+    }
+
+    #[test]
+    fn only_new_lines_are_counted_across_restarts() {
+        let dir = temp_dir();
+        let history = dir.join("history");
+        let store_path = dir.join("usage.json");
+        fs::write(&history, "git status\ngit log\n").unwrap();
+
+        let mut store = UsageStore::open(store_path.clone(), 1);
+        assert!(store.ingest(&history, count_lines));
+        assert!(!store.ingest(&history, count_lines));
+        store.save().unwrap();
+
+        // A partial line waits until it's complete
+        let mut file = fs::OpenOptions::new().append(true).open(&history).unwrap();
+        file.write_all(b"git status\ngit pu").unwrap();
+        let mut store = UsageStore::open(store_path.clone(), 1);
+        assert!(store.ingest(&history, count_lines));
+        assert_eq!(frequency(&store, "git status"), 2.0);
+        file.write_all(b"sh\n").unwrap();
+        assert!(store.ingest(&history, count_lines));
+        assert_eq!(frequency(&store, "git push"), 1.0);
+        assert_eq!(frequency(&store, "git log"), 1.0);
+
+        store.save().unwrap();
+        let store = UsageStore::open(store_path.clone(), 1);
+        assert_eq!(frequency(&store, "git push"), 1.0);
+
+        // Counts from another parser version are dropped
+        let mut store = UsageStore::open(store_path, 2);
+        assert_eq!(frequency(&store, "git push"), 0.0);
+        assert!(store.ingest(&history, count_lines));
+        assert_eq!(frequency(&store, "git status"), 2.0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rewritten_files_resume_after_the_last_line_read() {
+        let dir = temp_dir();
+        let history = dir.join("history");
+        fs::write(&history, "git a\ngit b\ngit c\n").unwrap();
+        let mut store = UsageStore::in_memory(1);
+        store.ingest(&history, count_lines);
+
+        // The shell trims the oldest lines and appends a new one
+        fs::remove_file(&history).unwrap();
+        fs::write(&history, "git b\ngit c\ngit d\n").unwrap();
+        assert!(store.ingest(&history, count_lines));
+        assert_eq!(frequency(&store, "git b"), 1.0);
+        assert_eq!(frequency(&store, "git d"), 1.0);
+
+        // A file that no longer has the last line read is counted again
+        fs::write(&history, "git e\n").unwrap();
+        assert!(store.ingest(&history, count_lines));
+        assert_eq!(frequency(&store, "git e"), 1.0);
+        assert_eq!(frequency(&store, "git d"), 1.0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn old_timestamps_are_folded() {
+        let mut entry = HistoryEntry::default();
+        entry.record(0);
+        for t in (1..=MAX_TIMESTAMPS as u64 + 2).rev() {
+            entry.record(t * 10);
+        }
+        assert_eq!(entry.frequency, MAX_TIMESTAMPS as f64 + 3.0);
+        assert_eq!(entry.timestamps.len(), MAX_TIMESTAMPS);
+        assert_eq!(entry.timestamps[0], 30);
+        assert_eq!((entry.folded, entry.folded_at), (2.0, 20));
+        assert_eq!(entry.undated(), 1.0);
+        assert_eq!(entry.last_seen, (MAX_TIMESTAMPS as u64 + 2) * 10);
+    }
+}
@@ -76,8 +76,8 @@ export function AliasForm({
             return false;
         }
 
-        if (!/^[a-z][\w-]*$/i.test(value)) {
-            setNameError('Must start with a letter; only letters, numbers, hyphens, and underscores');
+        if (!/^[a-z][a-z0-9-]*$/i.test(value)) {
+            setNameError('Must start with a letter; only letters, numbers, and hyphens');
             return false;
         }
         setNameError('');